@@ -15,7 +15,7 @@
 use autocxx_parser::IncludeCppConfig;
 
 use super::{
-    fun::{FnAnalysis, FnKind, FnPhase, MethodKind},
+    fun::{reject_unconstructible_out_params, FnAnalysis, FnKind, FnPhase, MethodKind},
     pod::PodAnalysis,
 };
 use crate::conversion::{api::TypeKind, error_reporter::convert_item_apis, ConvertError};
@@ -91,6 +91,10 @@ pub(crate) fn mark_types_abstract(
         } if abstract_types.contains(self_ty))
     });
 
+    // Nor can the C++ wrapper for a function construct an abstract type
+    // as an out parameter.
+    let apis = reject_unconstructible_out_params(apis, &abstract_types);
+
     // Finally, if there are any types which are nested inside other types,
     // they can't be abstract. This is due to two small limitations in cxx.
     // Imagine we have class Foo { class Bar }
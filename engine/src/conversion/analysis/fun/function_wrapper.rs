@@ -13,16 +13,29 @@
 // limitations under the License.
 
 use crate::{
-    conversion::api::SubclassName,
+    conversion::{api::SubclassName, ConvertError},
     types::{Namespace, QualifiedName},
 };
-use syn::{parse_quote, Ident, Type};
+use syn::{parse_quote, Ident, Type, TypePtr};
 
 #[derive(Clone, Debug)]
 pub(crate) enum CppConversionType {
     None,
     FromUniquePtrToValue,
     FromValueToUniquePtr,
+    /// A reference is passed from Rust, and the C++ API wants a pointer
+    /// (which is known to be non-null).
+    FromReferenceToPointer,
+    /// A C++ API returns a pointer known to be non-null, which we pass
+    /// to Rust as a reference.
+    FromPointerToReference,
+    /// A C++ API fills in a value via a pointer or reference parameter.
+    /// The C++ wrapper allocates that value in space provided by Rust -
+    /// either uninitialized memory (for POD types) or a `std::unique_ptr`.
+    OutParam {
+        pod: bool,
+        via_reference: bool,
+    },
 }
 
 impl CppConversionType {
@@ -31,15 +44,27 @@ impl CppConversionType {
             CppConversionType::None => CppConversionType::None,
             CppConversionType::FromUniquePtrToValue => CppConversionType::FromValueToUniquePtr,
             CppConversionType::FromValueToUniquePtr => CppConversionType::FromUniquePtrToValue,
+            CppConversionType::FromReferenceToPointer => CppConversionType::FromPointerToReference,
+            CppConversionType::FromPointerToReference => CppConversionType::FromReferenceToPointer,
+            CppConversionType::OutParam { .. } => self.clone(),
         }
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub(crate) enum RustConversionType {
     None,
     FromStr,
     ToBoxedUpHolder(SubclassName),
+    /// Rust callers pass an `Option` of a reference (the contained type),
+    /// which we turn into a possibly-null pointer.
+    FromOptionToPointer(Type),
+    /// A possibly-null pointer is returned to Rust callers as an `Option`
+    /// of a reference (the contained type).
+    FromPointerToOption(Type),
+    /// The parameter is hidden from Rust callers, and its value is instead
+    /// returned from the Rust wrapper function.
+    OutParam,
 }
 
 /// A policy for converting types. Conversion may occur on both the Rust and
@@ -85,6 +110,48 @@ impl TypeConversionPolicy {
         }
     }
 
+    pub(crate) fn new_from_reference_to_pointer(ty: Type) -> Self {
+        TypeConversionPolicy {
+            unwrapped_type: ty,
+            cpp_conversion: CppConversionType::FromReferenceToPointer,
+            rust_conversion: RustConversionType::None,
+        }
+    }
+
+    pub(crate) fn new_from_pointer_to_reference(ty: Type) -> Self {
+        TypeConversionPolicy {
+            unwrapped_type: ty,
+            cpp_conversion: CppConversionType::FromPointerToReference,
+            rust_conversion: RustConversionType::None,
+        }
+    }
+
+    pub(crate) fn new_from_option(ty: Type) -> Result<Self, ConvertError> {
+        let option_type = option_of_reference_type(&ty)?;
+        Ok(TypeConversionPolicy {
+            unwrapped_type: ty,
+            cpp_conversion: CppConversionType::None,
+            rust_conversion: RustConversionType::FromOptionToPointer(option_type),
+        })
+    }
+
+    pub(crate) fn new_to_option(ty: Type) -> Result<Self, ConvertError> {
+        let option_type = option_of_reference_type(&ty)?;
+        Ok(TypeConversionPolicy {
+            unwrapped_type: ty,
+            cpp_conversion: CppConversionType::None,
+            rust_conversion: RustConversionType::FromPointerToOption(option_type),
+        })
+    }
+
+    pub(crate) fn new_out_param(ty: Type, pod: bool, via_reference: bool) -> Self {
+        TypeConversionPolicy {
+            unwrapped_type: ty,
+            cpp_conversion: CppConversionType::OutParam { pod, via_reference },
+            rust_conversion: RustConversionType::OutParam,
+        }
+    }
+
     pub(crate) fn new_from_str(ty: Type) -> Self {
         TypeConversionPolicy {
             unwrapped_type: ty,
@@ -115,11 +182,23 @@ impl TypeConversionPolicy {
     pub(crate) fn converted_rust_type(&self) -> Type {
         match self.cpp_conversion {
             CppConversionType::FromUniquePtrToValue => self.make_unique_ptr_type(),
+            CppConversionType::OutParam { pod: true, .. } => {
+                let innerty = &self.unwrapped_type;
+                parse_quote! {
+                    *mut #innerty
+                }
+            }
+            CppConversionType::OutParam { pod: false, .. } => {
+                let innerty = self.make_unique_ptr_type();
+                parse_quote! {
+                    &mut #innerty
+                }
+            }
             _ => self.unwrapped_type.clone(),
         }
     }
 
-    fn make_unique_ptr_type(&self) -> Type {
+    pub(crate) fn make_unique_ptr_type(&self) -> Type {
         let innerty = &self.unwrapped_type;
         parse_quote! {
             cxx::UniquePtr < #innerty >
@@ -130,6 +209,29 @@ impl TypeConversionPolicy {
         !matches!(self.rust_conversion, RustConversionType::None)
     }
 
+    /// Whether this conversion hides a raw pointer from Rust callers,
+    /// such that the cxx::bridge function needs to be `unsafe` even though
+    /// the Rust wrapper function does not.
+    pub(crate) fn wraps_pointer_in_option(&self) -> bool {
+        matches!(
+            self.rust_conversion,
+            RustConversionType::FromOptionToPointer(_) | RustConversionType::FromPointerToOption(_)
+        )
+    }
+
+    pub(crate) fn is_out_param(&self) -> bool {
+        matches!(self.rust_conversion, RustConversionType::OutParam)
+    }
+
+    /// Whether this is an out parameter which is passed to the
+    /// cxx::bridge function as a raw pointer to uninitialized memory.
+    pub(crate) fn is_pod_out_param(&self) -> bool {
+        matches!(
+            self.cpp_conversion,
+            CppConversionType::OutParam { pod: true, .. }
+        )
+    }
+
     pub(crate) fn inverse(&self) -> Self {
         Self {
             unwrapped_type: self.unwrapped_type.clone(),
@@ -146,6 +248,10 @@ pub(crate) enum CppFunctionBody {
     StaticMethodCall(Namespace, Ident, Ident),
     Constructor,
     ConstructSuperclass(String),
+    /// Read a field of the receiver.
+    FieldGet(Ident),
+    /// Write a field of the receiver.
+    FieldSet(Ident),
 }
 
 #[derive(Clone)]
@@ -167,3 +273,21 @@ pub(crate) struct CppFunction {
     pub(crate) pass_obs_field: bool,
     pub(crate) qualification: Option<QualifiedName>,
 }
+
+/// Turn a `*const T` into `Option<&T>`, or `*mut T` into
+/// `Option<Pin<&mut T>>`.
+fn option_of_reference_type(ty: &Type) -> Result<Type, ConvertError> {
+    match ty {
+        Type::Ptr(TypePtr {
+            elem,
+            mutability: Some(_),
+            ..
+        }) => Ok(parse_quote! {
+            Option<::std::pin::Pin<&mut #elem>>
+        }),
+        Type::Ptr(TypePtr { elem, .. }) => Ok(parse_quote! {
+            Option<&#elem>
+        }),
+        _ => Err(ConvertError::NullabilityOnNonPointer),
+    }
+}
@@ -24,23 +24,24 @@ use crate::{
             fun::function_wrapper::CppFunctionKind,
             type_converter::{self, add_analysis, TypeConversionContext, TypeConverter},
         },
-        api::{ApiName, FuncToConvert, SubclassName},
+        api::{ApiName, FieldAccessor, FuncToConvert, SubclassName},
         convert_error::ConvertErrorWithContext,
         convert_error::ErrorContext,
         error_reporter::{convert_apis, report_any_error},
+        naming_policy::to_snake_case,
     },
     known_types::known_types,
     types::validate_ident_ok_for_rust,
 };
 use std::collections::{HashMap, HashSet};
 
-use autocxx_parser::{IncludeCppConfig, UnsafePolicy};
-use function_wrapper::{CppFunction, CppFunctionBody, TypeConversionPolicy};
+use autocxx_parser::{IncludeCppConfig, Nullability, UnsafePolicy};
+use function_wrapper::{CppConversionType, CppFunction, CppFunctionBody, TypeConversionPolicy};
 use itertools::Itertools;
 use proc_macro2::Span;
 use syn::{
-    parse_quote, punctuated::Punctuated, token::Comma, FnArg, Ident, Pat, ReturnType, Type,
-    TypePtr, Visibility,
+    parse_quote, punctuated::Punctuated, token::Comma, FnArg, Ident, Pat, PatType, ReturnType,
+    Type, TypePtr, Visibility,
 };
 
 use crate::{
@@ -154,8 +155,11 @@ pub(crate) struct FnAnalyzer<'a> {
     config: &'a IncludeCppConfig,
     overload_trackers_by_mod: HashMap<Namespace, OverloadTracker>,
     subclasses_by_superclass: HashMap<QualifiedName, Vec<SubclassName>>,
+    subclasses_by_primary_superclass: HashMap<QualifiedName, Vec<SubclassName>>,
     has_unrepresentable_constructors: HashSet<QualifiedName>,
     nested_type_name_map: HashMap<QualifiedName, String>,
+    policy_method_name_tracker: RustNameTracker,
+    policy_function_names: HashSet<String>,
 }
 
 impl<'a> FnAnalyzer<'a> {
@@ -173,9 +177,12 @@ impl<'a> FnAnalyzer<'a> {
             config,
             overload_trackers_by_mod: HashMap::new(),
             pod_safe_types: Self::build_pod_safe_type_set(&apis),
-            subclasses_by_superclass: subclass::subclasses_by_superclass(&apis),
+            subclasses_by_superclass: subclass::subclasses_by_superclass(&apis, false),
+            subclasses_by_primary_superclass: subclass::subclasses_by_superclass(&apis, true),
             has_unrepresentable_constructors: HashSet::new(),
             nested_type_name_map: Self::build_nested_type_map(&apis),
+            policy_method_name_tracker: RustNameTracker::new(),
+            policy_function_names: HashSet::new(),
         };
         let mut results = Vec::new();
         convert_apis(
@@ -186,9 +193,14 @@ impl<'a> FnAnalyzer<'a> {
             Api::enum_unchanged,
             Api::typedef_unchanged,
         );
+        me.add_union_accessors(&mut results);
         me.add_missing_make_uniques(&mut results);
         results.extend(me.extra_apis.into_iter().map(add_analysis));
-        results
+        let types_without_default_constructors = Self::find_types_without_default_constructors(
+            &results,
+            &me.has_unrepresentable_constructors,
+        );
+        reject_unconstructible_out_params(results, &types_without_default_constructors)
     }
 
     fn build_pod_safe_type_set(apis: &[Api<PodPhase>]) -> HashSet<QualifiedName> {
@@ -268,6 +280,42 @@ impl<'a> FnAnalyzer<'a> {
         self.rust_name_tracker.ok_to_use_rust_name(rust_name)
     }
 
+    /// Applies any `naming_policy!` to the Rust name of a function or method,
+    /// returning the new name and whether it changed. Constructors are
+    /// left alone since `make_unique` already follows Rust conventions.
+    fn apply_naming_policy(
+        &mut self,
+        kind: &FnKind,
+        rust_name: String,
+        cpp_name: &mut Option<String>,
+    ) -> Result<(String, bool), ConvertError> {
+        if !self.config.naming_policy().snake_case_functions
+            || matches!(kind, FnKind::Method(_, MethodKind::Constructor))
+        {
+            return Ok((rust_name, false));
+        }
+        let new_name = to_snake_case(&rust_name);
+        let renamed = new_name != rust_name;
+        if let FnKind::Method(self_ty, _) = kind {
+            // Methods live in a per-type namespace, so the overload tracker
+            // has ensured their original names are unique within the type.
+            // Snake-casing may break that.
+            let qualified_name = format!("{}::{}", self_ty.to_cpp_name(), new_name);
+            if !self
+                .policy_method_name_tracker
+                .ok_to_use_rust_name(&qualified_name)
+            {
+                return Err(ConvertError::NamingPolicyCollision(new_name));
+            }
+        } else if renamed {
+            self.policy_function_names.insert(new_name.clone());
+        }
+        if renamed && cpp_name.is_none() {
+            *cpp_name = Some(rust_name);
+        }
+        Ok((new_name, renamed))
+    }
+
     fn is_on_allowlist(&self, type_name: &QualifiedName) -> bool {
         self.config.is_on_allowlist(&type_name.to_cpp_name())
     }
@@ -295,7 +343,13 @@ impl<'a> FnAnalyzer<'a> {
         // Consider whether we need to synthesize subclass items.
         match &analysis.kind {
             FnKind::Method(sup, MethodKind::Constructor) => {
-                for sub in self.subclasses_by_superclass(sup) {
+                // Other superclasses are default-constructed. Interface
+                // implementations are always default-constructed.
+                let config = self.config;
+                for sub in self
+                    .subclasses_by_primary_superclass(sup)
+                    .filter(|sub| !config.is_interface_impl(&sub.id()))
+                {
                     // Add a constructor to the actual subclass definition in pure C++
                     results.push(create_subclass_constructor(&sub, &analysis, sup));
                     // And consider adding an API (in Rust/cxx/maybe C++) such that we
@@ -320,13 +374,29 @@ impl<'a> FnAnalyzer<'a> {
                 | MethodKind::PureVirtual(receiver_mutability),
             ) => {
                 for sub in self.subclasses_by_superclass(sup) {
+                    if self.config.is_interface_impl(&sub.id()) {
+                        // A Rust trait object implementing an interface
+                        // only needs to forward the pure virtual methods,
+                        // and has no superclass methods to call.
+                        if matches!(analysis.kind, FnKind::Method(_, MethodKind::PureVirtual(_))) {
+                            results.push(create_subclass_function(
+                                &sub,
+                                &analysis,
+                                &name,
+                                receiver_mutability,
+                                sup,
+                                &name.name,
+                            ));
+                        }
+                        continue;
+                    }
                     // For each subclass, we need to create a plain-C++ method to call its superclass
                     // and a Rust/C++ bridge API to call _that_.
                     // What we're generating here is entirely about the subclass, so the
-                    // superclass's namespace is irrelevant. We generate
-                    // all subclasses in the root namespace.
+                    // superclass's namespace is irrelevant. We generate it in
+                    // the subclass's namespace.
                     let super_fn_name =
-                        SubclassName::get_super_fn_name(&Namespace::new(), &analysis.rust_name);
+                        SubclassName::get_super_fn_name(sub.cpp_namespace(), &analysis.rust_name);
 
                     results.push(create_subclass_function(
                         &sub,
@@ -383,7 +453,6 @@ impl<'a> FnAnalyzer<'a> {
         name: ApiName,
         fun: Box<FuncToConvert>,
     ) -> Result<Option<(FnAnalysis, ApiName)>, ConvertErrorWithContext> {
-        let virtual_this = &fun.virtual_this_type;
         let mut cpp_name = name.cpp_name.clone();
         let ns = name.name.get_namespace();
 
@@ -402,15 +471,7 @@ impl<'a> FnAnalyzer<'a> {
         let (param_details, bads): (Vec<_>, Vec<_>) = fun
             .inputs
             .iter()
-            .map(|i| {
-                self.convert_fn_arg(
-                    i,
-                    ns,
-                    diagnostic_display_name,
-                    virtual_this.clone(),
-                    &fun.reference_args,
-                )
-            })
+            .map(|i| self.convert_fn_arg(i, ns, diagnostic_display_name, &fun))
             .partition(Result::is_ok);
         let (mut params, mut param_details): (Punctuated<_, Comma>, Vec<_>) =
             param_details.into_iter().map(Result::unwrap).unzip();
@@ -426,9 +487,6 @@ impl<'a> FnAnalyzer<'a> {
             .next()
             .cloned();
 
-        let requires_unsafe =
-            self.should_be_unsafe() || param_details.iter().any(|pd| pd.requires_unsafe);
-
         // End of parameter processing.
         // Work out naming, part one.
         // bindgen may have mangled the name either because it's invalid Rust
@@ -608,6 +666,12 @@ impl<'a> FnAnalyzer<'a> {
             _ => {}
         };
 
+        // If the user has asked for Rust naming conventions, the Rust name
+        // may now diverge from the C++ name, so note the latter.
+        let (rust_name, renamed_by_policy) = self
+            .apply_naming_policy(&kind, rust_name, &mut cpp_name)
+            .map_err(contextualize_error)?;
+
         // Analyze the return type, just as we previously did for the
         // parameters.
         let mut return_analysis = if let FnKind::Method(ref self_ty, MethodKind::Constructor) = kind
@@ -624,30 +688,103 @@ impl<'a> FnAnalyzer<'a> {
                 deps: std::iter::once(self_ty).cloned().collect(),
             }
         } else {
-            self.convert_return_type(&fun.output, ns, fun.return_type_is_reference)
-                .map_err(contextualize_error)?
+            let return_type_nullability = match &fun.output {
+                ReturnType::Type(_, ty) if !fun.return_type_is_reference => {
+                    self.unannotated_pointer_nullability(ty)
+                }
+                _ => None,
+            };
+            self.convert_return_type(
+                &fun.output,
+                ns,
+                fun.return_type_is_reference,
+                return_type_nullability,
+            )
+            .map_err(contextualize_error)?
         };
         let mut deps = params_deps;
         deps.extend(return_analysis.deps.drain());
 
+        // Turn any out parameter into a return value.
+        let out_param_fn_name = match kind {
+            FnKind::Method(ref self_ty, _) => format!(
+                "{}::{}",
+                self_ty.to_cpp_name(),
+                cpp_name.as_ref().unwrap_or(&rust_name)
+            ),
+            FnKind::Function => {
+                QualifiedName::new(ns, make_ident(cpp_name.as_ref().unwrap_or(&rust_name)))
+                    .to_cpp_name()
+            }
+        };
+        self.apply_out_param(
+            &fun,
+            &out_param_fn_name,
+            &kind,
+            ns,
+            return_analysis.was_reference,
+            &mut param_details,
+        )
+        .map_err(contextualize_error)?;
+
+        // Pointers which we present to Rust callers as Options don't make
+        // the Rust API unsafe, even though the cxx::bridge API must be.
+        let requires_unsafe = self.should_be_unsafe()
+            || matches!(fun.field_accessor, Some(FieldAccessor::GetUnionMember(_)))
+            || param_details
+                .iter()
+                .any(|pd| pd.requires_unsafe && !pd.conversion.wraps_pointer_in_option());
+
         if return_analysis.was_reference {
             // cxx only allows functions to return a reference if they take exactly
             // one reference as a parameter. Let's see...
             let num_input_references = param_details.iter().filter(|pd| pd.was_reference).count();
             if num_input_references != 1 {
-                return Err(contextualize_error(ConvertError::NotOneInputReference(
-                    rust_name,
-                )));
+                let from_nonnull_pointer = return_analysis.conversion.as_ref().map_or(false, |c| {
+                    matches!(c.cpp_conversion, CppConversionType::FromPointerToReference)
+                });
+                if !from_nonnull_pointer {
+                    return Err(contextualize_error(ConvertError::NotOneInputReference(
+                        rust_name,
+                    )));
+                }
+                // As for nullable pointers below, if we can't return a
+                // reference, stick with a raw pointer.
+                return_analysis = self
+                    .convert_return_type(&fun.output, ns, false, None)
+                    .map_err(contextualize_error)?;
             }
         }
         let mut ret_type = return_analysis.rt;
-        let ret_type_conversion = return_analysis.conversion;
+        let mut ret_type_conversion = return_analysis.conversion;
+        if let Some(conversion) = &ret_type_conversion {
+            if conversion.wraps_pointer_in_option() {
+                // Similarly, we can only return an Option<&T> if lifetime elision
+                // can figure out where the reference came from. Otherwise,
+                // stick with a raw pointer.
+                let num_input_references = param_details
+                    .iter()
+                    .filter(|pd| pd.was_reference || pd.conversion.wraps_pointer_in_option())
+                    .count();
+                if num_input_references != 1 {
+                    ret_type_conversion = Some(TypeConversionPolicy::new_unconverted(
+                        conversion.unwrapped_type.clone(),
+                    ));
+                }
+            }
+        }
 
         // Do we need to convert either parameters or return type?
         let param_conversion_needed = param_details.iter().any(|b| b.conversion.cpp_work_needed());
         let ret_type_conversion_needed = ret_type_conversion
             .as_ref()
             .map_or(false, |x| x.cpp_work_needed());
+        let rust_conversion_needed = param_details
+            .iter()
+            .any(|b| b.conversion.rust_work_needed())
+            || ret_type_conversion
+                .as_ref()
+                .map_or(false, |x| x.rust_work_needed());
         // See https://github.com/dtolnay/cxx/issues/878 for the reason for this next line.
         let effective_cpp_name = cpp_name.as_ref().unwrap_or(&rust_name);
         let cpp_name_incompatible_with_cxx =
@@ -658,10 +795,14 @@ impl<'a> FnAnalyzer<'a> {
         // That wrapper function is included in the cxx::bridge, and calls through to the
         // original function.
         let wrapper_function_needed = match kind {
+            _ if fun.field_accessor.is_some() => true,
             FnKind::Method(_, MethodKind::Static)
             | FnKind::Method(_, MethodKind::Virtual(_))
             | FnKind::Method(_, MethodKind::PureVirtual(_)) => true,
             FnKind::Method(..) if cxxbridge_name != rust_name => true,
+            // Rust wrappers for methods call a free function in the cxx::bridge,
+            // so we need a C++ wrapper too.
+            FnKind::Method(..) if rust_conversion_needed => true,
             _ if param_conversion_needed => true,
             _ if ret_type_conversion_needed => true,
             _ if cpp_name_incompatible_with_cxx => true,
@@ -678,9 +819,18 @@ impl<'a> FnAnalyzer<'a> {
                 "_"
             };
             cxxbridge_name = make_ident(&format!("{}{}autocxx_wrapper", cxxbridge_name, joiner));
-            let (payload, has_receiver) = match kind {
-                FnKind::Method(_, MethodKind::Constructor) => (CppFunctionBody::Constructor, false),
-                FnKind::Method(ref self_ty, MethodKind::Static) => (
+            let (payload, has_receiver) = match (&fun.field_accessor, &kind) {
+                (Some(FieldAccessor::Get(field)), _)
+                | (Some(FieldAccessor::GetUnionMember(field)), _) => {
+                    (CppFunctionBody::FieldGet(field.clone()), true)
+                }
+                (Some(FieldAccessor::Set(field)), _) => {
+                    (CppFunctionBody::FieldSet(field.clone()), true)
+                }
+                (_, FnKind::Method(_, MethodKind::Constructor)) => {
+                    (CppFunctionBody::Constructor, false)
+                }
+                (_, FnKind::Method(self_ty, MethodKind::Static)) => (
                     CppFunctionBody::StaticMethodCall(
                         ns.clone(),
                         self_ty.get_final_ident(),
@@ -688,7 +838,7 @@ impl<'a> FnAnalyzer<'a> {
                     ),
                     false,
                 ),
-                FnKind::Method(..) => (
+                (_, FnKind::Method(..)) => (
                     CppFunctionBody::FunctionCall(ns.clone(), cpp_construction_ident),
                     true,
                 ),
@@ -752,6 +902,15 @@ impl<'a> FnAnalyzer<'a> {
                 // There is a global space of rust_names even if they're in
                 // different namespaces.
                 let rust_name_ok = self.ok_to_use_rust_name(&rust_name);
+                if !rust_name_ok
+                    && (renamed_by_policy || self.policy_function_names.contains(&rust_name))
+                {
+                    // Falling back to a different name would defeat the
+                    // point of the naming policy.
+                    return Err(contextualize_error(ConvertError::NamingPolicyCollision(
+                        rust_name,
+                    )));
+                }
                 if cxxbridge_name == rust_name {
                     (rust_name_ident, RustRenameStrategy::None)
                 } else if rust_name_ok {
@@ -793,13 +952,22 @@ impl<'a> FnAnalyzer<'a> {
         }
     }
 
+    fn subclasses_by_primary_superclass(
+        &self,
+        sup: &QualifiedName,
+    ) -> impl Iterator<Item = SubclassName> {
+        match self.subclasses_by_primary_superclass.get(sup) {
+            Some(subs) => subs.clone().into_iter(),
+            None => Vec::new().into_iter(),
+        }
+    }
+
     fn convert_fn_arg(
         &mut self,
         arg: &FnArg,
         ns: &Namespace,
         fn_name: &str,
-        virtual_this: Option<QualifiedName>,
-        reference_args: &HashSet<Ident>,
+        fun: &FuncToConvert,
     ) -> Result<(FnArg, ArgumentAnalysis), ConvertError> {
         Ok(match arg {
             FnArg::Typed(pt) => {
@@ -808,6 +976,7 @@ impl<'a> FnAnalyzer<'a> {
                 let old_pat = *pt.pat;
                 let mut is_virtual = false;
                 let mut treat_as_reference = false;
+                let mut nullability = None;
                 let new_pat = match old_pat {
                     syn::Pat::Ident(mut pp) if pp.ident == "this" => {
                         let this_type = match pt.ty.as_ref() {
@@ -823,12 +992,13 @@ impl<'a> FnAnalyzer<'a> {
                                     let mut this_type = QualifiedName::from_type_path(typ);
                                     if this_type.is_cvoid() && pp.ident == "this" {
                                         is_virtual = true;
-                                        this_type = virtual_this.ok_or_else(|| {
-                                            ConvertError::VirtualThisType(
-                                                ns.clone(),
-                                                fn_name.into(),
-                                            )
-                                        })?;
+                                        this_type =
+                                            fun.virtual_this_type.clone().ok_or_else(|| {
+                                                ConvertError::VirtualThisType(
+                                                    ns.clone(),
+                                                    fn_name.into(),
+                                                )
+                                            })?;
                                         let this_type_path = this_type.to_type_path();
                                         let const_token = if mutability.is_some() {
                                             None
@@ -855,7 +1025,13 @@ impl<'a> FnAnalyzer<'a> {
                     }
                     syn::Pat::Ident(pp) => {
                         validate_ident_ok_for_cxx(&pp.ident.to_string())?;
-                        treat_as_reference = reference_args.contains(&pp.ident);
+                        treat_as_reference = fun.reference_args.contains(&pp.ident);
+                        if !treat_as_reference {
+                            nullability = self.unannotated_pointer_nullability(&pt.ty);
+                            if matches!(nullability, Some(Nullability::NonNull)) {
+                                treat_as_reference = true;
+                            }
+                        }
                         syn::Pat::Ident(pp)
                     }
                     _ => old_pat,
@@ -866,8 +1042,15 @@ impl<'a> FnAnalyzer<'a> {
                     type_converter::TypeKind::SubclassHolder(holder) => Some(holder),
                     _ => None,
                 };
-                let conversion =
-                    self.argument_conversion_details(&new_ty, &subclass_holder.cloned());
+                let conversion = match (nullability, &annotated_type.kind) {
+                    (Some(Nullability::NonNull), _) => {
+                        TypeConversionPolicy::new_from_reference_to_pointer(*new_ty.clone())
+                    }
+                    (Some(Nullability::Nullable), type_converter::TypeKind::Pointer) => {
+                        TypeConversionPolicy::new_from_option(*new_ty.clone())?
+                    }
+                    _ => self.argument_conversion_details(&new_ty, &subclass_holder.cloned()),
+                };
                 pt.pat = Box::new(new_pat.clone());
                 pt.ty = new_ty;
                 (
@@ -894,16 +1077,109 @@ impl<'a> FnAnalyzer<'a> {
         })
     }
 
+    /// Spot any parameter which the user has asked us (or which our
+    /// heuristics suggest) to treat as an out parameter. The C++ wrapper
+    /// function will then allocate the value, and Rust callers receive it
+    /// as a return value rather than passing in a pointer.
+    fn apply_out_param(
+        &mut self,
+        fun: &FuncToConvert,
+        fn_name: &str,
+        kind: &FnKind,
+        ns: &Namespace,
+        return_was_reference: bool,
+        param_details: &mut [ArgumentAnalysis],
+    ) -> Result<(), ConvertError> {
+        let unsupported = return_was_reference
+            || matches!(
+                kind,
+                FnKind::Method(
+                    _,
+                    MethodKind::Constructor | MethodKind::Virtual(_) | MethodKind::PureVirtual(_)
+                )
+            );
+        let param_name = |pd: &ArgumentAnalysis| match &pd.name {
+            Pat::Ident(pp) if pd.self_type.is_none() => Some(pp.ident.clone()),
+            _ => None,
+        };
+        let mut explicit = param_details.iter().enumerate().filter(|(_, pd)| {
+            param_name(pd).map_or(false, |id| {
+                self.config.is_out_param(fn_name, &id.to_string())
+            })
+        });
+        let (idx, is_explicit) = match (explicit.next(), explicit.next()) {
+            (Some(_), _) if unsupported => {
+                return Err(ConvertError::UnsupportedOutParam(fn_name.into()))
+            }
+            (Some(_), Some(_)) => return Err(ConvertError::UnsupportedOutParam(fn_name.into())),
+            (Some((idx, _)), None) => (idx, true),
+            (None, _) if self.config.detect_out_params() && !unsupported => {
+                match param_details.last().and_then(param_name) {
+                    Some(id) if looks_like_out_param(&id.to_string()) => {
+                        (param_details.len() - 1, false)
+                    }
+                    _ => return Ok(()),
+                }
+            }
+            (None, _) => return Ok(()),
+        };
+        let not_an_out_param = || {
+            if is_explicit {
+                Err(ConvertError::UnsupportedOutParam(fn_name.into()))
+            } else {
+                Ok(())
+            }
+        };
+        let (ident, ty) = match &fun.inputs[idx] {
+            FnArg::Typed(PatType { pat, ty, .. }) => match pat.as_ref() {
+                Pat::Ident(pp) => (pp.ident.clone(), ty.clone()),
+                _ => return not_an_out_param(),
+            },
+            _ => return not_an_out_param(),
+        };
+        if !matches!(
+            ty.as_ref(),
+            Type::Ptr(TypePtr {
+                mutability: Some(_),
+                ..
+            })
+        ) {
+            return not_an_out_param();
+        }
+        let annotated_type = self.convert_boxed_type(ty, ns, false)?;
+        let pointee = match *annotated_type.ty {
+            Type::Ptr(TypePtr { elem, .. }) => *elem,
+            _ => return not_an_out_param(),
+        };
+        let tn = match &pointee {
+            Type::Path(typ) => QualifiedName::from_type_path(typ),
+            _ => return not_an_out_param(),
+        };
+        if tn.is_cvoid() {
+            return not_an_out_param();
+        }
+        let pod = self.pod_safe_types.contains(&tn);
+        let via_reference = fun.reference_args.contains(&ident);
+        let pd = &mut param_details[idx];
+        pd.conversion = TypeConversionPolicy::new_out_param(pointee, pod, via_reference);
+        pd.was_reference = false;
+        pd.requires_unsafe = false;
+        Ok(())
+    }
+
     fn argument_conversion_details(
         &self,
         ty: &Type,
         is_subclass_holder: &Option<Ident>,
     ) -> TypeConversionPolicy {
         if let Some(holder_id) = is_subclass_holder {
-            let subclass = SubclassName::from_holder_name(holder_id);
+            let subclass = SubclassName::from_holder_name(holder_id, self.config);
+            // The holder may be in a C++ namespace, which we need to name
+            // in C++ even though Rust refers to it unqualified.
+            let holder_path = subclass.qualified_holder().to_type_path();
             return TypeConversionPolicy::box_up_subclass_holder(
                 parse_quote! {
-                    rust::Box<#holder_id>
+                    rust::Box<#holder_path>
                 },
                 subclass,
             );
@@ -939,11 +1215,25 @@ impl<'a> FnAnalyzer<'a> {
         }
     }
 
+    /// The nullability which `unannotated_pointers!` tells us to assume for
+    /// a parameter or return value of this type, if it's a pointer. This
+    /// doesn't apply to `void*`, C strings or pointers to pointers, since
+    /// those don't point to a single object which we could refer to.
+    fn unannotated_pointer_nullability(&self, ty: &Type) -> Option<Nullability> {
+        match ty {
+            Type::Ptr(TypePtr { elem, .. }) if points_to_single_object(elem) => {
+                self.config.unannotated_pointer_nullability()
+            }
+            _ => None,
+        }
+    }
+
     fn convert_return_type(
         &mut self,
         rt: &ReturnType,
         ns: &Namespace,
         convert_ptr_to_reference: bool,
+        nullability: Option<Nullability>,
     ) -> Result<ReturnTypeAnalysis, ConvertError> {
         let result = match rt {
             ReturnType::Default => ReturnTypeAnalysis {
@@ -953,12 +1243,24 @@ impl<'a> FnAnalyzer<'a> {
                 deps: HashSet::new(),
             },
             ReturnType::Type(rarrow, boxed_type) => {
+                let is_pointer = matches!(boxed_type.as_ref(), Type::Ptr(..));
+                let nullability = if is_pointer { nullability } else { None };
+                let convert_ptr_to_reference =
+                    convert_ptr_to_reference || matches!(nullability, Some(Nullability::NonNull));
                 // TODO remove the below clone
                 let annotated_type =
                     self.convert_boxed_type(boxed_type.clone(), ns, convert_ptr_to_reference)?;
                 let boxed_type = annotated_type.ty;
                 let was_reference = matches!(boxed_type.as_ref(), Type::Reference(_));
-                let conversion = self.return_type_conversion_details(boxed_type.as_ref());
+                let conversion = match nullability {
+                    Some(Nullability::NonNull) => {
+                        TypeConversionPolicy::new_from_pointer_to_reference(*boxed_type.clone())
+                    }
+                    Some(Nullability::Nullable) => {
+                        TypeConversionPolicy::new_to_option(*boxed_type.clone())?
+                    }
+                    None => self.return_type_conversion_details(boxed_type.as_ref()),
+                };
                 ReturnTypeAnalysis {
                     rt: ReturnType::Type(*rarrow, boxed_type),
                     conversion: Some(conversion),
@@ -1026,6 +1328,7 @@ impl<'a> FnAnalyzer<'a> {
                         return_type_is_reference: false,
                         reference_args: HashSet::new(),
                         original_name: None,
+                        field_accessor: None,
                     }),
                 )
             });
@@ -1033,14 +1336,151 @@ impl<'a> FnAnalyzer<'a> {
         }
     }
 
+    /// Find types which we know can't be default-constructed from outside
+    /// the class, because they have constructors but none of them is a
+    /// public default constructor.
+    fn find_types_without_default_constructors(
+        apis: &[Api<FnPhase>],
+        has_unrepresentable_constructors: &HashSet<QualifiedName>,
+    ) -> HashSet<QualifiedName> {
+        let mut types_with_constructors = has_unrepresentable_constructors.clone();
+        let mut default_constructible = HashSet::new();
+        for api in apis {
+            match api {
+                Api::Function {
+                    analysis:
+                        FnAnalysis {
+                            kind: FnKind::Method(self_ty, MethodKind::Constructor),
+                            param_details,
+                            ..
+                        },
+                    ..
+                } => {
+                    types_with_constructors.insert(self_ty.clone());
+                    if param_details.is_empty() {
+                        default_constructible.insert(self_ty.clone());
+                    }
+                }
+                Api::Struct {
+                    analysis:
+                        PodAnalysis {
+                            is_union: true,
+                            kind,
+                            ..
+                        },
+                    ..
+                } if *kind != TypeKind::Pod => {
+                    types_with_constructors.insert(api.name().clone());
+                }
+                _ => {}
+            }
+        }
+        &types_with_constructors - &default_constructible
+    }
+
     fn find_all_types(apis: &[Api<FnPhase>]) -> HashSet<QualifiedName> {
         apis.iter()
             .filter_map(|api| match api {
+                // C++ deletes the default constructor of a union with
+                // non-trivial members, so we can't synthesize one.
+                Api::Struct {
+                    analysis:
+                        PodAnalysis {
+                            is_union: true,
+                            kind,
+                            ..
+                        },
+                    ..
+                } if *kind != TypeKind::Pod => None,
                 Api::Struct { .. } => Some(api.name().clone()),
                 _ => None,
             })
             .collect::<HashSet<_>>()
     }
+
+    /// Rust can't represent a union by value if any of its members aren't
+    /// POD, so such unions are opaque. Here we synthesize accessors for
+    /// their members, implemented by C++ wrapper functions. Reading a member
+    /// is unsafe, as with Rust unions. We can only offer setters for POD
+    /// members, and getters for other members return a reference.
+    fn add_union_accessors(&mut self, apis: &mut Vec<Api<FnPhase>>) {
+        let unions: Vec<_> = apis
+            .iter()
+            .filter_map(|api| match api {
+                Api::Struct {
+                    item,
+                    analysis:
+                        PodAnalysis {
+                            is_union: true,
+                            kind,
+                            ..
+                        },
+                    ..
+                } if *kind != TypeKind::Pod => Some((api.name().clone(), item.fields.clone())),
+                _ => None,
+            })
+            .collect();
+        for (self_ty, fields) in unions {
+            let ns = self_ty.get_namespace().clone();
+            let type_id = self_ty.get_final_ident();
+            let path = self_ty.to_type_path();
+            for field in fields.iter() {
+                let (field_id, ty) = match (&field.ident, &field.ty) {
+                    (Some(field_id), Type::Path(typ)) => (field_id, typ),
+                    _ => continue,
+                };
+                let is_pod = self
+                    .pod_safe_types
+                    .contains(&QualifiedName::from_type_path(ty));
+                let getter_output = if is_pod {
+                    parse_quote! { -> #ty }
+                } else {
+                    parse_quote! { -> *const #ty }
+                };
+                let mut accessors = vec![(
+                    field_id.to_string(),
+                    FieldAccessor::GetUnionMember(field_id.clone()),
+                    parse_quote! { this: *const #path },
+                    getter_output,
+                )];
+                if is_pod {
+                    accessors.push((
+                        format!("set_{}", field_id),
+                        FieldAccessor::Set(field_id.clone()),
+                        parse_quote! { this: *mut #path, val: #ty },
+                        ReturnType::Default,
+                    ));
+                }
+                for (method_name, field_accessor, inputs, output) in accessors {
+                    let ident = make_ident(format!("{}_{}", type_id, method_name));
+                    let fake_api_name = ApiName::new(&ns, ident.clone());
+                    let items = report_any_error(&ns, apis, || {
+                        self.analyze_foreign_fn_and_subclasses(
+                            fake_api_name,
+                            Box::new(FuncToConvert {
+                                virtual_this_type: None,
+                                self_ty: Some(self_ty.clone()),
+                                ident,
+                                doc_attr: None,
+                                inputs,
+                                output,
+                                vis: parse_quote! { pub },
+                                is_pure_virtual: false,
+                                is_private: false,
+                                is_move_constructor: false,
+                                unused_template_param: false,
+                                return_type_is_reference: !is_pod,
+                                reference_args: HashSet::new(),
+                                original_name: Some(method_name),
+                                field_accessor: Some(field_accessor),
+                            }),
+                        )
+                    });
+                    apis.extend(items.into_iter().flatten());
+                }
+            }
+        }
+    }
 }
 
 impl Api<FnPhase> {
@@ -1076,6 +1516,15 @@ impl Api<FnPhase> {
             | Api::RustSubclassConstructor { .. }
             | Api::RustSubclassFn { .. }
             | Api::Subclass { .. } => true,
+            Api::Struct {
+                analysis:
+                    PodAnalysis {
+                        kind: TypeKind::Pod,
+                        layout: Some(_),
+                        ..
+                    },
+                ..
+            } => true,
             _ => false,
         }
     }
@@ -1102,10 +1551,7 @@ impl Api<FnPhase> {
             } => Box::new(old_tyname.iter().chain(deps.iter())),
             Api::Struct { analysis, .. } => Box::new(analysis.field_deps.iter()),
             Api::Function { analysis, .. } => Box::new(analysis.deps.iter()),
-            Api::Subclass {
-                name: _,
-                superclass,
-            } => Box::new(std::iter::once(superclass)),
+            Api::Subclass { superclasses, .. } => Box::new(superclasses.iter()),
             Api::RustSubclassFn { details, .. } => Box::new(std::iter::once(&details.dependency)),
             _ => Box::new(std::iter::empty()),
         }
@@ -1115,3 +1561,76 @@ impl Api<FnPhase> {
         self.deps().join(",")
     }
 }
+
+impl FnAnalysis {
+    /// The type of any out parameter. The C++ wrapper function will
+    /// default-construct a value of this type before calling the function.
+    fn out_param_type(&self) -> Option<QualifiedName> {
+        self.param_details
+            .iter()
+            .find(|pd| pd.conversion.is_out_param())
+            .and_then(|pd| match &pd.conversion.unwrapped_type {
+                Type::Path(typ) => Some(QualifiedName::from_type_path(typ)),
+                _ => None,
+            })
+    }
+
+    fn error_context(&self) -> ErrorContext {
+        match &self.kind {
+            FnKind::Method(self_ty, _) => ErrorContext::Method {
+                self_ty: self_ty.get_final_ident(),
+                method: make_ident(&self.rust_name),
+            },
+            FnKind::Function => ErrorContext::Item(make_ident(&self.rust_name)),
+        }
+    }
+}
+
+/// Out parameters are default-constructed by the C++ wrapper function, so
+/// we can only support them for types which allow that. We don't know
+/// that until we've seen all the constructors (or, for abstract types, until
+/// a later analysis phase) so this is done after the function has otherwise
+/// been analyzed, replacing any such function with an explanation.
+pub(crate) fn reject_unconstructible_out_params(
+    apis: Vec<Api<FnPhase>>,
+    unconstructible_types: &HashSet<QualifiedName>,
+) -> Vec<Api<FnPhase>> {
+    let mut results = Vec::new();
+    for api in apis {
+        if let Api::Function { name, analysis, .. } = &api {
+            if let Some(ty) = analysis
+                .out_param_type()
+                .filter(|ty| unconstructible_types.contains(ty))
+            {
+                let ctx = analysis.error_context();
+                report_any_error(&name.name.get_namespace().clone(), &mut results, || {
+                    Err::<(), _>(ConvertErrorWithContext(
+                        ConvertError::OutParamNotDefaultConstructible(ty),
+                        Some(ctx),
+                    ))
+                });
+                continue;
+            }
+        }
+        results.push(api);
+    }
+    results
+}
+
+/// Whether a pointer to this type points to a single object, rather than
+/// being a `void*`, a C string or a pointer to a pointer.
+fn points_to_single_object(pointee: &Type) -> bool {
+    match pointee {
+        Type::Ptr(..) => false,
+        Type::Path(typ) => !typ.path.segments.last().map_or(false, |seg| {
+            ["c_void", "c_char", "c_schar", "c_uchar"].contains(&seg.ident.to_string().as_str())
+        }),
+        _ => true,
+    }
+}
+
+/// Whether a parameter name suggests that it's an out parameter, for
+/// the purposes of `detect_out_params!()`.
+fn looks_like_out_param(name: &str) -> bool {
+    name == "out" || name.starts_with("out_") || name.ends_with("_out")
+}
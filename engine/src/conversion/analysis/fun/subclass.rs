@@ -26,22 +26,28 @@ use crate::{
         },
         api::{Api, ApiName},
     },
-    types::{make_ident, Namespace, QualifiedName},
+    types::{make_ident, QualifiedName},
 };
 
 use super::FnPhase;
 
+/// Finds the subclasses of each superclass, optionally considering only
+/// each subclass's primary superclass.
 pub(super) fn subclasses_by_superclass(
     apis: &[Api<PodPhase>],
+    primary_only: bool,
 ) -> HashMap<QualifiedName, Vec<SubclassName>> {
     let mut subclasses_per_superclass: HashMap<QualifiedName, Vec<SubclassName>> = HashMap::new();
 
     for api in apis.iter() {
-        if let Api::Subclass { name, superclass } = api {
-            subclasses_per_superclass
-                .entry(superclass.clone())
-                .or_default()
-                .push(name.clone());
+        if let Api::Subclass { name, superclasses } = api {
+            let count = if primary_only { 1 } else { superclasses.len() };
+            for superclass in superclasses.iter().take(count) {
+                subclasses_per_superclass
+                    .entry(superclass.clone())
+                    .or_default()
+                    .push(name.clone());
+            }
         }
     }
     subclasses_per_superclass
@@ -68,6 +74,7 @@ pub(super) fn create_subclass_fn_wrapper(
         original_name: None,
         return_type_is_reference: fun.return_type_is_reference,
         reference_args: fun.reference_args.clone(),
+        field_accessor: None,
     });
     let super_fn_name = ApiName::new_from_qualified_name(super_fn_name);
     (maybe_wrap, super_fn_name)
@@ -99,14 +106,14 @@ pub(super) fn create_subclass_function(
         CppFunctionKind::ConstMethod
     };
     let subclass_function: Api<FnPhase> = Api::RustSubclassFn {
-        name: ApiName::new_in_root_namespace(rust_call_name.clone()),
+        name: ApiName::new(sub.cpp_namespace(), rust_call_name.clone()),
         subclass: sub.clone(),
         details: Box::new(RustSubclassFnDetails {
             params,
             ret: analysis.ret_type.clone(),
             method_name: make_ident(&analysis.rust_name),
             cpp_impl: CppFunction {
-                payload: CppFunctionBody::FunctionCall(Namespace::new(), rust_call_name),
+                payload: CppFunctionBody::FunctionCall(sub.cpp_namespace().clone(), rust_call_name),
                 wrapper_function_name: name.name.get_final_ident(),
                 return_conversion: analysis.ret_conversion.clone(),
                 argument_conversion: analysis
@@ -174,8 +181,10 @@ pub(super) fn create_subclass_constructor_wrapper(
         unused_template_param: fun.unused_template_param,
         return_type_is_reference: fun.return_type_is_reference,
         reference_args: fun.reference_args.clone(),
+        field_accessor: None,
     });
-    let mut subclass_constructor_name = ApiName::new_in_root_namespace(subclass_constructor_name);
+    let mut subclass_constructor_name =
+        ApiName::new(sub.cpp_namespace(), subclass_constructor_name);
     subclass_constructor_name.cpp_name = Some(sub.cpp().get_final_item().to_string());
     (maybe_wrap, subclass_constructor_name)
 }
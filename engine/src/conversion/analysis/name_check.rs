@@ -12,24 +12,26 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
+use autocxx_parser::IncludeCppConfig;
 use syn::Ident;
 
 use crate::{
     conversion::{
         api::{Api, SubclassName},
         error_reporter::convert_item_apis,
+        naming_policy::to_upper_camel_case,
         ConvertError,
     },
-    types::{validate_ident_ok_for_cxx, QualifiedName},
+    types::{make_ident, validate_ident_ok_for_cxx, QualifiedName},
 };
 
 use super::fun::FnPhase;
 
 /// Do some final checks that the names we've come up with can be represented
 /// within cxx.
-pub(crate) fn check_names(apis: Vec<Api<FnPhase>>) -> Vec<Api<FnPhase>> {
+pub(crate) fn check_names(config: &IncludeCppConfig, apis: Vec<Api<FnPhase>>) -> Vec<Api<FnPhase>> {
     // If any items have names which can't be represented by cxx,
     // abort. This check should ideally be done at the times we fill in the
     // `name` field of each `api` in the first place, at parse time, though
@@ -55,10 +57,12 @@ pub(crate) fn check_names(apis: Vec<Api<FnPhase>>) -> Vec<Api<FnPhase>> {
         }
         Api::Subclass {
             name: SubclassName(ref name),
-            ref superclass,
+            ref superclasses,
         } => {
             validate_all_segments_ok_for_cxx(name.name.segment_iter())?;
-            validate_all_segments_ok_for_cxx(superclass.segment_iter())?;
+            for superclass in superclasses {
+                validate_all_segments_ok_for_cxx(superclass.segment_iter())?;
+            }
             Ok(Box::new(std::iter::once(api)))
         }
         Api::Function { ref name, .. } => {
@@ -102,9 +106,61 @@ pub(crate) fn check_names(apis: Vec<Api<FnPhase>>) -> Vec<Api<FnPhase>> {
             Ok(Box::new(std::iter::once(api)))
         }
     });
+    if config.naming_policy().upper_camel_case_types {
+        results = check_upper_camel_case_type_names(results);
+    }
     results
 }
 
+/// Types are given their UpperCamelCase name only in the output mod, so
+/// that name must not clash with any other item, nor with another type
+/// which would end up with the same name.
+fn check_upper_camel_case_type_names(apis: Vec<Api<FnPhase>>) -> Vec<Api<FnPhase>> {
+    let existing_names: HashSet<QualifiedName> =
+        apis.iter().map(|api| api.name().clone()).collect();
+    let mut new_names_found: HashMap<QualifiedName, usize> = HashMap::new();
+    for api in &apis {
+        if let Some(new_name) = upper_camel_case_type_name(api) {
+            *new_names_found.entry(new_name).or_default() += 1usize;
+        }
+    }
+    let mut results = Vec::new();
+    convert_item_apis(apis, &mut results, |api| {
+        if let Some(new_name) = upper_camel_case_type_name(&api) {
+            if existing_names.contains(&new_name) || new_names_found[&new_name] > 1usize {
+                return Err(ConvertError::NamingPolicyCollision(
+                    new_name.get_final_item().to_string(),
+                ));
+            }
+        }
+        Ok(Box::new(std::iter::once(api)))
+    });
+    results
+}
+
+/// The name a type will be given under `naming_policy!(upper_camel_case_types)`,
+/// if that differs from its existing name.
+fn upper_camel_case_type_name(api: &Api<FnPhase>) -> Option<QualifiedName> {
+    match api {
+        Api::Struct { .. }
+        | Api::Enum { .. }
+        | Api::ForwardDeclaration { .. }
+        | Api::ConcreteType { .. } => {
+            let name = api.name();
+            let new_name = to_upper_camel_case(name.get_final_item());
+            if new_name == name.get_final_item() {
+                None
+            } else {
+                Some(QualifiedName::new(
+                    name.get_namespace(),
+                    make_ident(new_name),
+                ))
+            }
+        }
+        _ => None,
+    }
+}
+
 fn validate_all_segments_ok_for_cxx(
     items: impl Iterator<Item = String>,
 ) -> Result<(), ConvertError> {
@@ -233,11 +233,16 @@ impl ByValueChecker {
     fn get_field_types(def: &ItemStruct) -> Vec<QualifiedName> {
         let mut results = Vec::new();
         for f in &def.fields {
-            let fty = &f.ty;
+            let mut fty = &f.ty;
+            // Arrays (including the opaque storage for bitfields) are POD
+            // if their elements are.
+            while let Type::Array(arr) = fty {
+                fty = &arr.elem;
+            }
             if let Type::Path(p) = fty {
                 results.push(QualifiedName::from_type_path(p));
             }
-            // TODO handle anything else which bindgen might spit out, e.g. arrays?
+            // TODO handle anything else which bindgen might spit out
         }
         results
     }
@@ -334,4 +339,59 @@ mod tests {
         bvc.ingest_struct(&t, &Namespace::new());
         assert!(bvc.satisfy_requests(vec![t_id]).is_err());
     }
+
+    #[test]
+    fn test_with_bitfields() {
+        let mut bvc = ByValueChecker::new();
+        let t: ItemStruct = parse_quote! {
+            struct Bar {
+                pub _bitfield_align_1: [u8; 0],
+                pub _bitfield_1: [u8; 2usize],
+                b: i64,
+            }
+        };
+        let t_id = ty_from_ident(&t.ident);
+        bvc.ingest_struct(&t, &Namespace::new());
+        bvc.satisfy_requests(vec![t_id.clone()]).unwrap();
+        assert!(bvc.is_pod(&t_id));
+    }
+
+    #[test]
+    fn test_with_union() {
+        let mut bvc = ByValueChecker::new();
+        let u: ItemStruct = parse_quote! {
+            #[bindgen_union]
+            struct Number {
+                i: i32,
+                f: f32,
+            }
+        };
+        let t: ItemStruct = parse_quote! {
+            struct Tagged {
+                tag: u32,
+                value: Number,
+            }
+        };
+        let u_id = ty_from_ident(&u.ident);
+        let t_id = ty_from_ident(&t.ident);
+        bvc.ingest_struct(&u, &Namespace::new());
+        bvc.ingest_struct(&t, &Namespace::new());
+        bvc.satisfy_requests(vec![t_id.clone()]).unwrap();
+        assert!(bvc.is_pod(&t_id));
+        assert!(bvc.is_pod(&u_id));
+    }
+
+    #[test]
+    fn test_with_array_of_cxxstring() {
+        let mut bvc = ByValueChecker::new();
+        let t: ItemStruct = parse_quote! {
+            struct Bar {
+                a: [CxxString; 2],
+                b: i64,
+            }
+        };
+        let t_id = ty_from_ident(&t.ident);
+        bvc.ingest_struct(&t, &Namespace::new());
+        assert!(bvc.satisfy_requests(vec![t_id]).is_err());
+    }
 }
@@ -18,7 +18,7 @@ use std::collections::HashSet;
 
 use autocxx_parser::IncludeCppConfig;
 use byvalue_checker::ByValueChecker;
-use syn::{ItemEnum, ItemStruct, Type};
+use syn::{parse::ParseStream, Attribute, Ident, ItemEnum, ItemStruct, LitInt, Token, Type};
 
 use crate::{
     conversion::{
@@ -37,6 +37,22 @@ pub(crate) struct PodAnalysis {
     pub(crate) kind: TypeKind,
     pub(crate) bases: HashSet<QualifiedName>,
     pub(crate) field_deps: HashSet<QualifiedName>,
+    /// Whether this is a C++ union rather than a struct or class.
+    pub(crate) is_union: bool,
+    /// The layout which bindgen expects this type to have, if it told us.
+    pub(crate) layout: Option<StructLayout>,
+}
+
+/// The size, alignment and field offsets of a C++ type, as calculated
+/// by bindgen. We generate assertions that both the C++ compiler and
+/// Rust agree with these.
+pub(crate) struct StructLayout {
+    pub(crate) size: usize,
+    pub(crate) align: usize,
+    pub(crate) field_offsets: Vec<(Ident, usize)>,
+    /// Whether all the fields are public in C++. Otherwise, C++ code
+    /// outside the type can't check their offsets.
+    pub(crate) all_fields_public: bool,
 }
 
 pub(crate) struct PodPhase;
@@ -120,6 +136,8 @@ fn analyze_struct(
     mut item: ItemStruct,
 ) -> Result<Box<dyn Iterator<Item = Api<PodPhase>>>, ConvertErrorWithContext> {
     let id = name.name.get_final_ident();
+    let is_union = super::has_attr(&item.attrs, "bindgen_union");
+    let layout = get_layout(&item.attrs);
     super::remove_bindgen_attrs(&mut item.attrs, id.clone())?;
     let bases = get_bases(&item);
     let mut field_deps = HashSet::new();
@@ -146,6 +164,8 @@ fn analyze_struct(
             kind: type_kind,
             bases,
             field_deps,
+            is_union,
+            layout,
         },
     })))
 }
@@ -158,14 +178,49 @@ fn get_struct_field_types(
     extra_apis: &mut Vec<UnanalyzedApi>,
 ) -> Result<(), ConvertError> {
     for f in &s.fields {
+        let mut ty = &f.ty;
+        while let Type::Array(arr) = ty {
+            ty = &arr.elem;
+        }
         let annotated =
-            type_converter.convert_type(f.ty.clone(), ns, &TypeConversionContext::CxxInnerType)?;
+            type_converter.convert_type(ty.clone(), ns, &TypeConversionContext::CxxInnerType)?;
         extra_apis.extend(annotated.extra_apis);
         deps.extend(annotated.types_encountered);
     }
     Ok(())
 }
 
+/// Reads the layout annotations which we added when parsing
+/// the bindgen output.
+fn get_layout(attrs: &[Attribute]) -> Option<StructLayout> {
+    let mut size = None;
+    let mut align = None;
+    let mut field_offsets = Vec::new();
+    for attr in attrs {
+        if attr.path.is_ident("bindgen_size") {
+            size = attr.parse_args::<LitInt>().ok()?.base10_parse().ok();
+        } else if attr.path.is_ident("bindgen_align") {
+            align = attr.parse_args::<LitInt>().ok()?.base10_parse().ok();
+        } else if attr.path.is_ident("bindgen_field_offset") {
+            let (field, offset) = attr
+                .parse_args_with(|input: ParseStream| {
+                    let field: Ident = input.parse()?;
+                    input.parse::<Token![,]>()?;
+                    let offset: LitInt = input.parse()?;
+                    Ok((field, offset))
+                })
+                .ok()?;
+            field_offsets.push((field, offset.base10_parse().ok()?));
+        }
+    }
+    Some(StructLayout {
+        size: size?,
+        align: align?,
+        field_offsets,
+        all_fields_public: !super::has_attr(attrs, "bindgen_nonpublic_fields"),
+    })
+}
+
 fn get_bases(item: &ItemStruct) -> HashSet<QualifiedName> {
     item.fields
         .iter()
@@ -15,7 +15,7 @@
 use std::collections::HashSet;
 
 use crate::types::{make_ident, Namespace, QualifiedName};
-use autocxx_parser::RustPath;
+use autocxx_parser::{IncludeCppConfig, RustPath};
 use syn::{
     punctuated::Punctuated, token::Comma, Attribute, FnArg, Ident, ImplItem, ItemConst, ItemEnum,
     ItemStruct, ItemType, ItemUse, ReturnType, Signature, Type, Visibility,
@@ -48,6 +48,19 @@ pub(crate) struct ImplBlockDetails {
     pub(crate) ty: Ident,
 }
 
+/// A function which we synthesize in order to give Rust access to a field
+/// of a C++ struct which Rust can't access directly, such as a bitfield
+/// or a member of a non-POD union. The C++ wrapper function reads or
+/// writes the field.
+#[derive(Clone)]
+pub(crate) enum FieldAccessor {
+    Get(Ident),
+    Set(Ident),
+    /// Reads a member of a union. This is unsafe, since the member
+    /// might not be the one which is currently active.
+    GetUnionMember(Ident),
+}
+
 /// A C++ function for which we need to generate bindings, but haven't
 /// yet analyzed in depth. This is little more than a `ForeignItemFn`
 /// broken down into its constituent parts, plus some metadata from the
@@ -68,6 +81,7 @@ pub(crate) struct FuncToConvert {
     pub(crate) original_name: Option<String>,
     pub(crate) virtual_this_type: Option<QualifiedName>,
     pub(crate) self_ty: Option<QualifiedName>,
+    pub(crate) field_accessor: Option<FieldAccessor>,
 }
 
 /// Layers of analysis which may be applied to decorate each API.
@@ -150,11 +164,29 @@ impl std::fmt::Debug for ApiName {
 pub(crate) struct SubclassName(pub(crate) ApiName);
 
 impl SubclassName {
-    pub(crate) fn new(id: Ident) -> Self {
-        Self(ApiName::new_in_root_namespace(id))
+    /// The subclass lives in the C++ namespace requested in the config,
+    /// or the root namespace if none was requested.
+    pub(crate) fn new(id: Ident, config: &IncludeCppConfig) -> Self {
+        let ns = config
+            .get_subclass_cpp_namespace(&id)
+            .map(Namespace::from_user_input)
+            .unwrap_or_else(Namespace::new);
+        Self(ApiName::new(&ns, id))
     }
-    pub(crate) fn from_holder_name(id: &Ident) -> Self {
-        Self::new(make_ident(id.to_string().strip_suffix("Holder").unwrap()))
+    pub(crate) fn from_holder_name(id: &Ident, config: &IncludeCppConfig) -> Self {
+        Self::new(
+            make_ident(id.to_string().strip_suffix("Holder").unwrap()),
+            config,
+        )
+    }
+    /// The C++ namespace in which the subclass's C++ peer, and the
+    /// functions supporting it, are generated.
+    pub(crate) fn cpp_namespace(&self) -> &Namespace {
+        self.0.name.get_namespace()
+    }
+    /// The fully-qualified name of the 'Holder' type.
+    pub(crate) fn qualified_holder(&self) -> QualifiedName {
+        QualifiedName::new(self.cpp_namespace(), self.holder())
     }
     pub(crate) fn id(&self) -> Ident {
         self.0.name.get_final_ident()
@@ -163,10 +195,15 @@ impl SubclassName {
     pub(crate) fn holder(&self) -> Ident {
         self.with_suffix("Holder")
     }
+    /// Generate the name for the Rust alias of the type held by the
+    /// 'Holder', which depends on whether the subclass is thread-safe.
+    pub(crate) fn peer_holder(&self) -> Ident {
+        self.with_suffix("PeerHolder")
+    }
     /// Generate the name for the 'Cpp' type
     pub(crate) fn cpp(&self) -> QualifiedName {
         let id = self.with_suffix("Cpp");
-        QualifiedName::new(self.0.name.get_namespace(), id)
+        QualifiedName::new(self.cpp_namespace(), id)
     }
     pub(crate) fn cpp_remove_ownership(&self) -> Ident {
         self.with_suffix("Cpp_remove_ownership")
@@ -174,6 +211,31 @@ impl SubclassName {
     pub(crate) fn remove_ownership(&self) -> Ident {
         self.with_suffix("_remove_ownership")
     }
+    /// Generate the name for the Rust function which the C++ peer's
+    /// destructor calls.
+    pub(crate) fn cpp_destroyed(&self) -> Ident {
+        self.with_suffix("_cpp_destroyed")
+    }
+    /// Generate the name for the C++ function which turns a `std::unique_ptr`
+    /// to the 'Cpp' type into a `std::shared_ptr`.
+    pub(crate) fn cpp_into_shared(&self) -> Ident {
+        self.with_suffix("Cpp_into_shared")
+    }
+    /// Generate the name for the C++ function which converts a `std::shared_ptr`
+    /// to the 'Cpp' type into one to the given superclass.
+    pub(crate) fn cpp_as_shared(&self, superclass: &QualifiedName) -> Ident {
+        self.with_suffix(&format!("Cpp_As_{}_shared", superclass.get_final_item()))
+    }
+    /// The C++ function which wraps a boxed Rust trait object, for a trait
+    /// which implements the given C++ interface, as that interface.
+    pub(crate) fn cpp_into_interface(&self, interface: &QualifiedName) -> Ident {
+        self.with_suffix(&format!("Cpp_into_{}", interface.get_final_item()))
+    }
+    /// The Rust function which hands a boxed Rust trait object to C++ as
+    /// the given C++ interface.
+    pub(crate) fn into_interface(&self, interface: &QualifiedName) -> Ident {
+        self.with_suffix(&format!("_into_{}", interface.get_final_item()))
+    }
     fn with_suffix(&self, suffix: &str) -> Ident {
         make_ident(format!("{}{}", self.0.name.get_final_item(), suffix))
     }
@@ -288,10 +350,12 @@ pub(crate) enum Api<T: AnalysisPhase> {
         cpp_impl: Box<CppFunction>,
         is_trivial: bool,
     },
-    /// A Rust subclass of a C++ class.
+    /// A Rust subclass of one or more C++ classes. The first superclass
+    /// is the primary superclass, whose constructors are used; any others
+    /// must be default-constructible.
     Subclass {
         name: SubclassName,
-        superclass: QualifiedName,
+        superclasses: Vec<QualifiedName>,
     },
 }
 
@@ -353,13 +417,9 @@ impl<T: AnalysisPhase> Api<T> {
 
     pub(crate) fn valid_types(&self) -> Box<dyn Iterator<Item = QualifiedName>> {
         match self {
-            Api::Subclass { name, .. } => Box::new(
-                vec![
-                    self.name().clone(),
-                    QualifiedName::new(&Namespace::new(), name.holder()),
-                ]
-                .into_iter(),
-            ),
+            Api::Subclass { name, .. } => {
+                Box::new(vec![self.name().clone(), name.qualified_holder()].into_iter())
+            }
             _ => Box::new(std::iter::once(self.name().clone())),
         }
     }
@@ -27,6 +27,13 @@ impl TypeConversionPolicy {
     ) -> Result<String, ConvertError> {
         match self.cpp_conversion {
             CppConversionType::FromUniquePtrToValue => self.wrapped_type(cpp_name_map),
+            CppConversionType::FromPointerToReference => self.pointer_type(cpp_name_map),
+            CppConversionType::OutParam { pod: true, .. } => {
+                Ok(format!("{}*", self.unwrapped_type_as_string(cpp_name_map)?))
+            }
+            CppConversionType::OutParam { pod: false, .. } => {
+                Ok(format!("{}&", self.wrapped_type(cpp_name_map)?))
+            }
             _ => self.unwrapped_type_as_string(cpp_name_map),
         }
     }
@@ -34,10 +41,20 @@ impl TypeConversionPolicy {
     pub(super) fn converted_type(&self, cpp_name_map: &CppNameMap) -> Result<String, ConvertError> {
         match self.cpp_conversion {
             CppConversionType::FromValueToUniquePtr => self.wrapped_type(cpp_name_map),
+            CppConversionType::FromReferenceToPointer => self.pointer_type(cpp_name_map),
             _ => self.unwrapped_type_as_string(cpp_name_map),
         }
     }
 
+    /// The unwrapped type is a reference; this returns the equivalent pointer.
+    fn pointer_type(&self, cpp_name_map: &CppNameMap) -> Result<String, ConvertError> {
+        let reference = self.unwrapped_type_as_string(cpp_name_map)?;
+        Ok(format!(
+            "{}*",
+            reference.strip_suffix('&').unwrap_or(&reference)
+        ))
+    }
+
     fn unwrapped_type_as_string(&self, cpp_name_map: &CppNameMap) -> Result<String, ConvertError> {
         type_to_cpp(&self.unwrapped_type, cpp_name_map)
     }
@@ -64,6 +81,13 @@ impl TypeConversionPolicy {
                 }
             }
             CppConversionType::FromUniquePtrToValue => format!("std::move(*{})", var_name),
+            CppConversionType::FromReferenceToPointer => format!("&{}", var_name),
+            CppConversionType::FromPointerToReference => format!("*{}", var_name),
+            CppConversionType::OutParam { via_reference, pod } => match (via_reference, pod) {
+                (true, _) => format!("*{}", var_name),
+                (false, true) => var_name.to_string(),
+                (false, false) => format!("{}.get()", var_name),
+            },
             CppConversionType::FromValueToUniquePtr => format!(
                 "std::make_unique<{}>({})",
                 self.unconverted_type(cpp_name_map)?,
@@ -71,4 +95,27 @@ impl TypeConversionPolicy {
             ),
         })
     }
+
+    /// Any statement which needs to run in the C++ wrapper function before
+    /// we call the underlying C++ API, for instance to allocate space for
+    /// an out parameter.
+    pub(super) fn cpp_preamble(
+        &self,
+        var_name: &str,
+        cpp_name_map: &CppNameMap,
+    ) -> Result<Option<String>, ConvertError> {
+        Ok(match self.cpp_conversion {
+            CppConversionType::OutParam { pod: true, .. } => Some(format!(
+                "new ({}) {}();",
+                var_name,
+                self.unwrapped_type_as_string(cpp_name_map)?
+            )),
+            CppConversionType::OutParam { pod: false, .. } => Some(format!(
+                "{} = std::make_unique<{}>();",
+                var_name,
+                self.unwrapped_type_as_string(cpp_name_map)?
+            )),
+            _ => None,
+        })
+    }
 }
@@ -17,19 +17,23 @@ pub(crate) mod type_to_cpp;
 
 use crate::{
     conversion::analysis::fun::{function_wrapper::CppFunctionKind, FnAnalysis},
-    types::{make_ident, QualifiedName},
+    types::{make_ident, Namespace, QualifiedName},
     CppFilePair,
 };
 use autocxx_parser::IncludeCppConfig;
 use itertools::Itertools;
 use std::collections::{HashMap, HashSet};
-use type_to_cpp::{original_name_map_from_apis, type_to_cpp, CppNameMap};
+use syn::Ident;
+use type_to_cpp::{
+    namespaced_name_using_original_name_map, original_name_map_from_apis, type_to_cpp, CppNameMap,
+};
 
 use super::{
     analysis::fun::{
         function_wrapper::{CppFunction, CppFunctionBody},
         FnPhase,
     },
+    analysis::pod::{PodAnalysis, StructLayout},
     api::{Api, SubclassName},
     ConvertError,
 };
@@ -79,6 +83,13 @@ struct AdditionalFunction {
     cpp_headers: Vec<Header>,
 }
 
+/// A virtual method overridden by a subclass, and the superclass
+/// in which it's declared.
+struct SubclassMethod<'a> {
+    fun: &'a CppFunction,
+    superclass: &'a QualifiedName,
+}
+
 /// Generates additional C++ glue functions needed by autocxx.
 /// In some ways it would be preferable to be able to pass snippets
 /// of C++ through to `cxx` for inclusion in the C++ file which it
@@ -133,7 +144,7 @@ impl<'a> CppCodeGenerator<'a> {
         apis: impl Iterator<Item = &'a Api<FnPhase>>,
     ) -> Result<(), ConvertError> {
         let mut constructors_by_subclass: HashMap<SubclassName, Vec<&CppFunction>> = HashMap::new();
-        let mut methods_by_subclass: HashMap<SubclassName, Vec<&CppFunction>> = HashMap::new();
+        let mut methods_by_subclass: HashMap<SubclassName, Vec<SubclassMethod>> = HashMap::new();
         let mut deferred_apis = Vec::new();
         for api in apis {
             match &api {
@@ -151,6 +162,16 @@ impl<'a> CppCodeGenerator<'a> {
                     type_to_cpp(rs_definition, &self.original_name_map)?,
                 ),
                 Api::CType { typename, .. } => self.generate_ctype_typedef(typename),
+                Api::Struct {
+                    name,
+                    analysis:
+                        PodAnalysis {
+                            layout: Some(layout),
+                            bases,
+                            ..
+                        },
+                    ..
+                } => self.generate_layout_assertions(&name.name, layout, !bases.is_empty()),
                 Api::Subclass { .. } => deferred_apis.push(api),
                 Api::RustSubclassFn {
                     subclass, details, ..
@@ -158,7 +179,10 @@ impl<'a> CppCodeGenerator<'a> {
                     methods_by_subclass
                         .entry(subclass.clone())
                         .or_default()
-                        .push(&details.cpp_impl);
+                        .push(SubclassMethod {
+                            fun: &details.cpp_impl,
+                            superclass: &details.superclass,
+                        });
                 }
                 Api::RustSubclassConstructor {
                     cpp_impl, subclass, ..
@@ -174,8 +198,17 @@ impl<'a> CppCodeGenerator<'a> {
 
         for api in deferred_apis.into_iter() {
             match api {
-                Api::Subclass { name, superclass } => self.generate_subclass(
-                    superclass,
+                Api::Subclass { name, superclasses }
+                    if self.config.is_interface_impl(&name.id()) =>
+                {
+                    self.generate_interface_impl(
+                        superclasses,
+                        name,
+                        methods_by_subclass.remove(name).unwrap_or_default(),
+                    )?
+                }
+                Api::Subclass { name, superclasses } => self.generate_subclass(
+                    superclasses,
                     name,
                     constructors_by_subclass.remove(name).unwrap_or_default(),
                     methods_by_subclass.remove(name).unwrap_or_default(),
@@ -377,6 +410,22 @@ impl<'a> CppCodeGenerator<'a> {
                 ),
             })
             .collect();
+        let preambles: Result<Vec<_>, _> = match conversion_direction {
+            ConversionDirection::RustCallsCpp => details
+                .argument_conversion
+                .iter()
+                .enumerate()
+                .map(|(counter, conv)| {
+                    conv.cpp_preamble(&get_arg_name(counter), &self.original_name_map)
+                })
+                .collect(),
+            _ => Ok(Vec::new()),
+        };
+        let preamble: String = preambles?
+            .into_iter()
+            .flatten()
+            .map(|p| format!("{} ", p))
+            .collect();
         let mut arg_list = arg_list?.into_iter();
         let receiver = if is_a_method { arg_list.next() } else { None };
         if matches!(&details.payload, CppFunctionBody::ConstructSuperclass(_)) {
@@ -420,6 +469,23 @@ impl<'a> CppCodeGenerator<'a> {
                 )
             }
             CppFunctionBody::ConstructSuperclass(_) => ("".to_string(), arg_list),
+            CppFunctionBody::FieldGet(field) => (
+                format!(
+                    "{}.{}",
+                    receiver.expect("Field accessor without receiver"),
+                    field
+                ),
+                "".to_string(),
+            ),
+            CppFunctionBody::FieldSet(field) => (
+                format!(
+                    "{}.{} = {}",
+                    receiver.expect("Field accessor without receiver"),
+                    field,
+                    arg_list
+                ),
+                "".to_string(),
+            ),
         };
         if let Some(ret) = &details.return_conversion {
             underlying_function_call = format!(
@@ -453,8 +519,19 @@ impl<'a> CppCodeGenerator<'a> {
             } else {
                 "".into()
             };
-        let definition_after_sig =
-            format!("{} {{ {} }}", field_assignments, underlying_function_call,);
+        let mut headers = vec![Header::system("memory")];
+        if details
+            .argument_conversion
+            .iter()
+            .any(|conv| conv.is_pod_out_param())
+        {
+            // For placement new.
+            headers.push(Header::system("new"));
+        }
+        let definition_after_sig = format!(
+            "{} {{ {}{} }}",
+            field_assignments, preamble, underlying_function_call,
+        );
         let (declaration, definition) = if requires_rust_declarations {
             (
                 Some(format!("{};", declaration)),
@@ -473,7 +550,7 @@ impl<'a> CppCodeGenerator<'a> {
             type_definition: None,
             declaration,
             definition,
-            headers: vec![Header::system("memory")],
+            headers,
             cpp_headers: Vec::new(),
         })
     }
@@ -494,23 +571,92 @@ impl<'a> CppCodeGenerator<'a> {
         })
     }
 
+    /// Emits static assertions that the C++ compiler lays out a POD type
+    /// in the same way as bindgen (and therefore Rust) expects.
+    fn generate_layout_assertions(
+        &mut self,
+        tn: &QualifiedName,
+        layout: &StructLayout,
+        has_bases: bool,
+    ) {
+        let cpp_name = namespaced_name_using_original_name_map(tn, &self.original_name_map);
+        let mismatch = |what: &str| {
+            format!(
+                "\"{} of {} differs between bindgen and the C++ compiler\"",
+                what, cpp_name
+            )
+        };
+        let mut assertions = vec![
+            format!(
+                "static_assert(sizeof({}) == {}, {});",
+                cpp_name,
+                layout.size,
+                mismatch("Size")
+            ),
+            format!(
+                "static_assert(alignof({}) == {}, {});",
+                cpp_name,
+                layout.align,
+                mismatch("Alignment")
+            ),
+        ];
+        // offsetof is only guaranteed to work for standard-layout types,
+        // which rules out those with fields in their base classes, or with
+        // fields of differing access control. We also can't name private
+        // or protected fields from here. Internal fields added by bindgen
+        // (such as bitfield storage) have no C++ equivalent.
+        if !has_bases && layout.all_fields_public {
+            assertions.extend(
+                layout
+                    .field_offsets
+                    .iter()
+                    .filter(|(field, _)| !field.to_string().starts_with('_'))
+                    .map(|(field, offset)| {
+                        let field = cpp_field_name(field);
+                        format!(
+                            "static_assert(offsetof({}, {}) == {}, {});",
+                            cpp_name,
+                            field,
+                            offset,
+                            mismatch(&format!("Offset of field {}", field))
+                        )
+                    }),
+            );
+        }
+        self.additional_functions.push(AdditionalFunction {
+            type_definition: None,
+            declaration: Some(assertions.join("\n")),
+            definition: None,
+            headers: vec![Header::system("cstddef")],
+            cpp_headers: Vec::new(),
+        })
+    }
+
     fn generate_subclass(
         &mut self,
-        superclass: &QualifiedName,
+        superclasses: &[QualifiedName],
         subclass: &SubclassName,
         constructors: Vec<&CppFunction>,
-        methods: Vec<&CppFunction>,
+        methods: Vec<SubclassMethod>,
     ) -> Result<(), ConvertError> {
         let holder = subclass.holder();
+        let ns = subclass.cpp_namespace();
         self.additional_functions.push(AdditionalFunction {
-            type_definition: Some(format!("struct {};", holder.to_string())),
+            type_definition: Some(wrap_in_namespace(
+                ns,
+                format!("struct {};", holder.to_string()),
+            )),
             declaration: None,
             definition: None,
             headers: Vec::new(),
             cpp_headers: Vec::new(),
         });
         let mut method_decls = Vec::new();
-        for method in methods {
+        for SubclassMethod {
+            fun: method,
+            superclass,
+        } in methods
+        {
             // First the method which calls from C++ to Rust
             let mut fn_impl = self.generate_cpp_function_inner(
                 method,
@@ -543,16 +689,36 @@ impl<'a> CppCodeGenerator<'a> {
             method_decls.push(super_fn_impl.declaration.take().unwrap());
             self.additional_functions.push(super_fn_impl);
         }
-        // In future, for each superclass..
-        let super_name = superclass.get_final_item();
-        method_decls.push(format!(
-            "const {}& As_{}() const {{ return *this; }}",
-            super_name, super_name,
-        ));
-        method_decls.push(format!(
-            "{}& As_{}_mut() {{ return *this; }}",
-            super_name, super_name
-        ));
+        // Functions to make std::shared_ptrs to the subclass, and to each
+        // superclass. These must be declared after the subclass itself, so
+        // they're pushed once it's done.
+        let mut shared_ptr_fns = vec![format!(
+            "inline std::shared_ptr<{}> {}(std::unique_ptr<{}> peer) {{ return std::shared_ptr<{}>(std::move(peer)); }}",
+            subclass.cpp(),
+            subclass.cpp_into_shared(),
+            subclass.cpp(),
+            subclass.cpp()
+        )];
+        for superclass in superclasses {
+            let super_name = superclass.get_final_item();
+            method_decls.push(format!(
+                "const {}& As_{}() const {{ return *this; }}",
+                super_name, super_name,
+            ));
+            method_decls.push(format!(
+                "{}& As_{}_mut() {{ return *this; }}",
+                super_name, super_name
+            ));
+            // The aliasing constructor shares ownership with the peer.
+            shared_ptr_fns.push(format!(
+                "inline std::shared_ptr<{}> {}(std::shared_ptr<{}> peer) {{ auto& base = peer->As_{}_mut(); return std::shared_ptr<{}>(std::move(peer), &base); }}",
+                superclass.to_cpp_name(),
+                subclass.cpp_as_shared(superclass),
+                subclass.cpp(),
+                super_name,
+                superclass.to_cpp_name(),
+            ));
+        }
         // And now constructors
         let mut constructor_decls: Vec<String> = Vec::new();
         for constructor in constructors {
@@ -566,18 +732,36 @@ impl<'a> CppCodeGenerator<'a> {
             constructor_decls.push(decl);
             self.additional_functions.push(fn_impl);
         }
+        // If the Rust side wants to know, the destructor tells it, before
+        // the Rust peer (if we own it) is dropped along with `obs`.
+        let (destructor_decl, destructor_def) =
+            if self.config.subclass_wants_cpp_destroyed(&subclass.id()) {
+                (
+                    format!("~{}();", subclass.cpp().get_final_item()),
+                    format!(
+                        "{}::~{}() {{\n{}(*obs);\n}}\n",
+                        subclass.cpp(),
+                        subclass.cpp().get_final_item(),
+                        subclass.cpp_destroyed()
+                    ),
+                )
+            } else {
+                (String::new(), String::new())
+            };
         self.additional_functions.push(AdditionalFunction {
-            type_definition: Some(format!(
-                "class {} : {}\n{{\npublic:\n{}\n{}\nvoid {}() const;\nprivate:rust::Box<{}> obs;\nvoid really_remove_ownership();\n\n}};",
-                subclass.cpp(),
-                superclass.to_cpp_name(),
+            type_definition: Some(wrap_in_namespace(ns, format!(
+                "class {} : {}\n{{\npublic:\n{}\n{}\n{}\nvoid {}() const;\nprivate:rust::Box<{}> obs;\nvoid really_remove_ownership();\n\n}};",
+                subclass.cpp().get_final_item(),
+                superclasses.iter().map(|sup| sup.to_cpp_name()).join(", "),
                 constructor_decls.join("\n"),
+                destructor_decl,
                 method_decls.join("\n"),
                 subclass.cpp_remove_ownership(),
                 holder
-            )),
+            ))),
             definition: Some(format!(
-                "void {}::{}() const {{\nconst_cast<{}*>(this)->really_remove_ownership();\n}}\n;void {}::really_remove_ownership() {{\nauto new_obs = {}(std::move(obs));\nobs = std::move(new_obs);\n}}\n",
+                "{}void {}::{}() const {{\nconst_cast<{}*>(this)->really_remove_ownership();\n}}\n;void {}::really_remove_ownership() {{\nauto new_obs = {}(std::move(obs));\nobs = std::move(new_obs);\n}}\n",
+                destructor_def,
                 subclass.cpp(),
                 subclass.cpp_remove_ownership().to_string(),
                 subclass.cpp(),
@@ -588,6 +772,132 @@ impl<'a> CppCodeGenerator<'a> {
             headers: Vec::new(),
             cpp_headers: vec![Header::user("cxxgen.h")],
         });
+        self.additional_functions.push(AdditionalFunction {
+            type_definition: None,
+            declaration: Some(wrap_in_namespace(ns, shared_ptr_fns.join("\n"))),
+            definition: None,
+            headers: vec![Header::system("memory")],
+            cpp_headers: Vec::new(),
+        });
+        Ok(())
+    }
+
+    /// Generates a C++ class which implements the given interfaces by
+    /// forwarding each pure virtual method to a boxed Rust trait object,
+    /// which it owns.
+    fn generate_interface_impl(
+        &mut self,
+        interfaces: &[QualifiedName],
+        subclass: &SubclassName,
+        methods: Vec<SubclassMethod>,
+    ) -> Result<(), ConvertError> {
+        let holder = subclass.holder();
+        let ns = subclass.cpp_namespace();
+        self.additional_functions.push(AdditionalFunction {
+            type_definition: Some(wrap_in_namespace(
+                ns,
+                format!("struct {};", holder.to_string()),
+            )),
+            declaration: None,
+            definition: None,
+            headers: Vec::new(),
+            cpp_headers: Vec::new(),
+        });
+        let mut method_decls = Vec::new();
+        for SubclassMethod { fun: method, .. } in methods {
+            let mut fn_impl = self.generate_cpp_function_inner(
+                method,
+                true,
+                ConversionDirection::CppCallsRust,
+                true,
+            )?;
+            method_decls.push(fn_impl.declaration.take().unwrap());
+            self.additional_functions.push(fn_impl);
+        }
+        let cpp_id = subclass.cpp().get_final_item().to_string();
+        let into_fns: Vec<_> = interfaces
+            .iter()
+            .map(|interface| {
+                format!(
+                    "std::unique_ptr<{}> {}(rust::Box<{}> obs)",
+                    interface.to_cpp_name(),
+                    subclass.cpp_into_interface(interface),
+                    holder,
+                )
+            })
+            .collect();
+        // C++ deletes our class through a std::unique_ptr to the interface.
+        let destructor_checks = interfaces
+            .iter()
+            .map(|interface| {
+                format!(
+                    "static_assert(std::has_virtual_destructor<{}>::value, \"{} must have a virtual destructor to be implemented in Rust\");",
+                    interface.to_cpp_name(),
+                    interface.to_cpp_name()
+                )
+            })
+            .join("\n");
+        self.additional_functions.push(AdditionalFunction {
+            type_definition: Some(wrap_in_namespace(ns, format!(
+                "{}\nclass {} : {}\n{{\npublic:\n{}(rust::Box<{}> obs);\n~{}();\n{}\nprivate:rust::Box<{}> obs;\n}};",
+                destructor_checks,
+                cpp_id,
+                interfaces.iter().map(|sup| format!("public {}", sup.to_cpp_name())).join(", "),
+                cpp_id,
+                holder,
+                cpp_id,
+                method_decls.join("\n"),
+                holder
+            ))),
+            declaration: Some(wrap_in_namespace(
+                ns,
+                into_fns.iter().map(|decl| format!("{};", decl)).join("\n"),
+            )),
+            // These are defined alongside the cxx-generated code so that
+            // rust::Box knows how to drop the holder.
+            definition: Some(wrap_in_namespace(ns, format!(
+                "{}::{}(rust::Box<{}> obs) : obs(std::move(obs)) {{}}\n{}::~{}() {{}}\n{}",
+                cpp_id,
+                cpp_id,
+                holder,
+                cpp_id,
+                cpp_id,
+                into_fns
+                    .iter()
+                    .map(|decl| format!(
+                        "{} {{ return std::make_unique<{}>(std::move(obs)); }}",
+                        decl, cpp_id
+                    ))
+                    .join("\n"),
+            ))),
+            headers: vec![Header::system("memory"), Header::system("type_traits")],
+            cpp_headers: vec![Header::user("cxxgen.h")],
+        });
         Ok(())
     }
 }
+
+/// Wraps some C++ declarations in blocks for the given namespace.
+fn wrap_in_namespace(ns: &Namespace, text: String) -> String {
+    if ns.is_empty() {
+        text
+    } else {
+        format!(
+            "{} {}\n{}",
+            ns.iter()
+                .map(|seg| format!("namespace {} {{", seg))
+                .join(" "),
+            text,
+            ns.iter().map(|_| "}").join(" ")
+        )
+    }
+}
+
+/// bindgen appends an underscore to C++ field names which are Rust keywords.
+fn cpp_field_name(rust_field: &Ident) -> String {
+    let rust_field = rust_field.to_string();
+    match rust_field.strip_suffix('_') {
+        Some(stripped) if syn::parse_str::<Ident>(stripped).is_err() => stripped.to_string(),
+        _ => rust_field,
+    }
+}
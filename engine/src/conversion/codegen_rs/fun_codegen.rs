@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use itertools::Itertools;
 use proc_macro2::TokenStream;
 use quote::quote;
 use syn::{
@@ -19,7 +20,7 @@ use syn::{
     parse_quote,
     punctuated::Punctuated,
     token::{Comma, Unsafe},
-    Attribute, FnArg, ForeignItem, Ident, ImplItem, Item, ReturnType,
+    Attribute, FnArg, ForeignItem, Ident, ImplItem, Item, Pat, ReturnType, Type,
 };
 
 use super::{
@@ -28,8 +29,15 @@ use super::{
 };
 use crate::{
     conversion::{
-        analysis::fun::{ArgumentAnalysis, FnAnalysis, FnKind, MethodKind, RustRenameStrategy},
+        analysis::fun::{
+            function_wrapper::{CppConversionType, TypeConversionPolicy},
+            ArgumentAnalysis, FnAnalysis, FnKind, MethodKind, ReceiverMutability,
+            RustRenameStrategy,
+        },
         api::ImplBlockDetails,
+        codegen_cpp::type_to_cpp::{
+            namespaced_name_using_original_name_map, type_to_cpp, CppNameMap,
+        },
     },
     types::{Namespace, QualifiedName},
 };
@@ -52,6 +60,7 @@ pub(super) fn gen_function(
     let params = analysis.params;
     let vis = analysis.vis;
     let kind = analysis.kind;
+    let ret_conversion = analysis.ret_conversion;
     let doc_attr = fun.doc_attr;
 
     let mut cpp_name_attr = Vec::new();
@@ -61,6 +70,37 @@ pub(super) fn gen_function(
     } else {
         None
     };
+    // Raw pointers which we hide from the Rust caller (for instance, by
+    // presenting them as Options) still need the cxx::bridge function to
+    // be unsafe. The Rust wrapper then needs an unsafe block.
+    let bridge_requires_unsafe = analysis.requires_unsafe
+        || param_details
+            .iter()
+            .any(|pd| pd.conversion.wraps_pointer_in_option() || pd.conversion.is_pod_out_param());
+    let bridge_unsafety: Option<Unsafe> = if bridge_requires_unsafe {
+        Some(parse_quote!(unsafe))
+    } else {
+        None
+    };
+    let ret_needs_rust_conversion = ret_conversion
+        .as_ref()
+        .map_or(false, |rc| rc.rust_work_needed());
+    let needs_unsafe_block = !analysis.requires_unsafe
+        && (bridge_requires_unsafe
+            || ret_conversion
+                .as_ref()
+                .map_or(false, |rc| rc.wraps_pointer_in_option()));
+    let wrapper_ret_type = match &ret_conversion {
+        Some(rc) if ret_needs_rust_conversion => {
+            let ty = rc.rust_wrapper_unconverted_type();
+            parse_quote! { -> #ty }
+        }
+        _ => ret_type.clone(),
+    };
+    let wrapper_ret_type = match param_details.iter().find(|pd| pd.conversion.is_out_param()) {
+        Some(out_param) => out_param_return_type(out_param, &ret_conversion, wrapper_ret_type),
+        None => wrapper_ret_type,
+    };
     let rust_name_attr: Vec<_> = match &analysis.rust_rename_strategy {
         RustRenameStrategy::RenameUsingRustAttr => Attribute::parse_outer
             .parse2(quote!(
@@ -82,6 +122,7 @@ pub(super) fn gen_function(
         .iter()
         .any(|pd| pd.conversion.rust_work_needed());
     let rust_wrapper_needed = any_param_needs_rust_conversion
+        || ret_needs_rust_conversion
         || (cxxbridge_name != rust_name && matches!(kind, FnKind::Method(..)));
     if rust_wrapper_needed {
         if let FnKind::Method(ref type_name, ref method_kind) = kind {
@@ -92,8 +133,10 @@ pub(super) fn gen_function(
                 type_name,
                 &cxxbridge_name,
                 &rust_name,
-                &ret_type,
+                &wrapper_ret_type,
+                &ret_conversion,
                 &unsafety,
+                needs_unsafe_block,
                 &doc_attr,
             ));
         } else {
@@ -101,8 +144,10 @@ pub(super) fn gen_function(
             materialization = Some(Use::Custom(generate_function_impl(
                 &param_details,
                 &rust_name,
-                &ret_type,
+                &wrapper_ret_type,
+                &ret_conversion,
                 &unsafety,
+                needs_unsafe_block,
                 &doc_attr,
             )));
         }
@@ -146,7 +191,7 @@ pub(super) fn gen_function(
         #(#rust_name_attr)*
         #(#cpp_name_attr)*
         #doc_attr
-        #vis #unsafety fn #cxxbridge_name #lifetime_tokens ( #params ) #ret_type;
+        #vis #bridge_unsafety fn #cxxbridge_name #lifetime_tokens ( #params ) #ret_type;
     ));
     RsCodegenResult {
         extern_c_mod_items: vec![extern_c_mod_item],
@@ -167,6 +212,12 @@ fn generate_arg_lists(
     let mut arg_list = Vec::new();
 
     for pd in param_details {
+        if pd.conversion.is_out_param() {
+            // Not passed by the caller; the Rust wrapper function provides
+            // the space and then returns the value.
+            arg_list.push(pd.conversion.rust_conversion(pd.name.clone()));
+            continue;
+        }
         let type_name = pd.conversion.rust_wrapper_unconverted_type();
         let wrapper_arg_name = if pd.self_type.is_some() && !is_constructor {
             parse_quote!(self)
@@ -181,6 +232,72 @@ fn generate_arg_lists(
     (wrapper_params, arg_list)
 }
 
+/// Whether the C++ function returns a `bool`, which we assume indicates
+/// whether any out parameter was filled in.
+fn returns_bool(ret_conversion: &Option<TypeConversionPolicy>) -> bool {
+    matches!(ret_conversion, Some(TypeConversionPolicy {
+        unwrapped_type: Type::Path(typ),
+        ..
+    }) if typ.path.is_ident("bool"))
+}
+
+/// The type returned by a Rust wrapper function for a C++ function
+/// which has an out parameter.
+fn out_param_return_type(
+    out_param: &ArgumentAnalysis,
+    ret_conversion: &Option<TypeConversionPolicy>,
+    ret_type: ReturnType,
+) -> ReturnType {
+    let out_type = out_param.conversion.rust_wrapper_unconverted_type();
+    match ret_type {
+        ReturnType::Default => parse_quote! { -> #out_type },
+        ReturnType::Type(..) if returns_bool(ret_conversion) => {
+            parse_quote! { -> Option<#out_type> }
+        }
+        ReturnType::Type(_, ty) => parse_quote! { -> (#ty, #out_type) },
+    }
+}
+
+/// Generate the body of a Rust wrapper function, which calls the
+/// cxx::bridge function and converts its return value if necessary.
+fn generate_wrapper_body(
+    call: TokenStream,
+    param_details: &[ArgumentAnalysis],
+    ret_conversion: &Option<TypeConversionPolicy>,
+    needs_unsafe_block: bool,
+) -> TokenStream {
+    let call = match ret_conversion {
+        Some(ret_conversion) => ret_conversion.rust_return_conversion(call),
+        None => call,
+    };
+    let call = match param_details.iter().find(|pd| pd.conversion.is_out_param()) {
+        None => call,
+        Some(out_param) => {
+            let declaration = out_param.conversion.out_param_declaration(&out_param.name);
+            let value = out_param.conversion.out_param_value(&out_param.name);
+            let result = if ret_conversion.is_none() {
+                value
+            } else if returns_bool(ret_conversion) {
+                quote! {
+                    if autocxx_ret { Some(#value) } else { None }
+                }
+            } else {
+                quote! { (autocxx_ret, #value) }
+            };
+            quote! {
+                #declaration
+                let autocxx_ret = #call;
+                #result
+            }
+        }
+    };
+    if needs_unsafe_block {
+        quote! { unsafe { #call } }
+    } else {
+        call
+    }
+}
+
 /// Generate an 'impl Type { methods-go-here }' item
 #[allow(clippy::too_many_arguments)] // it's true, but probably best for now
 fn generate_method_impl(
@@ -190,18 +307,26 @@ fn generate_method_impl(
     cxxbridge_name: &Ident,
     rust_name: &str,
     ret_type: &ReturnType,
+    ret_conversion: &Option<TypeConversionPolicy>,
     unsafety: &Option<Unsafe>,
+    needs_unsafe_block: bool,
     doc_attr: &Option<Attribute>,
 ) -> Box<ImplBlockDetails> {
     let (wrapper_params, arg_list) = generate_arg_lists(param_details, is_constructor);
     let (lifetime_tokens, wrapper_params, ret_type) =
         add_explicit_lifetime_if_necessary(param_details, wrapper_params, ret_type);
     let rust_name = make_ident(&rust_name);
+    let body = generate_wrapper_body(
+        quote! { cxxbridge::#cxxbridge_name ( #(#arg_list),* ) },
+        param_details,
+        ret_conversion,
+        needs_unsafe_block,
+    );
     Box::new(ImplBlockDetails {
         item: ImplItem::Method(parse_quote! {
             #doc_attr
             pub #unsafety fn #rust_name #lifetime_tokens ( #wrapper_params ) #ret_type {
-                cxxbridge::#cxxbridge_name ( #(#arg_list),* )
+                #body
             }
         }),
         ty: impl_block_type_name.get_final_ident(),
@@ -213,15 +338,97 @@ fn generate_function_impl(
     param_details: &[ArgumentAnalysis],
     rust_name: &str,
     ret_type: &ReturnType,
+    ret_conversion: &Option<TypeConversionPolicy>,
     unsafety: &Option<Unsafe>,
+    needs_unsafe_block: bool,
     doc_attr: &Option<Attribute>,
 ) -> Box<Item> {
     let (wrapper_params, arg_list) = generate_arg_lists(param_details, false);
     let rust_name = make_ident(&rust_name);
+    let body = generate_wrapper_body(
+        quote! { cxxbridge::#rust_name ( #(#arg_list),* ) },
+        param_details,
+        ret_conversion,
+        needs_unsafe_block,
+    );
     Box::new(Item::Fn(parse_quote! {
         #doc_attr
         pub #unsafety fn #rust_name ( #wrapper_params ) #ret_type {
-            cxxbridge::#rust_name ( #(#arg_list),* )
+            #body
         }
     }))
 }
+
+/// Reconstructs the C++ declaration of a function, so that we can show it
+/// in the Rust documentation. Returns `None` for functions which we
+/// synthesized ourselves.
+pub(super) fn cpp_declaration(
+    ns: &Namespace,
+    fun: &FuncToConvert,
+    analysis: &FnAnalysis,
+    cpp_call_name: &str,
+    cpp_name_map: &CppNameMap,
+) -> Option<String> {
+    if fun.field_accessor.is_some() {
+        return None;
+    }
+    let mut is_const = false;
+    let mut params = Vec::new();
+    for pd in &analysis.param_details {
+        let arg_name = match &pd.name {
+            Pat::Ident(pi) => &pi.ident,
+            _ => return None,
+        };
+        if let Some((_, receiver_mutability)) = &pd.self_type {
+            is_const = matches!(receiver_mutability, ReceiverMutability::Const);
+            continue;
+        }
+        let ty = type_to_cpp(&pd.conversion.unwrapped_type, cpp_name_map).ok()?;
+        let ty = match pd.conversion.cpp_conversion {
+            CppConversionType::OutParam {
+                via_reference: true,
+                ..
+            } => format!("{}&", ty),
+            CppConversionType::OutParam { .. } => format!("{}*", ty),
+            _ => ty,
+        };
+        params.push(format!("{} {}", ty, arg_name));
+    }
+    let ret_type = match &analysis.ret_conversion {
+        None => "void".to_string(),
+        Some(conversion) => type_to_cpp(&conversion.unwrapped_type, cpp_name_map).ok()?,
+    };
+    let (prefix, name, suffix) = match &analysis.kind {
+        FnKind::Method(self_ty, method_kind) => {
+            let ty_name = namespaced_name_using_original_name_map(self_ty, cpp_name_map);
+            let method_name = match method_kind {
+                MethodKind::Constructor => self_ty.get_final_item(),
+                _ => cpp_call_name,
+            };
+            let (prefix, suffix) = match method_kind {
+                MethodKind::Constructor => (String::new(), ""),
+                MethodKind::Static => (format!("static {} ", ret_type), ""),
+                MethodKind::Virtual(_) => (format!("virtual {} ", ret_type), ""),
+                MethodKind::PureVirtual(_) => (format!("virtual {} ", ret_type), " = 0"),
+                MethodKind::Normal(_) => (format!("{} ", ret_type), ""),
+            };
+            (prefix, format!("{}::{}", ty_name, method_name), suffix)
+        }
+        FnKind::Function => (
+            format!("{} ", ret_type),
+            ns.iter()
+                .map(String::as_str)
+                .chain(std::iter::once(cpp_call_name))
+                .join("::"),
+            "",
+        ),
+    };
+    Some(format!(
+        "{}{}({}){}{}",
+        prefix,
+        name,
+        params.join(", "),
+        if is_const { " const" } else { "" },
+        suffix
+    ))
+}
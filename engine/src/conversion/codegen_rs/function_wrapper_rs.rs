@@ -13,7 +13,7 @@
 // limitations under the License.
 
 use proc_macro2::TokenStream;
-use syn::{Pat, Type};
+use syn::{Pat, Type, TypePtr};
 
 use crate::conversion::analysis::fun::function_wrapper::{
     RustConversionType, TypeConversionPolicy,
@@ -26,15 +26,32 @@ impl TypeConversionPolicy {
         match self.rust_conversion {
             RustConversionType::None => self.converted_rust_type(),
             RustConversionType::ToBoxedUpHolder(ref sub) => {
-                let id = sub.id();
-                parse_quote! { autocxx::subclass::CppSubclassRustPeerHolder<
-                    super::super::super:: #id>
-                }
+                let peer_holder = sub.peer_holder();
+                parse_quote! { #peer_holder }
             }
             RustConversionType::FromStr => parse_quote! { impl ToCppString },
+            RustConversionType::FromOptionToPointer(ref option_type)
+            | RustConversionType::FromPointerToOption(ref option_type) => option_type.clone(),
+            RustConversionType::OutParam => {
+                if self.is_pod_out_param() {
+                    self.unwrapped_type.clone()
+                } else {
+                    self.make_unique_ptr_type()
+                }
+            }
         }
     }
 
+    fn unwrapped_type_is_mutable_pointer(&self) -> bool {
+        matches!(
+            self.unwrapped_type,
+            Type::Ptr(TypePtr {
+                mutability: Some(_),
+                ..
+            })
+        )
+    }
+
     pub(super) fn rust_conversion(&self, var: Pat) -> TokenStream {
         match self.rust_conversion {
             RustConversionType::None => quote! { #var },
@@ -45,6 +62,72 @@ impl TypeConversionPolicy {
                     Box::new(#holder_type(#var))
                 }
             }
+            RustConversionType::FromOptionToPointer(_) => {
+                if self.unwrapped_type_is_mutable_pointer() {
+                    quote! {
+                        match #var {
+                            Some(r) => ::std::pin::Pin::into_inner_unchecked(r) as *mut _,
+                            None => ::std::ptr::null_mut(),
+                        }
+                    }
+                } else {
+                    quote! {
+                        match #var {
+                            Some(r) => r as *const _,
+                            None => ::std::ptr::null(),
+                        }
+                    }
+                }
+            }
+            RustConversionType::FromPointerToOption(_) => {
+                panic!("Return type conversion applied to a parameter")
+            }
+            RustConversionType::OutParam => {
+                if self.is_pod_out_param() {
+                    quote! { #var.as_mut_ptr() }
+                } else {
+                    quote! { &mut #var }
+                }
+            }
+        }
+    }
+
+    /// For an out parameter, declare the local variable into which the
+    /// C++ wrapper function will place the value.
+    pub(super) fn out_param_declaration(&self, var: &Pat) -> TokenStream {
+        if self.is_pod_out_param() {
+            quote! { let mut #var = ::std::mem::MaybeUninit::uninit(); }
+        } else {
+            quote! { let mut #var = cxx::UniquePtr::null(); }
+        }
+    }
+
+    /// For an out parameter, retrieve the value once the C++ wrapper
+    /// function has filled it in.
+    pub(super) fn out_param_value(&self, var: &Pat) -> TokenStream {
+        if self.is_pod_out_param() {
+            quote! { #var.assume_init() }
+        } else {
+            quote! { #var }
+        }
+    }
+
+    /// Convert the value returned from the cxx::bridge function into
+    /// the type we return from the Rust wrapper function.
+    pub(super) fn rust_return_conversion(&self, call: TokenStream) -> TokenStream {
+        match self.rust_conversion {
+            RustConversionType::FromPointerToOption(_) => {
+                if self.unwrapped_type_is_mutable_pointer() {
+                    quote! {
+                        #call.as_mut().map(|r| ::std::pin::Pin::new_unchecked(r))
+                    }
+                } else {
+                    quote! {
+                        #call.as_ref()
+                    }
+                }
+            }
+            _ => call,
         }
     }
 }
@@ -98,3 +98,32 @@ fn add_lifetime_to_pinned_reference(segments: &mut Punctuated<PathSegment, syn::
         }
     }
 }
+
+/// Gives every reference within the type, including those nested within
+/// generic arguments such as `Pin<&mut T>`, the explicit lifetime `'a`
+/// unless it already has a lifetime. Returns whether any were found.
+pub(crate) fn add_lifetime_to_all_references(ty: &mut Type) -> bool {
+    match ty {
+        Type::Reference(tyr) => {
+            if tyr.lifetime.is_none() {
+                tyr.lifetime = Some(parse_quote! { 'a });
+            }
+            add_lifetime_to_all_references(&mut tyr.elem);
+            true
+        }
+        Type::Path(TypePath { path, .. }) => {
+            let mut found = false;
+            for seg in path.segments.iter_mut() {
+                if let syn::PathArguments::AngleBracketed(aba) = &mut seg.arguments {
+                    for arg in aba.args.iter_mut() {
+                        if let GenericArgument::Type(ty) = arg {
+                            found |= add_lifetime_to_all_references(ty);
+                        }
+                    }
+                }
+            }
+            found
+        }
+        _ => false,
+    }
+}
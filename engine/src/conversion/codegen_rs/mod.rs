@@ -18,16 +18,19 @@ mod impl_item_creator;
 mod lifetime;
 mod namespace_organizer;
 mod non_pod_struct;
+mod pod_union;
+mod subclass_constructors;
 pub(crate) mod unqualify;
 
 use std::collections::{HashMap, HashSet};
 
-use autocxx_parser::IncludeCppConfig;
+use autocxx_parser::{IncludeCppConfig, SubclassLock, SubclassPanicPolicy};
 
 use proc_macro2::{Span, TokenStream};
 use syn::{
     parse_quote, punctuated::Punctuated, token::Comma, Attribute, Expr, FnArg, ForeignItem,
     ForeignItemFn, Ident, ImplItem, Item, ItemForeignMod, ItemMod, Pat, ReturnType, TraitItem,
+    Type, TypePath,
 };
 
 use crate::{
@@ -35,22 +38,27 @@ use crate::{
         analysis::fun::MethodKind,
         codegen_rs::{
             non_pod_struct::{make_non_pod, new_non_pod_struct},
+            pod_union::make_union,
+            subclass_constructors::generate_superclass_constructor_helpers,
             unqualify::{unqualify_params, unqualify_ret_type},
         },
-        doc_attr::get_doc_attr,
+        doc_attr::{get_doc_attr, get_doc_text, make_doc_attr},
+        doxygen::doxygen_to_rustdoc,
+        naming_policy::to_upper_camel_case,
     },
     known_types::known_types,
-    types::{make_ident, Namespace, QualifiedName},
+    types::{make_ident, make_ns_ident, Namespace, QualifiedName},
 };
 use impl_item_creator::create_impl_items;
 
 use self::{
-    fun_codegen::gen_function,
-    namespace_organizer::{HasNs, NamespaceEntries},
+    fun_codegen::{cpp_declaration, gen_function},
+    namespace_organizer::{output_namespace, HasNs, NamespaceEntries},
 };
 
 use super::{
     analysis::fun::{FnAnalysis, FnKind},
+    analysis::pod::StructLayout,
     api::RustSubclassFnDetails,
     codegen_cpp::type_to_cpp::{
         namespaced_name_using_original_name_map, original_name_map_from_apis, CppNameMap,
@@ -140,6 +148,7 @@ pub(crate) struct RsCodeGenerator<'a> {
     bindgen_mod: ItemMod,
     original_name_map: CppNameMap,
     config: &'a IncludeCppConfig,
+    api_names: HashSet<QualifiedName>,
 }
 
 impl<'a> RsCodeGenerator<'a> {
@@ -155,6 +164,7 @@ impl<'a> RsCodeGenerator<'a> {
             bindgen_mod,
             original_name_map: original_name_map_from_apis(&all_apis),
             config,
+            api_names: all_apis.iter().map(|api| api.name().clone()).collect(),
         };
         c.rs_codegen(all_apis)
     }
@@ -166,6 +176,7 @@ impl<'a> RsCodeGenerator<'a> {
         let methods_by_superclass = self.accumulate_superclass_methods(&all_apis);
         let subclasses_with_a_single_trivial_constructor =
             find_trivially_constructed_subclasses(&all_apis);
+        let superclass_constructor_helpers = generate_superclass_constructor_helpers(&all_apis);
         // Now let's generate the Rust code.
         let (rs_codegen_results_and_namespaces, additional_cpp_needs): (Vec<_>, Vec<_>) = all_apis
             .into_iter()
@@ -176,6 +187,7 @@ impl<'a> RsCodeGenerator<'a> {
                     api,
                     &methods_by_superclass,
                     &subclasses_with_a_single_trivial_constructor,
+                    &superclass_constructor_helpers,
                 );
                 ((name, gen), more_cpp_needed)
             })
@@ -183,7 +195,7 @@ impl<'a> RsCodeGenerator<'a> {
         // First, the hierarchy of mods containing lots of 'use' statements
         // which is the final API exposed as 'ffi'.
         let mut use_statements =
-            Self::generate_final_use_statements(&rs_codegen_results_and_namespaces);
+            self.generate_final_use_statements(&rs_codegen_results_and_namespaces);
         // And work out what we need for the bindgen mod.
         let bindgen_root_items =
             self.generate_final_bindgen_mods(&rs_codegen_results_and_namespaces);
@@ -332,31 +344,41 @@ impl<'a> RsCodeGenerator<'a> {
     }
 
     /// Generate lots of 'use' statements to pull cxxbridge items into the output
-    /// mod hierarchy according to C++ namespaces.
+    /// mod hierarchy according to C++ namespaces (as modified by any directives
+    /// which alter that hierarchy).
     fn generate_final_use_statements(
+        &self,
         input_items: &[(QualifiedName, RsCodegenResult)],
     ) -> Vec<Item> {
         let mut output_items = Vec::new();
-        let ns_entries = NamespaceEntries::new(input_items);
+        let output_entries: Vec<_> = input_items
+            .iter()
+            .map(|(name, codegen)| OutputEntry {
+                ns: output_namespace(name.get_namespace(), self.config),
+                name,
+                codegen,
+            })
+            .collect();
+        let ns_entries = NamespaceEntries::new(&output_entries);
         Self::append_child_use_namespace(&ns_entries, &mut output_items);
         output_items
     }
 
     fn append_child_use_namespace(
-        ns_entries: &NamespaceEntries<(QualifiedName, RsCodegenResult)>,
+        ns_entries: &NamespaceEntries<OutputEntry>,
         output_items: &mut Vec<Item>,
     ) {
-        for (name, codegen) in ns_entries.entries() {
+        for OutputEntry { ns, name, codegen } in ns_entries.entries() {
             output_items.extend(codegen.materializations.iter().map(|materialization| {
                 match materialization {
                     Use::UsedFromCxxBridgeWithAlias(alias) => {
-                        Self::generate_cxx_use_stmt(name, Some(alias))
+                        Self::generate_cxx_use_stmt(name, ns, Some(alias))
                     }
-                    Use::UsedFromCxxBridge => Self::generate_cxx_use_stmt(name, None),
-                    Use::UsedFromBindgen => Self::generate_bindgen_use_stmt(name),
+                    Use::UsedFromCxxBridge => Self::generate_cxx_use_stmt(name, ns, None),
+                    Use::UsedFromBindgen => Self::generate_bindgen_use_stmt(name, ns),
                     Use::SpecificNameFromBindgen(id) => {
                         let name = QualifiedName::new(name.get_namespace(), id.clone());
-                        Self::generate_bindgen_use_stmt(&name)
+                        Self::generate_bindgen_use_stmt(&name, ns)
                     }
                     Use::Custom(item) => *item.clone(),
                 }
@@ -366,7 +388,7 @@ impl<'a> RsCodeGenerator<'a> {
             if child_ns_entries.is_empty() {
                 continue;
             }
-            let child_id = make_ident(child_name);
+            let child_id = make_ns_ident(child_name);
             let mut new_mod: ItemMod = parse_quote!(
                 pub mod #child_id {
                 }
@@ -431,7 +453,7 @@ impl<'a> RsCodeGenerator<'a> {
         }
         for (child_name, child_ns_entries) in ns_entries.children() {
             let new_ns = ns.push((*child_name).clone());
-            let child_id = make_ident(child_name);
+            let child_id = make_ns_ident(child_name);
 
             let mut inner_output_items = Vec::new();
             self.append_child_bindgen_namespace(child_ns_entries, &mut inner_output_items, &new_ns);
@@ -468,6 +490,7 @@ impl<'a> RsCodeGenerator<'a> {
         api: Api<FnPhase>,
         associated_methods: &HashMap<QualifiedName, Vec<SuperclassMethod>>,
         subclasses_with_a_single_trivial_constructor: &HashSet<QualifiedName>,
+        superclass_constructor_helpers: &HashMap<QualifiedName, Vec<ImplItem>>,
     ) -> RsCodegenResult {
         let name = api.name().clone();
         let id = name.get_final_ident();
@@ -489,8 +512,14 @@ impl<'a> RsCodeGenerator<'a> {
                     extern_rust_mod_items: Vec::new(),
                 }
             }
-            Api::Function { fun, analysis, .. } => {
-                gen_function(name.get_namespace(), *fun, analysis, cpp_call_name)
+            Api::Function {
+                mut fun, analysis, ..
+            } => {
+                let ns = name.get_namespace();
+                let declaration =
+                    cpp_declaration(ns, &fun, &analysis, &cpp_call_name, &self.original_name_map);
+                fun.doc_attr = self.convert_doc_attr(fun.doc_attr.take(), ns, declaration);
+                gen_function(ns, *fun, analysis, cpp_call_name)
             }
             Api::Const { const_item, .. } => RsCodegenResult {
                 global_items: Vec::new(),
@@ -513,17 +542,32 @@ impl<'a> RsCodeGenerator<'a> {
                 materializations: vec![Use::UsedFromBindgen],
                 extern_rust_mod_items: Vec::new(),
             },
-            Api::Struct { item, analysis, .. } => {
+            Api::Struct {
+                mut item, analysis, ..
+            } => {
+                self.convert_type_doc_attr(&mut item.attrs, name.get_namespace());
                 let doc_attr = get_doc_attr(&item.attrs);
-                self.generate_type(
+                let item = if analysis.is_union && analysis.kind == TypeKind::Pod {
+                    Item::Union(make_union(item))
+                } else {
+                    Item::Struct(item)
+                };
+                let mut result = self.generate_type(
                     &name,
                     id,
                     analysis.kind,
-                    || Some((Item::Struct(item), doc_attr)),
+                    || Some((item, doc_attr)),
                     associated_methods,
-                )
+                );
+                if let (TypeKind::Pod, Some(layout)) = (analysis.kind, &analysis.layout) {
+                    result
+                        .global_items
+                        .extend(self.generate_layout_assertions(&name, layout));
+                }
+                result
             }
-            Api::Enum { item, .. } => {
+            Api::Enum { mut item, .. } => {
+                self.convert_type_doc_attr(&mut item.attrs, name.get_namespace());
                 let doc_attr = get_doc_attr(&item.attrs);
                 self.generate_type(
                     &name,
@@ -575,14 +619,25 @@ impl<'a> RsCodeGenerator<'a> {
             },
             Api::RustSubclassFn {
                 details, subclass, ..
-            } => Self::generate_subclass_fn(id, *details, subclass),
+            } => self.generate_subclass_fn(id, *details, subclass),
+            Api::Subclass {
+                name, superclasses, ..
+            } if self.config.is_interface_impl(&name.id()) => {
+                self.generate_interface_impl(name, &superclasses)
+            }
             Api::Subclass {
-                name, superclass, ..
+                name, superclasses, ..
             } => {
-                let methods = associated_methods.get(&superclass);
                 let generate_peer_constructor =
                     subclasses_with_a_single_trivial_constructor.contains(&name.0.name);
-                self.generate_subclass(name, &superclass, methods, generate_peer_constructor)
+                let constructor_helpers = superclass_constructor_helpers.get(&name.0.name);
+                self.generate_subclass(
+                    name,
+                    &superclasses,
+                    associated_methods,
+                    generate_peer_constructor,
+                    constructor_helpers,
+                )
             }
             Api::RustSubclassConstructor { .. } => RsCodegenResult::default(),
             Api::IgnoredItem { err, ctx, .. } => Self::generate_error_entry(err, ctx),
@@ -592,35 +647,73 @@ impl<'a> RsCodeGenerator<'a> {
     fn generate_subclass(
         &self,
         sub: SubclassName,
-        superclass: &QualifiedName,
-        methods: Option<&Vec<SuperclassMethod>>,
+        superclasses: &[QualifiedName],
+        associated_methods: &HashMap<QualifiedName, Vec<SuperclassMethod>>,
         generate_peer_constructor: bool,
+        constructor_helpers: Option<&Vec<ImplItem>>,
     ) -> RsCodegenResult {
-        let super_name = superclass.get_final_item();
-        let super_path = superclass.to_type_path();
-        let super_cxxxbridge_id = superclass.get_final_ident();
         let id = sub.id();
         let holder = sub.holder();
+        let peer_holder = sub.peer_holder();
         let full_cpp = sub.cpp();
         let cpp_path = full_cpp.to_type_path();
         let cpp_id = full_cpp.get_final_ident();
+        let ns = sub.cpp_namespace();
+        let ns_attr = cxx_namespace_attr(ns);
+        // The path from the bindgen mod for our namespace to the Rust subclass.
+        let supers = std::iter::repeat(make_ident("super")).take(ns.depth() + 3);
+        let rust_path: TypePath = parse_quote! { #(#supers::)* #id };
+        let lock = self.config.get_subclass_lock(&id);
+        let storage: TypePath = match lock {
+            None => parse_quote! { autocxx::subclass::SingleThreaded },
+            Some(SubclassLock::Mutex) => parse_quote! { autocxx::subclass::ThreadSafeMutex },
+            Some(SubclassLock::RwLock) => parse_quote! { autocxx::subclass::ThreadSafeRwLock },
+        };
         let mut global_items = Vec::new();
+        let holder_path = sub.qualified_holder().get_bindgen_path_idents();
         global_items.push(parse_quote! {
-            pub use bindgen::root::#holder;
+            pub use #(#holder_path)::*;
         });
+        if !ns.is_empty() {
+            // Whatever its C++ namespace, the #[subclass] macro expects to
+            // find the C++ peer at the top level of the ffi mod.
+            let cpp_bindgen_path = full_cpp.get_bindgen_path_idents();
+            global_items.push(parse_quote! {
+                pub use #(#cpp_bindgen_path)::*;
+            });
+        }
+        if lock.is_some() {
+            // The subclass storage type ensures the Rust side is Send and Sync;
+            // by asking for a thread-safe subclass the user promises that the
+            // C++ superclass is too.
+            let cpp_bindgen_path = full_cpp.get_bindgen_path_idents();
+            global_items.push(parse_quote! {
+                unsafe impl Send for #(#cpp_bindgen_path)::* {}
+            });
+            global_items.push(parse_quote! {
+                unsafe impl Sync for #(#cpp_bindgen_path)::* {}
+            });
+        }
         let relinquish_ownership_call = sub.cpp_remove_ownership();
+        let into_shared_call = sub.cpp_into_shared();
         let mut bindgen_mod_items = vec![
             parse_quote! {
                 pub use cxxbridge::#cpp_id;
             },
             parse_quote! {
-                pub struct #holder(pub autocxx::subclass::CppSubclassRustPeerHolder<super::super::super::#id>);
+                pub type #peer_holder = autocxx::subclass::CppSubclassRustPeerHolder<#rust_path, #storage>;
+            },
+            parse_quote! {
+                pub struct #holder(pub #peer_holder);
             },
             parse_quote! {
                 impl autocxx::subclass::CppSubclassCppPeer for #cpp_id {
                     fn relinquish_ownership(&self) {
                         self.#relinquish_ownership_call();
                     }
+                    fn into_shared(peer: cxx::UniquePtr<Self>) -> cxx::SharedPtr<Self> {
+                        cxxbridge::#into_shared_call(peer)
+                    }
                 }
             },
         ];
@@ -629,8 +722,15 @@ impl<'a> RsCodeGenerator<'a> {
             parse_quote! {
                 fn #relinquish_ownership_call(self: &#cpp_id);
             },
+            parse_quote! {
+                #ns_attr
+                fn #into_shared_call(peer: UniquePtr<#cpp_id>) -> SharedPtr<#cpp_id>;
+            },
         ];
-        if let Some(methods) = methods {
+        for (superclass, methods) in superclasses
+            .iter()
+            .filter_map(|sup| associated_methods.get(sup).map(|methods| (sup, methods)))
+        {
             let supers = SubclassName::get_supers_trait_name(superclass).to_type_path();
             let methods_impls: Vec<ImplItem> = methods
                 .iter()
@@ -657,47 +757,89 @@ impl<'a> RsCodeGenerator<'a> {
                 .collect();
             bindgen_mod_items.push(parse_quote! {
                 #[allow(non_snake_case)]
-                impl #supers for super::super::super::#id {
+                impl #supers for #rust_path {
                     #(#methods_impls)*
                 }
             });
         }
         if generate_peer_constructor {
             bindgen_mod_items.push(parse_quote! {
-                impl autocxx::subclass::CppPeerConstructor<#cpp_id> for super::super::super::#id {
-                    fn make_peer(&mut self, peer_holder: autocxx::subclass::CppSubclassRustPeerHolder<Self>) -> cxx::UniquePtr<#cpp_path> {
+                impl autocxx::subclass::CppPeerConstructor<#cpp_id, #storage> for #rust_path {
+                    fn make_peer(&mut self, peer_holder: #peer_holder) -> cxx::UniquePtr<#cpp_path> {
                         #cpp_id :: make_unique(peer_holder)
                     }
                 }
             })
         };
+        if let Some(constructor_helpers) = constructor_helpers {
+            bindgen_mod_items.push(parse_quote! {
+                #[allow(non_snake_case)]
+                impl #rust_path {
+                    #(#constructor_helpers)*
+                }
+            });
+        }
 
-        // Once for each superclass, in future...
-        let as_id = make_ident(format!("As_{}", super_name));
-        extern_c_mod_items.push(parse_quote! {
-            fn #as_id(self: &#cpp_id) -> &#super_cxxxbridge_id;
-        });
-        let as_mut_id = make_ident(format!("As_{}_mut", super_name));
-        extern_c_mod_items.push(parse_quote! {
-            fn #as_mut_id(self: Pin<&mut #cpp_id>) -> Pin<&mut #super_cxxxbridge_id>;
-        });
-        bindgen_mod_items.push(parse_quote! {
-            impl AsRef<#super_path> for super::super::super::#id {
-                fn as_ref(&self) -> &cxxbridge::#super_cxxxbridge_id {
-                    use autocxx::subclass::CppSubclass;
-                    self.peer().#as_id()
+        for (i, superclass) in superclasses.iter().enumerate() {
+            let super_name = superclass.get_final_item();
+            let super_path = superclass.to_type_path();
+            let super_cxxxbridge_id = superclass.get_final_ident();
+            let as_id = make_ident(format!("As_{}", super_name));
+            extern_c_mod_items.push(parse_quote! {
+                fn #as_id(self: &#cpp_id) -> &#super_cxxxbridge_id;
+            });
+            let as_mut_id = make_ident(format!("As_{}_mut", super_name));
+            extern_c_mod_items.push(parse_quote! {
+                fn #as_mut_id(self: Pin<&mut #cpp_id>) -> Pin<&mut #super_cxxxbridge_id>;
+            });
+            let as_shared_call = sub.cpp_as_shared(superclass);
+            extern_c_mod_items.push(parse_quote! {
+                #ns_attr
+                fn #as_shared_call(peer: SharedPtr<#cpp_id>) -> SharedPtr<#super_cxxxbridge_id>;
+            });
+            let as_shared_id = make_ident(format!("As_{}_shared", super_name));
+            bindgen_mod_items.push(parse_quote! {
+                #[allow(non_snake_case)]
+                impl #cpp_id {
+                    /// Converts a shared pointer to this C++ peer into one to its superclass,
+                    /// sharing ownership.
+                    pub fn #as_shared_id(peer: cxx::SharedPtr<Self>) -> cxx::SharedPtr<cxxbridge::#super_cxxxbridge_id> {
+                        cxxbridge::#as_shared_call(peer)
+                    }
                 }
-            }
-        });
-        // TODO it would be nice to impl AsMut here but pin prevents us
-        bindgen_mod_items.push(parse_quote! {
-            impl super::super::super::#id {
-                pub fn pin_mut(&mut self) -> std::pin::Pin<&mut cxxbridge::#super_cxxxbridge_id> {
-                    use autocxx::subclass::CppSubclass;
-                    self.peer_mut().#as_mut_id()
+            });
+            bindgen_mod_items.push(parse_quote! {
+                impl AsRef<#super_path> for #rust_path {
+                    fn as_ref(&self) -> &cxxbridge::#super_cxxxbridge_id {
+                        use autocxx::subclass::CppSubclass;
+                        self.peer().#as_id()
+                    }
                 }
+            });
+            // TODO it would be nice to impl AsMut here but pin prevents us
+            bindgen_mod_items.push(parse_quote! {
+                #[allow(non_snake_case)]
+                impl #rust_path {
+                    pub fn #as_id(&self) -> &cxxbridge::#super_cxxxbridge_id {
+                        use autocxx::subclass::CppSubclass;
+                        self.peer().#as_id()
+                    }
+                    pub fn #as_mut_id(&mut self) -> std::pin::Pin<&mut cxxbridge::#super_cxxxbridge_id> {
+                        use autocxx::subclass::CppSubclass;
+                        self.peer_mut().#as_mut_id()
+                    }
+                }
+            });
+            if i == 0 {
+                bindgen_mod_items.push(parse_quote! {
+                    impl #rust_path {
+                        pub fn pin_mut(&mut self) -> std::pin::Pin<&mut cxxbridge::#super_cxxxbridge_id> {
+                            self.#as_mut_id()
+                        }
+                    }
+                });
             }
-        });
+        }
         let remove_ownership = sub.remove_ownership();
         global_items.push(parse_quote! {
             #[allow(non_snake_case)]
@@ -705,59 +847,210 @@ impl<'a> RsCodeGenerator<'a> {
                 Box::new(#holder(me.0.relinquish_ownership()))
             }
         });
+        let mut extern_rust_mod_items = vec![
+            parse_quote! {
+                #ns_attr
+                pub type #holder;
+            },
+            parse_quote! {
+                #ns_attr
+                fn #remove_ownership(me: Box<#holder>) -> Box<#holder>;
+            },
+        ];
+        if self.config.subclass_wants_cpp_destroyed(&id) {
+            let cpp_destroyed = sub.cpp_destroyed();
+            let subclass_name = sub.0.name.to_string();
+            global_items.push(parse_quote! {
+                #[allow(non_snake_case)]
+                pub fn #cpp_destroyed(me: &#holder) {
+                    autocxx::subclass::notify_cpp_peer_destroyed::<_, cxxbridge::#cpp_id, _>(&me.0, #subclass_name)
+                }
+            });
+            extern_rust_mod_items.push(parse_quote! {
+                #ns_attr
+                fn #cpp_destroyed(me: &#holder);
+            });
+        }
         RsCodegenResult {
             extern_c_mod_items,
             bridge_items: create_impl_items(&cpp_id, self.config),
             bindgen_mod_items,
-            materializations: vec![Use::Custom(Box::new(parse_quote! {
-                pub use cxxbridge::#cpp_id;
-            }))],
+            materializations: vec![Use::SpecificNameFromBindgen(cpp_id)],
             global_items,
             impl_entry: None,
-            extern_rust_mod_items: vec![
-                parse_quote! {
-                    pub type #holder;
-                },
-                parse_quote! {
-                    fn #remove_ownership(me: Box<#holder>) -> Box<#holder>;
-                },
-            ],
+            extern_rust_mod_items,
+        }
+    }
+
+    /// Generates the Rust side of a C++ class which implements some C++
+    /// interfaces by forwarding to a boxed trait object.
+    fn generate_interface_impl(
+        &self,
+        sub: SubclassName,
+        interfaces: &[QualifiedName],
+    ) -> RsCodegenResult {
+        let id = sub.id();
+        let holder = sub.holder();
+        let ns = sub.cpp_namespace();
+        let ns_attr = cxx_namespace_attr(ns);
+        // The path from the bindgen mod for our namespace to the Rust trait.
+        let supers = std::iter::repeat(make_ident("super")).take(ns.depth() + 3);
+        let trait_path: TypePath = parse_quote! { #(#supers::)* #id };
+        let holder_path = sub.qualified_holder().get_bindgen_path_idents();
+        let mut global_items = vec![parse_quote! {
+            pub use #(#holder_path)::*;
+        }];
+        let mut extern_c_mod_items = Vec::new();
+        for interface in interfaces {
+            let cpp_into = sub.cpp_into_interface(interface);
+            let into = sub.into_interface(interface);
+            let interface_cxxbridge_id = interface.get_final_ident();
+            let interface_path = interface.get_bindgen_path_idents();
+            let doc = format!(
+                "Hands a Rust implementation of `{}` to C++ as a `{}`, which C++ then owns.",
+                id,
+                interface.to_cpp_name()
+            );
+            extern_c_mod_items.push(parse_quote! {
+                #ns_attr
+                fn #cpp_into(obs: Box<#holder>) -> UniquePtr<#interface_cxxbridge_id>;
+            });
+            global_items.push(parse_quote! {
+                #[doc = #doc]
+                #[allow(non_snake_case)]
+                pub fn #into(obj: Box<dyn super::#id>) -> cxx::UniquePtr<#(#interface_path)::*> {
+                    cxxbridge::#cpp_into(Box::new(#holder(std::cell::RefCell::new(obj))))
+                }
+            });
+        }
+        RsCodegenResult {
+            extern_c_mod_items,
+            bridge_items: Vec::new(),
+            bindgen_mod_items: vec![parse_quote! {
+                pub struct #holder(pub std::cell::RefCell<Box<dyn #trait_path>>);
+            }],
+            materializations: Vec::new(),
+            global_items,
+            impl_entry: None,
+            extern_rust_mod_items: vec![parse_quote! {
+                #ns_attr
+                pub type #holder;
+            }],
         }
     }
 
     fn generate_subclass_fn(
+        &self,
         api_name: Ident,
         details: RustSubclassFnDetails,
         subclass: SubclassName,
     ) -> RsCodegenResult {
         let params = details.params;
         let ret = details.ret;
+        // If we're to throw a C++ exception on panic, we ask cxx to do so by
+        // returning a Result. cxx throws a rust::Error if it's an Err.
+        let throws =
+            self.config.get_subclass_panic_policy(&subclass.id()) == SubclassPanicPolicy::Throw;
+        let cxxbridge_ret = unqualify_ret_type(ret.clone());
+        let (ret, cxxbridge_ret) = if throws {
+            let ret_ty = Self::ret_type_or_unit(ret);
+            let cxxbridge_ret_ty = Self::ret_type_or_unit(cxxbridge_ret);
+            (
+                parse_quote! { -> Result<#ret_ty, String> },
+                parse_quote! { -> Result<#cxxbridge_ret_ty> },
+            )
+        } else {
+            (ret, cxxbridge_ret)
+        };
         let unsafe_token = get_unsafe_token(details.requires_unsafe);
         let global_def = quote! { #unsafe_token fn #api_name(#params) #ret };
         let params = unqualify_params(params);
-        let ret = unqualify_ret_type(ret);
+        let ret = cxxbridge_ret;
         let method_name = details.method_name;
+        let ns_attr = cxx_namespace_attr(subclass.cpp_namespace());
         let cxxbridge_decl: ForeignItemFn =
-            parse_quote! { #unsafe_token fn #api_name(#params) #ret; };
+            parse_quote! { #ns_attr #unsafe_token fn #api_name(#params) #ret; };
         let args: Punctuated<Expr, Comma> =
             Self::args_from_sig(&cxxbridge_decl.sig.inputs).collect();
         let superclass_id = details.superclass.get_final_ident();
         let methods_trait = SubclassName::get_methods_trait_name(&details.superclass);
         let methods_trait = methods_trait.to_type_path();
-        let (deref_ty, deref_call, borrow, mut_token) = match details.receiver_mutability {
-            ReceiverMutability::Const => ("Deref", "deref", "try_borrow", None),
+        let (deref_ty, deref_call, mut_token) = match details.receiver_mutability {
+            ReceiverMutability::Const => ("Deref", "deref", None),
             ReceiverMutability::Mutable => (
                 "DerefMut",
                 "deref_mut",
-                "try_borrow_mut",
                 Some(syn::token::Mut(Span::call_site())),
             ),
         };
+        let lock = self.config.get_subclass_lock(&subclass.id());
+        let borrow = match (lock, &details.receiver_mutability) {
+            (None, ReceiverMutability::Const) => "try_borrow",
+            (None, ReceiverMutability::Mutable) => "try_borrow_mut",
+            (Some(SubclassLock::Mutex), _) => "lock",
+            (Some(SubclassLock::RwLock), ReceiverMutability::Const) => "read",
+            (Some(SubclassLock::RwLock), ReceiverMutability::Mutable) => "write",
+        };
         let deref_ty = make_ident(deref_ty);
         let deref_call = make_ident(deref_call);
         let borrow = make_ident(borrow);
         let destroy_panic_msg = format!("Rust subclass API (method {} of subclass {} of superclass {}) called after subclass destroyed", method_name, subclass.0.name, superclass_id);
-        let reentrancy_panic_msg = format!("Rust subclass API (method {} of subclass {} of superclass {}) called whilst subclass already borrowed - likely a re-entrant call",  method_name, subclass.0.name, superclass_id);
+        let subclass_name = subclass.0.name.to_string();
+        let method_name_str = method_name.to_string();
+        let (on_success, on_panic) = if throws {
+            (
+                quote! { Ok(r) },
+                quote! { Err(autocxx::subclass::report_subclass_panic(#subclass_name, #method_name_str, payload)) },
+            )
+        } else {
+            (
+                quote! { r },
+                quote! { autocxx::subclass::abort_on_subclass_panic(#subclass_name, #method_name_str, payload) },
+            )
+        };
+        let reentrancy_panic_msg = if lock.is_some() {
+            format!("Rust subclass API (method {} of subclass {} of superclass {}) called after the subclass lock was poisoned by a panic", method_name, subclass.0.name, superclass_id)
+        } else {
+            format!("Rust subclass API (method {} of subclass {} of superclass {}) called whilst subclass already borrowed - likely a re-entrant call",  method_name, subclass.0.name, superclass_id)
+        };
+        // So that destroying the C++ peer from within this method doesn't
+        // try to lock the subclass again.
+        let note_locked = if lock.is_some() {
+            quote! {
+                let _locked = autocxx::subclass::SubclassLockedByThisThread::new(rc.as_ref());
+            }
+        } else {
+            quote! {}
+        };
+        let call = if self.config.is_interface_impl(&subclass.id()) {
+            // The holder owns a boxed trait object rather than referring
+            // to a Rust subclass.
+            let rust_trait = subclass.id();
+            quote! {
+                let #mut_token b = me.0
+                    .#borrow()
+                    .expect(#reentrancy_panic_msg);
+                let r = std::ops::#deref_ty::#deref_call(& #mut_token b);
+                <dyn super::#rust_trait> :: #method_name
+                    (& #mut_token **r,
+                    #args)
+            }
+        } else {
+            quote! {
+                let rc = me.0
+                    .get()
+                    .expect(#destroy_panic_msg);
+                let #mut_token b = rc
+                    .as_ref()
+                    .#borrow()
+                    .expect(#reentrancy_panic_msg);
+                #note_locked
+                let r = std::ops::#deref_ty::#deref_call(& #mut_token b);
+                #methods_trait :: #method_name
+                    (r,
+                    #args)
+            }
+        };
         RsCodegenResult {
             extern_c_mod_items: Vec::new(),
             bridge_items: Vec::new(),
@@ -765,17 +1058,14 @@ impl<'a> RsCodeGenerator<'a> {
             materializations: Vec::new(),
             global_items: vec![parse_quote! {
                 #global_def {
-                    let rc = me.0
-                        .get()
-                        .expect(#destroy_panic_msg);
-                    let #mut_token b = rc
-                        .as_ref()
-                        .#borrow()
-                        .expect(#reentrancy_panic_msg);
-                    let r = std::ops::#deref_ty::#deref_call(& #mut_token b);
-                    #methods_trait :: #method_name
-                        (r,
-                        #args)
+                    // Panics mustn't unwind into C++.
+                    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                        #call
+                    }));
+                    match result {
+                        Ok(r) => #on_success,
+                        Err(payload) => #on_panic,
+                    }
                 }
             }],
             impl_entry: None,
@@ -783,6 +1073,13 @@ impl<'a> RsCodeGenerator<'a> {
         }
     }
 
+    fn ret_type_or_unit(ret: ReturnType) -> Type {
+        match ret {
+            ReturnType::Default => parse_quote! { () },
+            ReturnType::Type(_, ty) => *ty,
+        }
+    }
+
     fn args_from_sig(params: &Punctuated<FnArg, Comma>) -> impl Iterator<Item = Expr> + '_ {
         params
             .iter()
@@ -809,7 +1106,7 @@ impl<'a> RsCodeGenerator<'a> {
         F: FnOnce() -> Option<(Item, Option<Attribute>)>,
     {
         let mut bindgen_mod_items = Vec::new();
-        let mut materializations = vec![Use::UsedFromCxxBridge];
+        let mut materializations = vec![self.type_materialization(name)];
         Self::add_superclass_stuff_to_type(
             name,
             &mut bindgen_mod_items,
@@ -836,8 +1133,10 @@ impl<'a> RsCodeGenerator<'a> {
                     }
                 }
                 bindgen_mod_items.push(item);
+                let mut global_items = self.generate_extern_type_impl(type_kind, name);
+                global_items.extend(self.generate_thread_safety_impls(name));
                 RsCodegenResult {
-                    global_items: self.generate_extern_type_impl(type_kind, name),
+                    global_items,
                     impl_entry: None,
                     bridge_items: create_impl_items(&id, self.config),
                     extern_c_mod_items: vec![self.generate_cxxbridge_type(name, true, None)],
@@ -853,7 +1152,7 @@ impl<'a> RsCodeGenerator<'a> {
                     extern_c_mod_items: vec![self.generate_cxxbridge_type(name, false, doc_attr)],
                     extern_rust_mod_items: Vec::new(),
                     bridge_items: Vec::new(),
-                    global_items: Vec::new(),
+                    global_items: self.generate_thread_safety_impls(name),
                     bindgen_mod_items,
                     impl_entry: None,
                     materializations,
@@ -862,6 +1161,20 @@ impl<'a> RsCodeGenerator<'a> {
         }
     }
 
+    /// Work out how to expose a type in the output mod. If the user has
+    /// asked for UpperCamelCase type names, this is where we rename it.
+    /// Any type whose new name would collide was already rejected by
+    /// `check_names`.
+    fn type_materialization(&self, name: &QualifiedName) -> Use {
+        if self.config.naming_policy().upper_camel_case_types {
+            let new_name = to_upper_camel_case(name.get_final_item());
+            if new_name != name.get_final_item() {
+                return Use::UsedFromCxxBridgeWithAlias(make_ident(new_name));
+            }
+        }
+        Use::UsedFromCxxBridge
+    }
+
     fn add_superclass_stuff_to_type(
         name: &QualifiedName,
         bindgen_mod_items: &mut Vec<Item>,
@@ -988,8 +1301,12 @@ impl<'a> RsCodeGenerator<'a> {
         }
     }
 
-    fn generate_cxx_use_stmt(name: &QualifiedName, alias: Option<&Ident>) -> Item {
-        let segs = Self::find_output_mod_root(name.get_namespace())
+    fn generate_cxx_use_stmt(
+        name: &QualifiedName,
+        output_ns: &Namespace,
+        alias: Option<&Ident>,
+    ) -> Item {
+        let segs = Self::find_output_mod_root(output_ns)
             .chain(std::iter::once(make_ident("cxxbridge")))
             .chain(std::iter::once(name.get_final_ident()));
         Item::Use(match alias {
@@ -1002,14 +1319,76 @@ impl<'a> RsCodeGenerator<'a> {
         })
     }
 
-    fn generate_bindgen_use_stmt(name: &QualifiedName) -> Item {
-        let segs =
-            Self::find_output_mod_root(name.get_namespace()).chain(name.get_bindgen_path_idents());
+    fn generate_bindgen_use_stmt(name: &QualifiedName, output_ns: &Namespace) -> Item {
+        let segs = Self::find_output_mod_root(output_ns).chain(name.get_bindgen_path_idents());
         Item::Use(parse_quote! {
             pub use #(#segs)::*;
         })
     }
 
+    /// Generates assertions that Rust lays out a POD type in the same way as
+    /// bindgen calculated: its size and alignment at compile time, and its
+    /// field offsets in a test. The C++ side makes equivalent checks.
+    fn generate_layout_assertions(
+        &self,
+        tyname: &QualifiedName,
+        layout: &StructLayout,
+    ) -> Vec<Item> {
+        let tynamestring = namespaced_name_using_original_name_map(tyname, &self.original_name_map);
+        let fulltypath = tyname.get_bindgen_path_idents();
+        let size = layout.size;
+        let align = layout.align;
+        let size_msg = format!("Size of {} differs between bindgen and Rust", tynamestring);
+        let align_msg = format!(
+            "Alignment of {} differs between bindgen and Rust",
+            tynamestring
+        );
+        let mut assertions = vec![
+            Item::Const(parse_quote! {
+                const _: () = assert!(::std::mem::size_of::<#(#fulltypath)::*>() == #size, #size_msg);
+            }),
+            Item::Const(parse_quote! {
+                const _: () = assert!(::std::mem::align_of::<#(#fulltypath)::*>() == #align, #align_msg);
+            }),
+        ];
+        // Field offsets can't be calculated at compile time without
+        // offset_of!, which would need a very recent Rust, so instead
+        // we check them in a test as bindgen's own layout tests do.
+        let offset_checks = layout.field_offsets.iter().map(|(field, offset)| {
+            let offset_msg = format!(
+                "Offset of field {} of {} differs between bindgen and Rust",
+                field, tynamestring
+            );
+            quote! {
+                assert_eq!(
+                    unsafe { &(*(::std::ptr::null::<#(#fulltypath)::*>())).#field as *const _ as usize },
+                    #offset,
+                    #offset_msg
+                );
+            }
+        });
+        if !layout.field_offsets.is_empty() {
+            let test_name = make_ident(format!(
+                "autocxx_layout_test_{}",
+                tyname
+                    .get_namespace()
+                    .iter()
+                    .map(String::as_str)
+                    .chain(std::iter::once(tyname.get_final_item()))
+                    .collect::<Vec<_>>()
+                    .join("_")
+            ));
+            assertions.push(Item::Fn(parse_quote! {
+                #[test]
+                #[allow(non_snake_case, deref_nullptr)]
+                fn #test_name() {
+                    #(#offset_checks)*
+                }
+            }));
+        }
+        assertions
+    }
+
     fn generate_extern_type_impl(&self, type_kind: TypeKind, tyname: &QualifiedName) -> Vec<Item> {
         let tynamestring = namespaced_name_using_original_name_map(tyname, &self.original_name_map);
         let fulltypath = tyname.get_bindgen_path_idents();
@@ -1026,6 +1405,64 @@ impl<'a> RsCodeGenerator<'a> {
         })]
     }
 
+    /// Implements `Send` and `Sync` for a type if the user has promised,
+    /// using `thread_safe!`, that it's OK to do so.
+    fn generate_thread_safety_impls(&self, tyname: &QualifiedName) -> Vec<Item> {
+        let cpp_name = namespaced_name_using_original_name_map(tyname, &self.original_name_map);
+        let fulltypath = tyname.get_bindgen_path_idents();
+        let mut items = Vec::new();
+        if self.config.is_send(&cpp_name) {
+            items.push(parse_quote! {
+                unsafe impl Send for #(#fulltypath)::* {}
+            });
+        }
+        if self.config.is_sync(&cpp_name) {
+            items.push(parse_quote! {
+                unsafe impl Sync for #(#fulltypath)::* {}
+            });
+        }
+        items
+    }
+
+    /// Converts any Doxygen markup in a doc comment into rustdoc, and
+    /// appends the original C++ declaration, if known.
+    fn convert_doc_attr(
+        &self,
+        doc_attr: Option<Attribute>,
+        ns: &Namespace,
+        cpp_declaration: Option<String>,
+    ) -> Option<Attribute> {
+        // Rustdoc resolves links relative to the documented item, so we
+        // can only link to items in the same namespace. Anything else is
+        // left as plain code text.
+        let resolve = |cpp_name: &str| {
+            let relative_name = QualifiedName::new_from_cpp_name(&format!("{}::{}", ns, cpp_name));
+            let absolute_name = QualifiedName::new_from_cpp_name(cpp_name);
+            [relative_name, absolute_name]
+                .iter()
+                .find(|name| self.api_names.contains(name))
+                .filter(|name| name.get_namespace() == ns)
+                .map(|name| name.get_final_item().to_string())
+        };
+        let text = doc_attr
+            .as_ref()
+            .and_then(get_doc_text)
+            .map(|text| doxygen_to_rustdoc(&text, &resolve));
+        let declaration = cpp_declaration.map(|decl| format!(" ```cpp\n {}\n ```", decl));
+        let text = match (text, declaration) {
+            (Some(text), Some(declaration)) => format!("{}\n\n{}", text, declaration),
+            (text, declaration) => text.or(declaration)?,
+        };
+        Some(make_doc_attr(&text))
+    }
+
+    fn convert_type_doc_attr(&self, attrs: &mut Vec<Attribute>, ns: &Namespace) {
+        if let Some(doc_attr) = self.convert_doc_attr(get_doc_attr(attrs), ns, None) {
+            attrs.retain(|attr| !attr.path.is_ident("doc"));
+            attrs.insert(0, doc_attr);
+        }
+    }
+
     fn generate_cxxbridge_type(
         &self,
         name: &QualifiedName,
@@ -1069,7 +1506,7 @@ impl<'a> RsCodeGenerator<'a> {
             for_extern_c_ts.extend(quote! {
                 type #id = super::bindgen::root::
             });
-            for_extern_c_ts.extend(ns.iter().map(make_ident).map(|id| {
+            for_extern_c_ts.extend(ns.iter().map(make_ns_ident).map(|id| {
                 quote! {
                     #id::
                 }
@@ -1098,6 +1535,17 @@ fn get_unsafe_token(requires_unsafe: bool) -> TokenStream {
     }
 }
 
+/// A cxx `#[namespace]` attribute for items in the given namespace,
+/// or nothing for the root namespace.
+fn cxx_namespace_attr(ns: &Namespace) -> Option<Attribute> {
+    if ns.is_empty() {
+        None
+    } else {
+        let ns_string = ns.to_string();
+        Some(parse_quote! { #[namespace = #ns_string] })
+    }
+}
+
 fn find_trivially_constructed_subclasses(apis: &[Api<FnPhase>]) -> HashSet<QualifiedName> {
     let (simple_constructors, complex_constructors): (Vec<_>, Vec<_>) = apis
         .iter()
@@ -1121,6 +1569,20 @@ fn find_trivially_constructed_subclasses(apis: &[Api<FnPhase>]) -> HashSet<Quali
         .collect()
 }
 
+/// An item destined for the final mod hierarchy of 'use' statements,
+/// along with the namespace in which it should appear there.
+struct OutputEntry<'a> {
+    ns: Namespace,
+    name: &'a QualifiedName,
+    codegen: &'a RsCodegenResult,
+}
+
+impl HasNs for OutputEntry<'_> {
+    fn get_namespace(&self) -> &Namespace {
+        &self.ns
+    }
+}
+
 impl HasNs for (QualifiedName, RsCodegenResult) {
     fn get_namespace(&self) -> &Namespace {
         self.0.get_namespace()
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use crate::types::Namespace;
+use autocxx_parser::IncludeCppConfig;
 use std::collections::BTreeMap;
 
 pub trait HasNs {
@@ -70,10 +71,55 @@ impl<'a, T: HasNs> NamespaceEntries<'a, T> {
     }
 }
 
+/// Works out the namespace in which an item should appear within the
+/// generated Rust mod hierarchy, taking account of any `namespace_alias!`
+/// and `flatten_inline_namespaces!` directives. This only affects the
+/// Rust paths: C++ code always uses the real namespace.
+pub(crate) fn output_namespace(ns: &Namespace, config: &IncludeCppConfig) -> Namespace {
+    let segments: Vec<&String> = ns.iter().collect();
+    // If several aliases apply, the most specific wins.
+    let alias = config
+        .namespace_aliases()
+        .filter_map(|(aliased_ns, alias)| {
+            let aliased_ns = Namespace::from_user_input(aliased_ns);
+            let aliased_segments: Vec<&String> = aliased_ns.iter().collect();
+            if segments.starts_with(&aliased_segments) {
+                Some((aliased_segments.len(), alias))
+            } else {
+                None
+            }
+        })
+        .max_by_key(|(len, _)| *len);
+    let (mut output, remaining_segments) = match alias {
+        Some((len, alias)) => (Namespace::new().push(alias.to_string()), &segments[len..]),
+        None => (Namespace::new(), &segments[..]),
+    };
+    for segment in remaining_segments {
+        if !(config.flatten_inline_namespaces() && is_abi_namespace(segment)) {
+            output = output.push((*segment).clone());
+        }
+    }
+    output
+}
+
+/// bindgen already merges true inline namespaces into their parent, but
+/// standard libraries use namespaces with reserved names (e.g. `std::__1`,
+/// `std::__ndk1`, `std::__cxx11`, `std::_V2`) for ABI versioning, and users
+/// never want to see those. Other reserved names, such as `std::__detail`
+/// or `__gnu_cxx`, are real namespaces and are left alone.
+fn is_abi_namespace(segment: &str) -> bool {
+    ["__cxx", "__ndk", "__", "_V"].iter().any(|prefix| {
+        matches!(segment.strip_prefix(prefix), Some(version)
+            if !version.is_empty() && version.chars().all(|c| c.is_ascii_digit()))
+    })
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{HasNs, NamespaceEntries};
+    use super::{output_namespace, HasNs, NamespaceEntries};
     use crate::types::Namespace;
+    use autocxx_parser::IncludeCppConfig;
+    use syn::parse_quote;
 
     struct TestApi(&'static str, Namespace);
     impl HasNs for TestApi {
@@ -128,6 +174,27 @@ mod tests {
         assert_ident(k_nse_entries[1], "M");
     }
 
+    #[test]
+    fn test_output_namespace() {
+        let config: IncludeCppConfig = parse_quote! {
+            namespace_alias!("org::chromium", chromium)
+            namespace_alias!("org::chromium::base", base)
+            flatten_inline_namespaces!()
+        };
+        let output = |ns: &str| output_namespace(&Namespace::from_user_input(ns), &config);
+        assert_eq!(
+            output("org::chromium::base::internal").to_string(),
+            "base::internal"
+        );
+        assert_eq!(output("org::chromium::net").to_string(), "chromium::net");
+        assert_eq!(output("org::other").to_string(), "org::other");
+        assert_eq!(output("std::__1").to_string(), "std");
+        assert_eq!(output("std::_V2::chrono").to_string(), "std::chrono");
+        assert_eq!(output("std::__cxx11").to_string(), "std");
+        assert_eq!(output("std::__detail").to_string(), "std::__detail");
+        assert_eq!(output("__gnu_cxx").to_string(), "__gnu_cxx");
+    }
+
     fn assert_ident(api: &TestApi, expected: &str) {
         assert_eq!(api.0, expected);
     }
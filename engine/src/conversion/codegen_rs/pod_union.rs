@@ -0,0 +1,87 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use syn::{parse_quote, Fields, FieldsNamed, ItemStruct, ItemUnion, Token, Type};
+
+/// Turns a POD union, which we've been representing as a struct during
+/// analysis, back into a Rust `union`. Rust only allows union fields
+/// which are `Copy` or `ManuallyDrop`, and bindgen's types aren't `Copy`,
+/// so anything other than a primitive is wrapped in a `ManuallyDrop`.
+/// Either way, writing to a field is safe whereas reading is `unsafe`.
+pub(crate) fn make_union(s: ItemStruct) -> ItemUnion {
+    let mut fields = match s.fields {
+        Fields::Named(fields) => fields,
+        _ => FieldsNamed {
+            brace_token: Default::default(),
+            named: Default::default(),
+        },
+    };
+    for f in fields.named.iter_mut() {
+        if !is_copy(&f.ty) {
+            let ty = &f.ty;
+            f.ty = parse_quote! { ::std::mem::ManuallyDrop<#ty> };
+        }
+    }
+    ItemUnion {
+        attrs: s
+            .attrs
+            .into_iter()
+            .filter(|a| !a.path.is_ident("derive"))
+            .collect(),
+        vis: s.vis,
+        union_token: Token![union](s.struct_token.span),
+        ident: s.ident,
+        generics: s.generics,
+        fields,
+    }
+}
+
+fn is_copy(ty: &Type) -> bool {
+    match ty {
+        Type::Array(arr) => is_copy(&arr.elem),
+        Type::Ptr(_) => true,
+        Type::Path(typ) => {
+            let segs: Vec<_> = typ
+                .path
+                .segments
+                .iter()
+                .map(|s| s.ident.to_string())
+                .collect();
+            match segs.as_slice() {
+                [single] => matches!(
+                    single.as_str(),
+                    "bool"
+                        | "char"
+                        | "f32"
+                        | "f64"
+                        | "i8"
+                        | "i16"
+                        | "i32"
+                        | "i64"
+                        | "i128"
+                        | "isize"
+                        | "u8"
+                        | "u16"
+                        | "u32"
+                        | "u64"
+                        | "u128"
+                        | "usize"
+                ),
+                [.., os, raw, _] => os == "os" && raw == "raw",
+                _ => false,
+            }
+        }
+        _ => false,
+    }
+}
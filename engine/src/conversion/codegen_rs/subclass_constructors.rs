@@ -0,0 +1,182 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{HashMap, HashSet};
+
+use quote::quote;
+use syn::{parse_quote, GenericArgument, ImplItem, PathArguments, Type, TypePath};
+
+use crate::{
+    conversion::{
+        analysis::fun::{ArgumentAnalysis, FnAnalysis, FnKind, FnPhase, MethodKind},
+        api::{Api, SubclassName},
+        naming_policy::to_snake_case,
+    },
+    types::{make_ident, QualifiedName},
+};
+
+use super::{get_unsafe_token, lifetime::add_lifetime_to_all_references};
+
+/// Generic types which we look inside when naming a constructor helper
+/// after its parameter types.
+const WRAPPER_TYPES: &[&str] = &["Pin", "UniquePtr", "SharedPtr", "Option", "Box"];
+
+/// Generates, for each subclass, a `with_base_...` function per constructor
+/// of its superclass. Each returns a closure suitable for passing to
+/// `CppSubclass::new_rust_owned_with` and friends, which constructs the C++
+/// peer using that superclass constructor and the given arguments.
+/// The functions are named after the types of the constructor's
+/// parameters, e.g. `with_base_u32_string` or `with_base_default`.
+pub(super) fn generate_superclass_constructor_helpers(
+    apis: &[Api<FnPhase>],
+) -> HashMap<QualifiedName, Vec<ImplItem>> {
+    let subclasses_by_cpp: HashMap<QualifiedName, &SubclassName> = apis
+        .iter()
+        .filter_map(|api| match api {
+            Api::Subclass { name, .. } => Some((name.cpp(), name)),
+            _ => None,
+        })
+        .collect();
+    let mut constructors_by_subclass: HashMap<&SubclassName, Vec<&FnAnalysis>> = HashMap::new();
+    for api in apis {
+        if let Api::Function {
+            analysis:
+                analysis @ FnAnalysis {
+                    kind: FnKind::Method(self_ty, MethodKind::Constructor),
+                    ..
+                },
+            ..
+        } = api
+        {
+            if let Some(sub) = subclasses_by_cpp.get(self_ty) {
+                constructors_by_subclass
+                    .entry(sub)
+                    .or_default()
+                    .push(analysis);
+            }
+        }
+    }
+    constructors_by_subclass
+        .into_iter()
+        .map(|(sub, constructors)| {
+            let names: Vec<_> = constructors
+                .iter()
+                .map(|analysis| helper_name(&analysis.param_details))
+                .collect();
+            let mut seen = HashSet::new();
+            let clashing: HashSet<_> = names.iter().filter(|n| !seen.insert(*n)).collect();
+            let helpers = constructors
+                .iter()
+                .zip(names.iter())
+                .map(|(analysis, name)| {
+                    let name = if clashing.contains(name) {
+                        // Fall back on the numeric suffix which bindgen gave
+                        // the overloaded constructor.
+                        format!(
+                            "{}{}",
+                            name,
+                            analysis.rust_name.trim_start_matches("make_unique")
+                        )
+                    } else {
+                        name.clone()
+                    };
+                    generate_helper(sub, analysis, &name)
+                })
+                .collect();
+            (sub.0.name.clone(), helpers)
+        })
+        .collect()
+}
+
+fn generate_helper(sub: &SubclassName, analysis: &FnAnalysis, name: &str) -> ImplItem {
+    let peer_holder = sub.peer_holder();
+    let cpp_id = sub.cpp().get_final_ident();
+    let make_unique = make_ident(&analysis.rust_name);
+    let helper_name = make_ident(name);
+    // The first parameter is the Rust peer holder; we supply that.
+    let (mut param_types, param_names): (Vec<Type>, Vec<_>) = analysis
+        .param_details
+        .iter()
+        .skip(1)
+        .filter(|pd| !pd.conversion.is_out_param())
+        .map(|pd| {
+            (
+                pd.conversion.rust_wrapper_unconverted_type(),
+                pd.name.clone(),
+            )
+        })
+        .unzip();
+    // The returned closure captures the arguments, so must not outlive any
+    // references among them.
+    let mut has_references = false;
+    for ty in param_types.iter_mut() {
+        has_references |= add_lifetime_to_all_references(ty);
+    }
+    let (generics, bound) = if has_references {
+        (quote! { <'a> }, quote! { + 'a })
+    } else {
+        (quote! {}, quote! {})
+    };
+    let unsafe_token = get_unsafe_token(analysis.requires_unsafe);
+    parse_quote! {
+        /// Returns a function which constructs the C++ peer of this subclass
+        /// using the superclass constructor taking these parameters. Pass it
+        /// to `new_rust_owned_with`, `new_cpp_owned_with` or similar.
+        pub #unsafe_token fn #helper_name #generics(#(#param_names: #param_types),*)
+            -> impl FnOnce(&mut Self, #peer_holder) -> cxx::UniquePtr<#cpp_id> #bound {
+            move |_, peer_holder| #unsafe_token { #cpp_id::#make_unique(peer_holder, #(#param_names),*) }
+        }
+    }
+}
+
+fn helper_name(param_details: &[ArgumentAnalysis]) -> String {
+    let fragments: Vec<_> = param_details
+        .iter()
+        .skip(1)
+        .filter(|pd| !pd.conversion.is_out_param())
+        .map(|pd| type_name_fragment(&pd.conversion.rust_wrapper_unconverted_type()))
+        .collect();
+    if fragments.is_empty() {
+        "with_base_default".into()
+    } else {
+        format!("with_base_{}", fragments.join("_"))
+    }
+}
+
+/// Names a parameter type, ignoring references, pointers and
+/// smart pointers, so that `Pin<&mut Foo>` becomes `foo`.
+fn type_name_fragment(ty: &Type) -> String {
+    match ty {
+        Type::Reference(tyr) => type_name_fragment(&tyr.elem),
+        Type::Ptr(typ) => type_name_fragment(&typ.elem),
+        Type::Path(TypePath { path, .. }) => match path.segments.last() {
+            Some(seg) => {
+                if WRAPPER_TYPES.iter().any(|w| seg.ident == w) {
+                    if let PathArguments::AngleBracketed(aba) = &seg.arguments {
+                        if let Some(GenericArgument::Type(inner)) = aba.args.first() {
+                            return type_name_fragment(inner);
+                        }
+                    }
+                }
+                to_snake_case(&seg.ident.to_string())
+                    .trim_end_matches('_')
+                    .to_string()
+            }
+            None => "arg".into(),
+        },
+        // `impl ToCppString`
+        Type::ImplTrait(_) => "string".into(),
+        _ => "arg".into(),
+    }
+}
@@ -53,6 +53,10 @@ pub enum ConvertError {
     BoxContainingNonRustType(QualifiedName),
     RustTypeWithAPath(QualifiedName),
     AbstractNestedType,
+    NullabilityOnNonPointer,
+    UnsupportedOutParam(String),
+    OutParamNotDefaultConstructible(QualifiedName),
+    NamingPolicyCollision(String),
 }
 
 fn format_maybe_identifier(id: &Option<Ident>) -> String {
@@ -98,6 +102,10 @@ impl Display for ConvertError {
             ConvertError::BoxContainingNonRustType(ty) => write!(f, "A rust::Box<T> was encountered where T was not known to be a Rust type. Use rust_type!(T): {}", ty.to_cpp_name())?,
             ConvertError::RustTypeWithAPath(ty) => write!(f, "A qualified Rust type was found (i.e. one containing ::): {}. Rust types must always be a simple identifier.", ty.to_cpp_name())?,
             ConvertError::AbstractNestedType => write!(f, "This type is nested within another struct/class, yet is abstract (or is not on the allowlist so we can't be sure). This is not yet supported by autocxx. If you don't believe this type is abstract, add it to the allowlist.")?,
+            ConvertError::NullabilityOnNonPointer => write!(f, "A parameter or return type was to be represented as an Option of a reference, because of the unannotated_pointers directive, but autocxx does not represent it as a raw pointer.")?,
+            ConvertError::UnsupportedOutParam(fn_name) => write!(f, "Function {} has a parameter marked as an out parameter, but autocxx can only convert a single non-const pointer or reference to a named type into a return value, and not for constructors, virtual functions or functions returning references.", fn_name)?,
+            ConvertError::OutParamNotDefaultConstructible(ty) => write!(f, "This function has an out parameter of type {}, which the generated C++ would need to default-construct, but that type is abstract or has no public default constructor.", ty.to_cpp_name())?,
+            ConvertError::NamingPolicyCollision(rust_name) => write!(f, "Applying the naming policy to this item gives the Rust name {}, which is already used by another item.", rust_name)?,
         }
         Ok(())
     }
@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use syn::Attribute;
+use syn::{parse_quote, Attribute, Lit, Meta, MetaNameValue};
 
 /// Returns the attribute (if any) which contains a doc comment.
 pub(super) fn get_doc_attr(attrs: &[Attribute]) -> Option<Attribute> {
@@ -21,3 +21,18 @@ pub(super) fn get_doc_attr(attrs: &[Attribute]) -> Option<Attribute> {
         .find(|a| a.path.get_ident().iter().any(|p| *p == "doc"))
         .cloned()
 }
+
+/// Returns the text of a doc comment attribute.
+pub(super) fn get_doc_text(attr: &Attribute) -> Option<String> {
+    match attr.parse_meta() {
+        Ok(Meta::NameValue(MetaNameValue {
+            lit: Lit::Str(text),
+            ..
+        })) => Some(text.value()),
+        _ => None,
+    }
+}
+
+pub(super) fn make_doc_attr(text: &str) -> Attribute {
+    parse_quote! { #[doc = #text] }
+}
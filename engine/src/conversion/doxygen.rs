@@ -0,0 +1,287 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Conversion of Doxygen markup within C++ comments into rustdoc Markdown.
+
+/// Where lines of the comment are currently going.
+enum Section {
+    Body,
+    Argument,
+    Returns,
+}
+
+/// Converts a C++ comment which may contain Doxygen commands (e.g. `@param`,
+/// `\return` or `\code`) into rustdoc Markdown. Parameters are gathered into
+/// an "Arguments" section, and return values into a "Returns" section, as is
+/// conventional for Rust. `resolve` is called with each name referred to by
+/// `\ref`, `@see` and similar commands, and should return the Rust path to
+/// which we should link, if the name refers to something we've generated
+/// which can be linked from here. Other names are left as code text.
+pub(crate) fn doxygen_to_rustdoc(text: &str, resolve: &dyn Fn(&str) -> Option<String>) -> String {
+    let mut body: Vec<String> = Vec::new();
+    let mut arguments: Vec<String> = Vec::new();
+    let mut returns: Vec<String> = Vec::new();
+    let mut see_also: Vec<String> = Vec::new();
+    let mut section = Section::Body;
+    let mut code_block_end: Option<&str> = None;
+    for line in text.lines() {
+        let line = line.strip_prefix(' ').unwrap_or(line);
+        if let Some(end) = code_block_end {
+            if get_command(line.trim_start()).map_or(false, |(cmd, _)| cmd == end) {
+                body.push("```".to_string());
+                code_block_end = None;
+            } else {
+                body.push(line.to_string());
+            }
+            continue;
+        }
+        let (cmd, rest) = match get_command(line.trim_start()) {
+            Some(cmd_and_rest) => cmd_and_rest,
+            None => {
+                let converted = convert_inline(line, resolve);
+                match section {
+                    _ if line.trim().is_empty() => {
+                        section = Section::Body;
+                        body.push(converted);
+                    }
+                    Section::Body => body.push(converted),
+                    Section::Argument => append_to_last(&mut arguments, converted.trim()),
+                    Section::Returns => append_to_last(&mut returns, converted.trim()),
+                }
+                continue;
+            }
+        };
+        let rest = convert_inline(rest, resolve);
+        match cmd {
+            "brief" | "short" | "details" => {
+                section = Section::Body;
+                body.push(rest.trim_start().to_string());
+            }
+            "param" | "tparam" => {
+                let rest = rest.trim_start();
+                // Skip any direction, e.g. @param[in]
+                let rest = match rest.strip_prefix('[') {
+                    Some(dir) => dir.split_once(']').map_or(rest, |(_, r)| r.trim_start()),
+                    None => rest,
+                };
+                let (name, desc) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                arguments.push(format!("* `{}` - {}", name, desc.trim()));
+                section = Section::Argument;
+            }
+            "return" | "returns" | "result" => {
+                returns.push(rest.trim().to_string());
+                section = Section::Returns;
+            }
+            "retval" => {
+                let rest = rest.trim();
+                let (value, desc) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                returns.push(format!("* `{}` - {}", value, desc.trim()));
+                section = Section::Returns;
+            }
+            "code" => {
+                body.push("```cpp".to_string());
+                code_block_end = Some("endcode");
+            }
+            "verbatim" => {
+                body.push("```text".to_string());
+                code_block_end = Some("endverbatim");
+            }
+            "see" | "sa" => {
+                see_also.extend(
+                    rest.split(',')
+                        .map(str::trim)
+                        .filter(|name| !name.is_empty())
+                        .map(|name| format!("* {}", make_link(name, resolve))),
+                );
+                section = Section::Body;
+            }
+            "note" | "warning" | "attention" | "pre" | "post" | "deprecated" | "since" | "todo"
+            | "bug" => {
+                body.push(format!("**{}:** {}", heading_for(cmd), rest.trim()));
+                section = Section::Body;
+            }
+            _ => {
+                body.push(line.to_string());
+                section = Section::Body;
+            }
+        }
+    }
+    if code_block_end.is_some() {
+        body.push("```".to_string());
+    }
+    while body.last().map_or(false, |l| l.trim().is_empty()) {
+        body.pop();
+    }
+    for (heading, items) in [
+        ("Arguments", arguments),
+        ("Returns", returns),
+        ("See also", see_also),
+    ] {
+        if !items.is_empty() {
+            if !body.is_empty() {
+                body.push(String::new());
+            }
+            body.push(format!("# {}", heading));
+            body.push(String::new());
+            body.extend(items);
+        }
+    }
+    body.iter()
+        .map(|l| format!(" {}", l).trim_end().to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// If this line starts with a Doxygen command, returns the command and
+/// the remainder of the line.
+fn get_command(line: &str) -> Option<(&str, &str)> {
+    let line = line.strip_prefix('@').or_else(|| line.strip_prefix('\\'))?;
+    let end = line
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(line.len());
+    if end == 0 {
+        return None;
+    }
+    let (cmd, rest) = line.split_at(end);
+    // Allow things like \code{.cpp} and @param[in]
+    let rest = if cmd == "code" {
+        rest.strip_prefix('{')
+            .and_then(|r| r.split_once('}'))
+            .map_or(rest, |(_, r)| r)
+    } else {
+        rest
+    };
+    Some((cmd, rest))
+}
+
+fn heading_for(cmd: &str) -> &'static str {
+    match cmd {
+        "note" => "Note",
+        "warning" => "Warning",
+        "attention" => "Attention",
+        "pre" => "Precondition",
+        "post" => "Postcondition",
+        "deprecated" => "Deprecated",
+        "since" => "Since",
+        "todo" => "To do",
+        _ => "Bug",
+    }
+}
+
+fn append_to_last(items: &mut [String], more: &str) {
+    if let Some(last) = items.last_mut() {
+        if !more.is_empty() {
+            last.push(' ');
+            last.push_str(more);
+        }
+    }
+}
+
+/// Converts inline Doxygen commands such as `\c word` or `\ref Name`.
+fn convert_inline(line: &str, resolve: &dyn Fn(&str) -> Option<String>) -> String {
+    let indent = &line[..line.len() - line.trim_start().len()];
+    let mut words = line.split(' ').filter(|w| !w.is_empty());
+    let mut output = Vec::new();
+    while let Some(word) = words.next() {
+        let cmd = word.strip_prefix('@').or_else(|| word.strip_prefix('\\'));
+        let converted = match cmd {
+            Some("c") | Some("p") => words.next().map(|w| wrap_word(w, "`", "`")),
+            Some("b") => words.next().map(|w| wrap_word(w, "**", "**")),
+            Some("e") | Some("a") | Some("em") => words.next().map(|w| wrap_word(w, "*", "*")),
+            Some("ref") | Some("link") => words.next().map(|w| {
+                let (name, punctuation) = split_trailing_punctuation(w);
+                format!("{}{}", make_link(name, resolve), punctuation)
+            }),
+            Some("endlink") => Some(String::new()),
+            _ => None,
+        };
+        output.push(converted.unwrap_or_else(|| word.to_string()));
+    }
+    format!(
+        "{}{}",
+        indent,
+        output
+            .into_iter()
+            .filter(|w| !w.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    )
+}
+
+fn wrap_word(word: &str, before: &str, after: &str) -> String {
+    let (word, punctuation) = split_trailing_punctuation(word);
+    format!("{}{}{}{}", before, word, after, punctuation)
+}
+
+fn split_trailing_punctuation(word: &str) -> (&str, &str) {
+    let end = word.trim_end_matches(&['.', ',', ';', ':', '!', '?'][..]);
+    word.split_at(end.len())
+}
+
+fn make_link(name: &str, resolve: &dyn Fn(&str) -> Option<String>) -> String {
+    match resolve(name.trim_end_matches("()")) {
+        Some(path) => format!("[`{}`]({})", name, path),
+        None => format!("`{}`", name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::doxygen_to_rustdoc;
+
+    fn resolve(name: &str) -> Option<String> {
+        if name == "Widget" {
+            Some("Widget".to_string())
+        } else {
+            None
+        }
+    }
+
+    #[test]
+    fn test_plain_comment_unchanged() {
+        assert_eq!(
+            doxygen_to_rustdoc(" Does a thing.\n Another line.", &resolve),
+            " Does a thing.\n Another line."
+        );
+    }
+
+    #[test]
+    fn test_params_and_return() {
+        let input = " @brief Frobs the \\p widget.\n\n @param[in] widget The thing\n   to frob.\n @param count How many.\n \\return Whether it worked.";
+        let expected = " Frobs the `widget`.\n\n # Arguments\n\n * `widget` - The thing to frob.\n * `count` - How many.\n\n # Returns\n\n Whether it worked.";
+        assert_eq!(doxygen_to_rustdoc(input, &resolve), expected);
+    }
+
+    #[test]
+    fn test_code_block() {
+        let input = " Example:\n \\code{.cpp}\n   frob(w);\n \\endcode";
+        let expected = " Example:\n ```cpp\n   frob(w);\n ```";
+        assert_eq!(doxygen_to_rustdoc(input, &resolve), expected);
+    }
+
+    #[test]
+    fn test_links() {
+        let input = " Works with \\ref Widget and \\ref Gadget.\n @see Widget, Gadget";
+        let expected = " Works with [`Widget`](Widget) and `Gadget`.\n\n # See also\n\n * [`Widget`](Widget)\n * `Gadget`";
+        assert_eq!(doxygen_to_rustdoc(input, &resolve), expected);
+    }
+
+    #[test]
+    fn test_note() {
+        assert_eq!(
+            doxygen_to_rustdoc(" @note Not \\b thread-safe.", &resolve),
+            " **Note:** Not **thread-safe**."
+        );
+    }
+}
@@ -136,10 +136,10 @@ pub(crate) fn convert_apis<FF, SF, EF, TF, A, B: 'static>(
                             cpp_impl,
                             is_trivial,
                         }))),
-                        Api::Subclass { name, superclass } => {
+                        Api::Subclass { name, superclasses } => {
                             Ok(Box::new(std::iter::once(Api::Subclass {
                                 name,
-                                superclass,
+                                superclasses,
                             })))
                         }
                         Api::IgnoredItem { name, err, ctx } => {
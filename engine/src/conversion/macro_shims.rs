@@ -0,0 +1,47 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+
+use autocxx_parser::IncludeCppConfig;
+
+/// Prefix for the constants we generate in order to evaluate
+/// preprocessor macros.
+pub(crate) const MACRO_SHIM_PREFIX: &str = "autocxx_macro_";
+
+/// bindgen only manages to evaluate simple integer macros. For anything
+/// on the allowlist which bindgen found to be an object-like macro, we
+/// generate a `constexpr` variable initialized from it, so that the C++
+/// compiler evaluates it (including casts, references to other macros and
+/// string literals). bindgen then tells us the resulting value and type,
+/// and we rename it back to the name of the macro. Function-like macros
+/// get no shim, since naming them without arguments wouldn't compile.
+pub(crate) fn generate_macro_shims(
+    config: &IncludeCppConfig,
+    object_like_macros: &HashSet<String>,
+) -> String {
+    config
+        .possible_macros()
+        .filter(|name| object_like_macros.contains(*name))
+        .map(|name| format!("constexpr auto {}{} = {};\n", MACRO_SHIM_PREFIX, name, name))
+        .collect()
+}
+
+/// The names of the variables generated by [generate_macro_shims], which
+/// we need to tell bindgen about.
+pub(crate) fn macro_shim_names(config: &IncludeCppConfig) -> impl Iterator<Item = String> + '_ {
+    config
+        .possible_macros()
+        .map(|name| format!("{}{}", MACRO_SHIM_PREFIX, name))
+}
@@ -20,7 +20,10 @@ mod codegen_rs;
 mod conversion_tests;
 mod convert_error;
 mod doc_attr;
+mod doxygen;
 mod error_reporter;
+mod macro_shims;
+mod naming_policy;
 mod parse;
 mod utilities;
 
@@ -29,6 +32,7 @@ use autocxx_parser::IncludeCppConfig;
 pub(crate) use codegen_cpp::CppCodeGenerator;
 pub(crate) use convert_error::ConvertError;
 use itertools::Itertools;
+pub(crate) use macro_shims::{generate_macro_shims, macro_shim_names};
 use syn::{Item, ItemMod};
 
 use crate::{CppFilePair, UnsafePolicy};
@@ -148,7 +152,7 @@ impl<'a> BridgeConverter<'a> {
                 let analyzed_apis = mark_types_abstract(self.config, analyzed_apis);
                 Self::dump_apis_with_deps("marking abstract", &analyzed_apis);
                 // Remove any APIs whose names are not compatible with cxx.
-                let analyzed_apis = check_names(analyzed_apis);
+                let analyzed_apis = check_names(self.config, analyzed_apis);
                 // During parsing or subsequent processing we might have encountered
                 // items which we couldn't process due to as-yet-unsupported features.
                 // There might be other items depending on such things. Let's remove them
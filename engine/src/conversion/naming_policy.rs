@@ -0,0 +1,87 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Conversion of C++ names into names which follow Rust conventions,
+//! as requested by the `naming_policy!` directive.
+
+use crate::types::validate_ident_ok_for_rust;
+
+/// Converts a C++ function or method name (e.g. `GetFrameCount`) to
+/// snake_case (e.g. `get_frame_count`). Runs of capitals are treated as
+/// a single word, so `GetURLForID` becomes `get_url_for_id`. If the result
+/// would be a Rust keyword, an underscore is appended.
+pub(crate) fn to_snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut result = String::with_capacity(name.len() + 4);
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).map_or(false, |n| n.is_lowercase());
+            let starts_word = prev.is_lowercase()
+                || prev.is_ascii_digit()
+                || (prev.is_uppercase() && next_is_lower);
+            if starts_word {
+                result.push('_');
+            }
+        }
+        result.extend(c.to_lowercase());
+    }
+    if validate_ident_ok_for_rust(&result).is_err() && !result.ends_with('_') {
+        result.push('_');
+    }
+    result
+}
+
+/// Converts a C++ type name (e.g. `frame_buffer_t`) to UpperCamelCase
+/// (e.g. `FrameBufferT`). Names with a leading underscore are left alone,
+/// as are the existing capitals within each word.
+pub(crate) fn to_upper_camel_case(name: &str) -> String {
+    if name.starts_with('_') {
+        return name.to_string();
+    }
+    name.split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                None => String::new(),
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_snake_case, to_upper_camel_case};
+
+    #[test]
+    fn test_snake_case() {
+        assert_eq!(to_snake_case("GetFrameCount"), "get_frame_count");
+        assert_eq!(to_snake_case("getFrameCount"), "get_frame_count");
+        assert_eq!(to_snake_case("get_frame_count"), "get_frame_count");
+        assert_eq!(to_snake_case("GetURLForID"), "get_url_for_id");
+        assert_eq!(to_snake_case("Read2"), "read2");
+        assert_eq!(to_snake_case("Vec3Length"), "vec3_length");
+        assert_eq!(to_snake_case("make_unique1"), "make_unique1");
+        assert_eq!(to_snake_case("Move"), "move_");
+    }
+
+    #[test]
+    fn test_upper_camel_case() {
+        assert_eq!(to_upper_camel_case("frame_buffer_t"), "FrameBufferT");
+        assert_eq!(to_upper_camel_case("FrameBuffer"), "FrameBuffer");
+        assert_eq!(to_upper_camel_case("vec3_f"), "Vec3F");
+        assert_eq!(to_upper_camel_case("_Internal"), "_Internal");
+    }
+}
@@ -19,8 +19,7 @@ use crate::{
         api::{Api, ApiName, SubclassName, TypedefKind, UnanalyzedApi},
         ConvertError,
     },
-    types::Namespace,
-    types::QualifiedName,
+    types::{make_ident, Namespace, QualifiedName},
 };
 use crate::{
     conversion::{
@@ -30,12 +29,29 @@ use crate::{
     types::validate_ident_ok_for_cxx,
 };
 use autocxx_parser::IncludeCppConfig;
-use syn::{parse_quote, Attribute, Fields, Ident, Item, LitStr, TypePath, UseTree};
+use syn::{
+    parse_quote, punctuated::Punctuated, Attribute, Expr, ExprLit, Fields, GenericArgument, Ident,
+    Item, ItemConst, ItemFn, ItemStruct, Lit, LitStr, PathArguments, Stmt, Token, Type, TypePath,
+    UseTree, Visibility,
+};
 
-use super::super::utilities::generate_utilities;
+use super::super::{macro_shims::MACRO_SHIM_PREFIX, utilities::generate_utilities};
 
 use super::parse_foreign_mod::ParseForeignMod;
 
+/// The type which bindgen uses to store bitfields.
+const BITFIELD_UNIT: &str = "__BindgenBitfieldUnit";
+
+/// The type which bindgen uses for members of unions which it can't
+/// represent as a native Rust `union`.
+const UNION_FIELD: &str = "__BindgenUnionField";
+
+/// The storage field which bindgen adds to such unions.
+const UNION_STORAGE_FIELD: &str = "bindgen_union_field";
+
+/// The prefix of the tests which bindgen generates to check struct layout.
+const LAYOUT_TEST_PREFIX: &str = "bindgen_test_layout_";
+
 /// Parses a bindgen mod in order to understand the APIs within it.
 pub(crate) struct ParseBindgen<'a> {
     config: &'a IncludeCppConfig,
@@ -45,6 +61,9 @@ pub(crate) struct ParseBindgen<'a> {
     /// even if the 'this' is actually recorded as void in the
     /// function signature.
     latest_virtual_this_type: Option<QualifiedName>,
+    /// Macros for which we've found the value calculated by the
+    /// C++ compiler.
+    macros_found: HashSet<QualifiedName>,
 }
 
 fn api_name(ns: &Namespace, id: Ident, attrs: &[Attribute]) -> ApiName {
@@ -91,6 +110,7 @@ impl<'a> ParseBindgen<'a> {
             config,
             apis: Vec::new(),
             latest_virtual_this_type: None,
+            macros_found: HashSet::new(),
         }
     }
 
@@ -114,11 +134,25 @@ impl<'a> ParseBindgen<'a> {
     /// Some API items are not populated from bindgen output, but instead
     /// directly from items in the config.
     fn add_apis_from_config(&mut self) {
-        self.apis
-            .extend(self.config.subclasses.iter().map(|sc| Api::Subclass {
-                name: SubclassName::new(sc.subclass.clone()),
-                superclass: QualifiedName::new_from_cpp_name(&sc.superclass),
-            }));
+        // A subclass of several superclasses has one config entry
+        // per superclass.
+        let mut subclasses: Vec<(&Ident, Vec<QualifiedName>)> = Vec::new();
+        for sc in &self.config.subclasses {
+            let superclass = QualifiedName::new_from_cpp_name(&sc.superclass);
+            match subclasses.iter_mut().find(|(id, _)| **id == sc.subclass) {
+                Some((_, superclasses)) => superclasses.push(superclass),
+                None => subclasses.push((&sc.subclass, vec![superclass])),
+            }
+        }
+        let config = self.config;
+        self.apis.extend(
+            subclasses
+                .into_iter()
+                .map(|(id, superclasses)| Api::Subclass {
+                    name: SubclassName::new(id.clone(), config),
+                    superclasses,
+                }),
+        );
         self.apis
             .extend(self.config.extern_rust_funs.iter().map(|fun| {
                 let id = fun.sig.ident.clone();
@@ -183,32 +217,43 @@ impl<'a> ParseBindgen<'a> {
                     .convert_foreign_mod_items(fm.items, self.latest_virtual_this_type.clone());
                 Ok(())
             }
-            Item::Struct(s) => {
-                if s.ident.to_string().ends_with("__bindgen_vtable") {
+            Item::Struct(mut s) => {
+                if s.ident.to_string().ends_with("__bindgen_vtable")
+                    || s.ident == BITFIELD_UNIT
+                    || s.ident == UNION_FIELD
+                {
                     return Ok(());
                 }
-                let is_forward_declaration = Self::spot_forward_declaration(&s.fields);
-                // cxx::bridge can't cope with type aliases to generic
-                // types at the moment.
-                let name = api_name_qualified(ns, s.ident.clone(), &s.attrs)?;
-                let api = if ns.is_empty() && self.config.is_rust_type(&s.ident) {
-                    None
-                } else if is_forward_declaration {
-                    Some(UnanalyzedApi::ForwardDeclaration { name })
-                } else {
-                    Some(UnanalyzedApi::Struct {
-                        name,
-                        item: s,
-                        analysis: (),
-                    })
-                };
-                if let Some(api) = api {
-                    self.latest_virtual_this_type = Some(api.name().clone());
-                    if !self.config.is_on_blocklist(&api.name().to_cpp_name()) {
-                        self.apis.push(api);
-                    }
+                if Self::make_fields_public(&mut s.fields) {
+                    s.attrs.push(parse_quote! { #[bindgen_nonpublic_fields] });
                 }
-                Ok(())
+                mod_converter
+                    .note_bitfield_units(s.ident.clone(), Self::get_bitfield_units(&s.fields));
+                Self::make_bitfields_opaque(&mut s.fields);
+                Self::spot_union_fields(&mut s);
+                self.parse_struct(s, ns)
+            }
+            Item::Union(u) => {
+                // We represent unions as structs until codegen time, with an
+                // annotation to tell us to turn them back into unions.
+                let mut attrs = u.attrs;
+                attrs.push(parse_quote! { #[bindgen_union] });
+                let mut fields = Fields::Named(u.fields);
+                if Self::make_fields_public(&mut fields) {
+                    attrs.push(parse_quote! { #[bindgen_nonpublic_fields] });
+                }
+                self.parse_struct(
+                    ItemStruct {
+                        attrs,
+                        vis: u.vis,
+                        struct_token: Token![struct](u.union_token.span),
+                        ident: u.ident,
+                        generics: u.generics,
+                        fields,
+                        semi_token: None,
+                    },
+                    ns,
+                )
             }
             Item::Enum(e) => {
                 let api = UnanalyzedApi::Enum {
@@ -220,6 +265,11 @@ impl<'a> ParseBindgen<'a> {
                 }
                 Ok(())
             }
+            Item::Fn(f) if f.sig.ident.to_string().starts_with(LAYOUT_TEST_PREFIX) => {
+                self.record_layout(f, ns);
+                Ok(())
+            }
+            Item::Impl(imp) if Self::is_bindgen_utility_type(&imp.self_ty) => Ok(()),
             Item::Impl(imp) => {
                 // We *mostly* ignore all impl blocks generated by bindgen.
                 // Methods also appear in 'extern "C"' blocks which
@@ -292,7 +342,29 @@ impl<'a> ParseBindgen<'a> {
                 }
                 Ok(())
             }
-            Item::Const(const_item) => {
+            Item::Const(mut const_item) => {
+                let macro_name = const_item
+                    .ident
+                    .to_string()
+                    .strip_prefix(MACRO_SHIM_PREFIX)
+                    .map(make_ident);
+                if let Some(macro_name) = macro_name {
+                    // This is the C++ compiler's evaluation of a macro, which
+                    // supersedes anything bindgen managed to work out itself.
+                    let name = QualifiedName::new(ns, macro_name.clone());
+                    self.apis.retain(|api| match api {
+                        UnanalyzedApi::Const { name: n, .. } => n.name != name,
+                        _ => true,
+                    });
+                    Self::convert_macro_value(&mut const_item);
+                    const_item.ident = macro_name;
+                    self.macros_found.insert(name);
+                } else if self
+                    .macros_found
+                    .contains(&QualifiedName::new(ns, const_item.ident.clone()))
+                {
+                    return Ok(());
+                }
                 self.apis.push(UnanalyzedApi::Const {
                     name: api_name(ns, const_item.ident.clone(), &const_item.attrs),
                     const_item,
@@ -315,6 +387,223 @@ impl<'a> ParseBindgen<'a> {
         }
     }
 
+    fn parse_struct(
+        &mut self,
+        s: ItemStruct,
+        ns: &Namespace,
+    ) -> Result<(), ConvertErrorWithContext> {
+        let is_forward_declaration = Self::spot_forward_declaration(&s.fields);
+        // cxx::bridge can't cope with type aliases to generic
+        // types at the moment.
+        let name = api_name_qualified(ns, s.ident.clone(), &s.attrs)?;
+        let api = if ns.is_empty() && self.config.is_rust_type(&s.ident) {
+            None
+        } else if is_forward_declaration {
+            Some(UnanalyzedApi::ForwardDeclaration { name })
+        } else {
+            Some(UnanalyzedApi::Struct {
+                name,
+                item: s,
+                analysis: (),
+            })
+        };
+        if let Some(api) = api {
+            self.latest_virtual_this_type = Some(api.name().clone());
+            if !self.config.is_on_blocklist(&api.name().to_cpp_name()) {
+                self.apis.push(api);
+            }
+        }
+        Ok(())
+    }
+
+    /// bindgen represents string constants as null-terminated byte strings.
+    /// Where a macro was a string, we'd rather give Rust a `&str`.
+    fn convert_macro_value(const_item: &mut ItemConst) {
+        if let Expr::Lit(ExprLit {
+            lit: Lit::ByteStr(bytes),
+            ..
+        }) = const_item.expr.as_ref()
+        {
+            let mut value = bytes.value();
+            if value.last() == Some(&0) {
+                value.pop();
+            }
+            if let Ok(value) = String::from_utf8(value) {
+                let value = LitStr::new(&value, bytes.span());
+                const_item.ty = parse_quote! { &'static str };
+                const_item.expr = parse_quote! { #value };
+            }
+        }
+    }
+
+    /// bindgen generates tests which check that the Rust layout of each
+    /// struct matches what clang told it about the C++ layout. We don't
+    /// pass those tests on; instead we annotate the struct with the layout
+    /// which they describe, so that we can later generate equivalent
+    /// compile-time checks on both the C++ and Rust sides.
+    fn record_layout(&mut self, f: ItemFn, ns: &Namespace) {
+        let ty_id = match f.sig.ident.to_string().strip_prefix(LAYOUT_TEST_PREFIX) {
+            Some(ty_id) => make_ident(ty_id),
+            None => return,
+        };
+        let tyname = QualifiedName::new(ns, ty_id);
+        let layout_attrs = f.block.stmts.iter().filter_map(Self::get_layout_assertion);
+        let item = self.apis.iter_mut().rev().find_map(|api| match api {
+            Api::Struct { name, item, .. } if name.name == tyname => Some(item),
+            _ => None,
+        });
+        if let Some(item) = item {
+            item.attrs.extend(layout_attrs);
+        }
+    }
+
+    /// Turns one of the `assert_eq!`s within a bindgen layout test into
+    /// a `bindgen_size`, `bindgen_align` or `bindgen_field_offset` attribute.
+    fn get_layout_assertion(stmt: &Stmt) -> Option<Attribute> {
+        let mac = match stmt {
+            Stmt::Item(Item::Macro(m)) => &m.mac,
+            Stmt::Semi(Expr::Macro(m), _) | Stmt::Expr(Expr::Macro(m)) => &m.mac,
+            _ => return None,
+        };
+        if !mac.path.is_ident("assert_eq") {
+            return None;
+        }
+        let args = mac
+            .parse_body_with(Punctuated::<Expr, Token![,]>::parse_terminated)
+            .ok()?;
+        let value = match args.iter().nth(1)? {
+            Expr::Lit(ExprLit {
+                lit: Lit::Int(value),
+                ..
+            }) => value,
+            _ => return None,
+        };
+        // The final argument is a description of the form
+        // concat!("Offset of field: ", stringify!(Foo), "::", stringify!(a))
+        let description = match args.iter().nth(2)? {
+            Expr::Macro(m) => m
+                .mac
+                .parse_body_with(Punctuated::<Expr, Token![,]>::parse_terminated)
+                .ok()?,
+            _ => return None,
+        };
+        let description_kind = match description.first()? {
+            Expr::Lit(ExprLit {
+                lit: Lit::Str(kind),
+                ..
+            }) => kind.value(),
+            _ => return None,
+        };
+        if description_kind.starts_with("Size of") {
+            Some(parse_quote! { #[bindgen_size(#value)] })
+        } else if description_kind.starts_with("Alignment of") {
+            Some(parse_quote! { #[bindgen_align(#value)] })
+        } else if description_kind.starts_with("Offset of field") {
+            let field = match description.last()? {
+                Expr::Macro(m) => &m.mac.tokens,
+                _ => return None,
+            };
+            Some(parse_quote! { #[bindgen_field_offset(#field, #value)] })
+        } else {
+            None
+        }
+    }
+
+    /// We ask bindgen to respect C++ access specifiers, so that we know
+    /// which fields are private or protected in C++. But we don't want that
+    /// to change the Rust API, so we make such fields public again, and return
+    /// whether there were any.
+    fn make_fields_public(fields: &mut Fields) -> bool {
+        let mut found_nonpublic = false;
+        for f in fields.iter_mut() {
+            if !matches!(f.vis, Visibility::Public(_)) {
+                f.vis = parse_quote! { pub };
+                found_nonpublic = true;
+            }
+        }
+        found_nonpublic
+    }
+
+    fn get_bitfield_units(fields: &Fields) -> HashSet<Ident> {
+        fields
+            .iter()
+            .filter(|f| Self::get_generic_arg_of(&f.ty, BITFIELD_UNIT).is_some())
+            .filter_map(|f| f.ident.clone())
+            .collect()
+    }
+
+    /// bindgen represents the storage for a group of bitfields using its
+    /// own generic `__BindgenBitfieldUnit` type. We don't pass that on;
+    /// instead the storage becomes an opaque byte array of the same layout,
+    /// and the individual bitfields are accessed via C++ wrapper functions.
+    fn make_bitfields_opaque(fields: &mut Fields) {
+        for f in fields.iter_mut() {
+            if let Some(storage) = Self::get_generic_arg_of(&f.ty, BITFIELD_UNIT) {
+                f.ty = storage;
+            }
+        }
+    }
+
+    /// bindgen represents unions which can't be native Rust unions as
+    /// structs, where each member is wrapped in a `__BindgenUnionField`
+    /// and the storage is in an extra field. We turn those back into the
+    /// member types themselves, and annotate the struct so that we treat
+    /// it as a union from here on.
+    fn spot_union_fields(s: &mut ItemStruct) {
+        let is_union = s.fields.iter().any(|f| {
+            f.ident
+                .as_ref()
+                .map_or(false, |id| id == UNION_STORAGE_FIELD)
+        });
+        if !is_union {
+            return;
+        }
+        if let Fields::Named(fields) = &mut s.fields {
+            fields.named = fields
+                .named
+                .iter()
+                .filter(|f| {
+                    f.ident
+                        .as_ref()
+                        .map_or(true, |id| id != UNION_STORAGE_FIELD)
+                })
+                .cloned()
+                .map(|mut f| {
+                    if let Some(inner) = Self::get_generic_arg_of(&f.ty, UNION_FIELD) {
+                        f.ty = inner;
+                    }
+                    f
+                })
+                .collect();
+        }
+        s.attrs.push(parse_quote! { #[bindgen_union] });
+    }
+
+    /// If this type is `SomeType<T>` where `SomeType` is the given
+    /// bindgen utility type, return `T`.
+    fn get_generic_arg_of(ty: &Type, utility_type: &str) -> Option<Type> {
+        match ty {
+            Type::Path(typ) => {
+                let seg = typ.path.segments.last()?;
+                if seg.ident != utility_type {
+                    return None;
+                }
+                match &seg.arguments {
+                    PathArguments::AngleBracketed(args) => match args.args.first() {
+                        Some(GenericArgument::Type(inner)) => Some(inner.clone()),
+                        _ => None,
+                    },
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn is_bindgen_utility_type(ty: &Type) -> bool {
+        matches!(ty, Type::Path(typ) if typ.path.segments.last().map_or(false, |seg| seg.ident == BITFIELD_UNIT || seg.ident == UNION_FIELD))
+    }
+
     fn spot_forward_declaration(s: &Fields) -> bool {
         s.iter()
             .filter_map(|f| f.ident.as_ref())
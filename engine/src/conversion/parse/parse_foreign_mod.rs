@@ -16,18 +16,18 @@ use crate::conversion::api::ApiName;
 use crate::conversion::doc_attr::get_doc_attr;
 use crate::conversion::error_reporter::report_any_error;
 use crate::conversion::{
-    api::{FuncToConvert, UnanalyzedApi},
+    api::{FieldAccessor, FuncToConvert, UnanalyzedApi},
     convert_error::ConvertErrorWithContext,
     convert_error::ErrorContext,
 };
 use crate::{
     conversion::ConvertError,
-    types::{Namespace, QualifiedName},
+    types::{make_ident, Namespace, QualifiedName},
 };
 use std::collections::{HashMap, HashSet};
 use syn::{
-    Block, Expr, ExprCall, ForeignItem, ForeignItemFn, Ident, ImplItem, ItemImpl, LitStr, Stmt,
-    Type,
+    parse_quote, Block, Expr, ExprCall, FnArg, ForeignItem, ForeignItemFn, Ident, ImplItem,
+    ImplItemMethod, ItemImpl, LitStr, Pat, PatType, Receiver, Stmt, Type, Visibility,
 };
 
 use super::parse_bindgen::get_bindgen_original_name_annotation;
@@ -47,6 +47,9 @@ pub(crate) struct ParseForeignMod {
     // may actually be methods (static or otherwise). Mapping from
     // function name to type name.
     method_receivers: HashMap<Ident, QualifiedName>,
+    // The fields of each struct which bindgen uses to store bitfields,
+    // e.g. `_bitfield_1`.
+    bitfield_units: HashMap<Ident, HashSet<Ident>>,
     ignored_apis: Vec<UnanalyzedApi>,
 }
 
@@ -56,6 +59,7 @@ impl ParseForeignMod {
             ns,
             funcs_to_convert: Vec::new(),
             method_receivers: HashMap::new(),
+            bitfield_units: HashMap::new(),
             ignored_apis: Vec::new(),
         }
     }
@@ -107,6 +111,7 @@ impl ParseForeignMod {
                     return_type_is_reference,
                     reference_args,
                     original_name,
+                    field_accessor: None,
                 });
                 Ok(())
             }
@@ -162,6 +167,14 @@ impl ParseForeignMod {
         (ref_params, ref_return)
     }
 
+    /// Record which fields of a struct are bindgen's storage for bitfields,
+    /// so that we can spot the bitfield accessors in its impl block.
+    pub(crate) fn note_bitfield_units(&mut self, ty_id: Ident, units: HashSet<Ident>) {
+        if !units.is_empty() {
+            self.bitfield_units.insert(ty_id, units);
+        }
+    }
+
     /// Record information from impl blocks encountered in bindgen
     /// output.
     pub(crate) fn convert_impl_items(&mut self, imp: ItemImpl) {
@@ -169,8 +182,17 @@ impl ParseForeignMod {
             Type::Path(typ) => typ.path.segments.last().unwrap().ident.clone(),
             _ => return,
         };
+        let bitfields = self.find_bitfield_names(&ty_id, &imp.items);
         for i in imp.items {
             if let ImplItem::Method(itm) = i {
+                if let Some(accessor) = Self::get_bitfield_accessor(&ty_id, &itm, &bitfields) {
+                    self.method_receivers.insert(
+                        accessor.ident.clone(),
+                        QualifiedName::new(&self.ns, ty_id.clone()),
+                    );
+                    self.funcs_to_convert.push(accessor);
+                    continue;
+                }
                 let effective_fun_name = if itm.sig.ident == "new" {
                     ty_id.clone()
                 } else {
@@ -187,6 +209,98 @@ impl ParseForeignMod {
         }
     }
 
+    /// For each bitfield storage unit `_bitfield_N`, bindgen generates a
+    /// `new_bitfield_N` function taking the value of each bitfield stored
+    /// within it. Those parameters therefore tell us the names of the bitfields.
+    fn find_bitfield_names(&self, ty_id: &Ident, items: &[ImplItem]) -> HashSet<Ident> {
+        let units = match self.bitfield_units.get(ty_id) {
+            Some(units) => units,
+            None => return HashSet::new(),
+        };
+        items
+            .iter()
+            .filter_map(|i| match i {
+                ImplItem::Method(itm) => Some(itm),
+                _ => None,
+            })
+            .filter(|itm| {
+                itm.sig
+                    .ident
+                    .to_string()
+                    .strip_prefix("new")
+                    .map_or(false, |unit| units.contains(&make_ident(unit)))
+            })
+            .flat_map(|itm| itm.sig.inputs.iter())
+            .filter_map(|arg| match arg {
+                FnArg::Typed(PatType { pat, .. }) => match pat.as_ref() {
+                    Pat::Ident(pi) => Some(pi.ident.clone()),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// bindgen generates getters and setters for bitfields, implemented
+    /// in Rust in terms of its own idea of the bitfield layout. Instead,
+    /// we make a function which calls a C++ wrapper to get or set the field,
+    /// so that the C++ compiler remains in charge of the layout.
+    fn get_bitfield_accessor(
+        ty_id: &Ident,
+        itm: &ImplItemMethod,
+        bitfields: &HashSet<Ident>,
+    ) -> Option<FuncToConvert> {
+        // Accessors for bitfields which are private or protected in C++
+        // aren't `pub`, and our C++ wrapper couldn't access those bitfields.
+        if bitfields.is_empty() || !matches!(itm.vis, Visibility::Public(_)) {
+            return None;
+        }
+        let mut inputs = itm.sig.inputs.iter();
+        let receiver_mutability = match inputs.next() {
+            Some(FnArg::Receiver(Receiver {
+                reference: Some(_),
+                mutability,
+                ..
+            })) => mutability,
+            _ => return None,
+        };
+        let method_name = itm.sig.ident.to_string();
+        let (field_accessor, inputs) = match (receiver_mutability, inputs.next()) {
+            (None, None) if bitfields.contains(&itm.sig.ident) => (
+                FieldAccessor::Get(itm.sig.ident.clone()),
+                parse_quote! { this: *const #ty_id },
+            ),
+            (Some(_), Some(FnArg::Typed(PatType { ty, .. }))) => {
+                let field_name = make_ident(method_name.strip_prefix("set_")?);
+                if !bitfields.contains(&field_name) {
+                    return None;
+                }
+                (
+                    FieldAccessor::Set(field_name),
+                    parse_quote! { this: *mut #ty_id, val: #ty },
+                )
+            }
+            _ => return None,
+        };
+        Some(FuncToConvert {
+            virtual_this_type: None,
+            self_ty: None,
+            ident: make_ident(format!("{}_{}", ty_id, method_name)),
+            doc_attr: get_doc_attr(&itm.attrs),
+            inputs,
+            output: itm.sig.output.clone(),
+            vis: parse_quote! { pub },
+            is_pure_virtual: false,
+            is_private: false,
+            is_move_constructor: false,
+            unused_template_param: false,
+            return_type_is_reference: false,
+            reference_args: HashSet::new(),
+            original_name: Some(method_name),
+            field_accessor: Some(field_accessor),
+        })
+    }
+
     /// Indicate that all foreign mods and all impl blocks have been
     /// fed into us, and we should process that information to generate
     /// the resulting APIs.
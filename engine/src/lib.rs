@@ -28,9 +28,8 @@ mod types;
 #[cfg(any(test, feature = "build"))]
 mod builder;
 
-use autocxx_parser::{IncludeCppConfig, UnsafePolicy};
-use conversion::BridgeConverter;
-use parse_callbacks::AutocxxParseCallbacks;
+use conversion::{generate_macro_shims, macro_shim_names, BridgeConverter};
+use parse_callbacks::{AutocxxParseCallbacks, ObjectLikeMacros};
 use parse_file::CppBuildable;
 use proc_macro2::TokenStream as TokenStream2;
 use std::{fmt::Display, path::PathBuf};
@@ -63,6 +62,8 @@ pub use builder::{
 };
 pub use parse_file::{parse_file, ParseError, ParsedFile};
 
+pub use autocxx_parser::{IncludeCppConfig, IncludeCppConfigBuilder, UnsafePolicy};
+
 pub use cxx_gen::HEADER;
 
 /// Re-export cxx such that clients can use the same version as
@@ -246,12 +247,7 @@ pub struct IncludeCppEngine {
 impl Parse for IncludeCppEngine {
     fn parse(input: ParseStream) -> ParseResult<Self> {
         let config = input.parse::<IncludeCppConfig>()?;
-        let state = if config.parse_only {
-            State::ParseOnly
-        } else {
-            State::NotGenerated
-        };
-        Ok(Self { config, state })
+        Ok(Self::new_from_config(config))
     }
 }
 
@@ -260,6 +256,18 @@ impl IncludeCppEngine {
         mac.parse_body::<IncludeCppEngine>().map_err(Error::Parsing)
     }
 
+    /// Creates an engine from a configuration which has been constructed
+    /// directly, typically using [`IncludeCppConfig::builder`], rather than
+    /// parsed from an `include_cpp!` macro.
+    pub fn new_from_config(config: IncludeCppConfig) -> Self {
+        let state = if config.parse_only {
+            State::ParseOnly
+        } else {
+            State::NotGenerated
+        };
+        Self { config, state }
+    }
+
     pub fn config_mut(&mut self) -> &mut IncludeCppConfig {
         assert!(
             matches!(self.state, State::NotGenerated),
@@ -292,7 +300,11 @@ impl IncludeCppEngine {
             })
             .enable_cxx_namespaces()
             .generate_inline_functions(true)
-            .layout_tests(false); // TODO revisit later
+            // Neither of these changes what we generate for the user: we
+            // consume bindgen's layout tests to learn the layout of each type,
+            // and make non-public fields public again, having noted them.
+            .respect_cxx_access_specs(true)
+            .layout_tests(true);
         for item in known_types().get_initial_blocklist() {
             builder = builder.blocklist_item(item);
         }
@@ -307,6 +319,9 @@ impl IncludeCppEngine {
                     .allowlist_function(&a)
                     .allowlist_var(&a);
             }
+            for shim in macro_shim_names(&self.config) {
+                builder = builder.allowlist_var(&shim);
+            }
         }
 
         log::info!(
@@ -373,16 +388,41 @@ impl IncludeCppEngine {
         }
 
         let mod_name = self.config.get_mod_name();
-        let mut builder = self.make_bindgen_builder(&inc_dirs, extra_clang_args);
-        if let Some(dep_recorder) = dep_recorder {
-            builder = builder.parse_callbacks(Box::new(AutocxxParseCallbacks(dep_recorder)));
-        }
         let header_contents = self.build_header();
         self.dump_header_if_so_configured(&header_contents, &inc_dirs, extra_clang_args);
-        let header_and_prelude = format!("{}\n\n{}", known_types().get_prelude(), header_contents);
-        builder = builder.header_contents("example.hpp", &header_and_prelude);
-
-        let bindings = builder.generate().map_err(Error::Bindgen)?;
+        let header_and_prelude =
+            format!("{}\n\n{}\n", known_types().get_prelude(), header_contents);
+        let object_like_macros = ObjectLikeMacros::default();
+        let callbacks = AutocxxParseCallbacks {
+            dep_recorder,
+            possible_macros: self.config.possible_macros().map(String::from).collect(),
+            object_like_macros: object_like_macros.clone(),
+        };
+        let bindings = self
+            .make_bindgen_builder(&inc_dirs, extra_clang_args)
+            .parse_callbacks(Box::new(callbacks))
+            .header_contents("example.hpp", &header_and_prelude)
+            .generate()
+            .map_err(Error::Bindgen)?;
+
+        // If any allowlisted names turned out to be object-like macros, parse
+        // again with macro shims so that the C++ compiler evaluates them.
+        // A macro which doesn't expand to a constant expression makes that
+        // parse fail, in which case we stick with what bindgen could work
+        // out without the shims.
+        let macro_shims = generate_macro_shims(&self.config, &object_like_macros.take());
+        let bindings = if macro_shims.is_empty() {
+            bindings
+        } else {
+            let header_and_shims = format!("{}{}", header_and_prelude, macro_shims);
+            self.make_bindgen_builder(&inc_dirs, extra_clang_args)
+                .header_contents("example.hpp", &header_and_shims)
+                .generate()
+                .unwrap_or_else(|_| {
+                    info!("Unable to evaluate macros using the C++ compiler");
+                    bindings
+                })
+        };
         let bindings = self.parse_bindings(bindings)?;
 
         let converter = BridgeConverter::new(&self.config.inclusions, &self.config);
@@ -12,18 +12,57 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::panic::UnwindSafe;
+use std::{
+    collections::HashSet,
+    panic::UnwindSafe,
+    sync::{Arc, Mutex},
+};
 
 use crate::RebuildDependencyRecorder;
-use autocxx_bindgen::callbacks::ParseCallbacks;
+use autocxx_bindgen::callbacks::{MacroParsingBehavior, ParseCallbacks};
+
+/// The names of allowlisted items which bindgen found to be object-like
+/// macros, and which we can therefore safely generate macro shims for.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct ObjectLikeMacros(Arc<Mutex<HashSet<String>>>);
+
+impl ObjectLikeMacros {
+    pub(crate) fn take(&self) -> HashSet<String> {
+        std::mem::take(&mut self.0.lock().unwrap())
+    }
+}
 
 #[derive(Debug)]
-pub(crate) struct AutocxxParseCallbacks(pub Box<dyn RebuildDependencyRecorder>);
+pub(crate) struct AutocxxParseCallbacks {
+    pub(crate) dep_recorder: Option<Box<dyn RebuildDependencyRecorder>>,
+    pub(crate) possible_macros: HashSet<String>,
+    pub(crate) object_like_macros: ObjectLikeMacros,
+}
 
 impl UnwindSafe for AutocxxParseCallbacks {}
 
 impl ParseCallbacks for AutocxxParseCallbacks {
     fn include_file(&self, filename: &str) {
-        self.0.record_header_file_dependency(filename);
+        if let Some(dep_recorder) = &self.dep_recorder {
+            dep_recorder.record_header_file_dependency(filename);
+        }
+    }
+
+    fn will_parse_macro(&self, name: &str) -> MacroParsingBehavior {
+        if self.possible_macros.contains(name) {
+            self.object_like_macros
+                .0
+                .lock()
+                .unwrap()
+                .insert(name.to_string());
+        }
+        MacroParsingBehavior::Default
+    }
+
+    fn func_macro(&self, name: &str, _value: &[&[u8]]) {
+        // bindgen calls this after will_parse_macro, with the name followed
+        // by the parameter list.
+        let name = name.split('(').next().unwrap_or_default();
+        self.object_like_macros.0.lock().unwrap().remove(name);
     }
 }
@@ -23,7 +23,7 @@ use proc_macro2::{Span, TokenStream};
 use quote::ToTokens;
 use std::{collections::HashSet, fmt::Display, io::Read, path::PathBuf};
 use std::{panic::UnwindSafe, path::Path, rc::Rc};
-use syn::{Item, LitStr};
+use syn::{spanned::Spanned, Item, LitStr};
 
 /// Errors which may occur when parsing a Rust source file to discover
 /// and interpret include_cxx macros.
@@ -77,17 +77,53 @@ pub fn parse_file<P1: AsRef<Path>>(
     auto_allowlist: bool,
 ) -> Result<ParsedFile, ParseError> {
     let mut source = String::new();
-    let mut file = std::fs::File::open(rs_file).map_err(ParseError::FileOpen)?;
+    let mut file = std::fs::File::open(rs_file.as_ref()).map_err(ParseError::FileOpen)?;
     file.read_to_string(&mut source)
         .map_err(ParseError::FileRead)?;
     proc_macro2::fallback::force();
     let source = syn::parse_file(&source).map_err(ParseError::Syntax)?;
-    parse_file_contents(source, auto_allowlist)
+    let default_subclass_cpp_namespace = std::env::var("CARGO_PKG_NAME")
+        .ok()
+        .and_then(|package| default_subclass_cpp_namespace(&package, rs_file.as_ref()));
+    parse_file_contents(source, auto_allowlist, default_subclass_cpp_namespace)
 }
 
-fn parse_file_contents(source: syn::File, auto_allowlist: bool) -> Result<ParsedFile, ParseError> {
+/// The C++ namespace for the peers of subclasses which don't ask for one:
+/// the package name followed by the Rust module path of the file, e.g.
+/// `my_crate::observers` for `src/observers.rs`. This stops peers from
+/// different crates and modules colliding. If we're not being run by cargo,
+/// there's no package name, and they live in the root namespace.
+fn default_subclass_cpp_namespace(package: &str, rs_file: &Path) -> Option<String> {
+    let mut segments = vec![package.replace('-', "_")];
+    let components = rs_file
+        .components()
+        .map(|c| c.as_os_str().to_str())
+        .collect::<Option<Vec<_>>>()?;
+    if let Some(src) = components.iter().rposition(|c| *c == "src") {
+        let (file, dirs) = components[src + 1..].split_last()?;
+        segments.extend(dirs.iter().map(|dir| dir.replace('-', "_")));
+        match file.strip_suffix(".rs")? {
+            "lib" | "main" | "mod" => {}
+            module => segments.push(module.replace('-', "_")),
+        }
+    }
+    let ns = segments.join("::");
+    if autocxx_parser::is_valid_subclass_cpp_namespace(&ns) {
+        Some(ns)
+    } else {
+        None
+    }
+}
+
+fn parse_file_contents(
+    source: syn::File,
+    auto_allowlist: bool,
+    default_subclass_cpp_namespace: Option<String>,
+) -> Result<ParsedFile, ParseError> {
     let mut results = Vec::new();
     let mut extra_superclasses = Vec::new();
+    let mut subclass_options = Vec::new();
+    let mut thread_safe_subclass_attr = None;
     let mut discoveries = Discoveries::default();
     for item in source.items {
         results.push(match item {
@@ -128,10 +164,35 @@ fn parse_file_contents(source: syn::File, auto_allowlist: bool) -> Result<Parsed
                         let args: SubclassAttrs = is_superclass_attr
                             .parse_args()
                             .map_err(ParseError::Syntax)?;
-                        if let Some(superclass) = args.superclass {
+                        if args.thread_safe.is_some() {
+                            thread_safe_subclass_attr = Some(is_superclass_attr.span());
+                        }
+                        if args.superclasses.is_empty() {
+                            // The superclass must be in a subclass! directive
+                            // within the include_cpp!, to which we'll apply this.
+                            if args.thread_safe.is_some()
+                                || args.on_panic.is_some()
+                                || args.cpp_namespace.is_some()
+                                || args.on_cpp_destroyed
+                            {
+                                subclass_options.push((
+                                    subclass.clone(),
+                                    args.thread_safe,
+                                    args.on_panic,
+                                    args.cpp_namespace.clone(),
+                                    args.on_cpp_destroyed,
+                                ));
+                            }
+                        }
+                        for superclass in args.superclasses {
                             extra_superclasses.push(Subclass {
                                 superclass,
-                                subclass,
+                                subclass: subclass.clone(),
+                                thread_safe: args.thread_safe,
+                                on_panic: args.on_panic,
+                                cpp_namespace: args.cpp_namespace.clone(),
+                                on_cpp_destroyed: args.on_cpp_destroyed,
+                                interface_impl: false,
                             })
                         }
                     }
@@ -150,7 +211,10 @@ fn parse_file_contents(source: syn::File, auto_allowlist: bool) -> Result<Parsed
     {
         return Err(ParseError::DiscoveredRustItemsWhenNotInAutoDiscover);
     }
-    if !extra_superclasses.is_empty() || (auto_allowlist && !discoveries.is_empty()) {
+    if !extra_superclasses.is_empty()
+        || !subclass_options.is_empty()
+        || (auto_allowlist && !discoveries.is_empty())
+    {
         let mut autocxx_seg_iterator = results.iter_mut().filter_map(|seg| match seg {
             Segment::Autocxx(engine) => Some(engine),
             _ => None,
@@ -163,6 +227,27 @@ fn parse_file_contents(source: syn::File, auto_allowlist: bool) -> Result<Parsed
                     .config_mut()
                     .subclasses
                     .append(&mut extra_superclasses);
+                for (subclass, lock, on_panic, cpp_namespace, on_cpp_destroyed) in subclass_options
+                {
+                    for sc in engine
+                        .config_mut()
+                        .subclasses
+                        .iter_mut()
+                        .filter(|sc| sc.subclass == subclass)
+                    {
+                        sc.thread_safe = lock.or(sc.thread_safe);
+                        sc.on_panic = on_panic.or(sc.on_panic);
+                        sc.cpp_namespace = cpp_namespace.clone().or(sc.cpp_namespace.take());
+                        sc.on_cpp_destroyed |= on_cpp_destroyed;
+                    }
+                }
+                if let Some(span) = thread_safe_subclass_attr {
+                    engine
+                        .config_mut()
+                        .unsafe_policy
+                        .check_thread_safety_allowed(span)
+                        .map_err(ParseError::Syntax)?;
+                }
                 if auto_allowlist {
                     for cpp in discoveries.cpp_list {
                         engine
@@ -191,6 +276,11 @@ fn parse_file_contents(source: syn::File, auto_allowlist: bool) -> Result<Parsed
         _ => None,
     });
     for seg in autocxx_seg_iterator {
+        if let Some(ns) = &default_subclass_cpp_namespace {
+            for sc in seg.config.subclasses.iter_mut() {
+                sc.cpp_namespace.get_or_insert_with(|| ns.clone());
+            }
+        }
         seg.config
             .confirm_complete(auto_allowlist)
             .map_err(ParseError::Syntax)?;
@@ -321,3 +411,29 @@ impl RebuildDependencyRecorder for CompositeDepRecorder {
         self.0.record_header_file_dependency(filename);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::default_subclass_cpp_namespace;
+    use std::path::Path;
+
+    #[test]
+    fn test_default_subclass_cpp_namespace() {
+        let ns = |rs_file| default_subclass_cpp_namespace("my-crate", Path::new(rs_file));
+        assert_eq!(ns("src/main.rs").as_deref(), Some("my_crate"));
+        assert_eq!(
+            ns("/home/me/my-crate/src/lib.rs").as_deref(),
+            Some("my_crate")
+        );
+        assert_eq!(
+            ns("src/observers/mod.rs").as_deref(),
+            Some("my_crate::observers")
+        );
+        assert_eq!(
+            ns("src/observers/impl.rs").as_deref(),
+            Some("my_crate::observers::impl")
+        );
+        assert_eq!(ns("/tmp/input.rs").as_deref(), Some("my_crate"));
+        assert_eq!(ns("src/2d.rs"), None);
+    }
+}
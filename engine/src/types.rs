@@ -25,6 +25,18 @@ pub(crate) fn make_ident<S: AsRef<str>>(id: S) -> Ident {
     Ident::new(id.as_ref(), Span::call_site())
 }
 
+/// Makes an identifier for part of a C++ namespace. The namespaces of
+/// subclass peers may be named after Rust keywords, in which case this is
+/// a raw identifier such as `r#impl`.
+pub(crate) fn make_ns_ident<S: AsRef<str>>(segment: S) -> Ident {
+    let segment = segment.as_ref();
+    if syn::parse_str::<Ident>(segment).is_ok() {
+        make_ident(segment)
+    } else {
+        Ident::new_raw(segment, Span::call_site())
+    }
+}
+
 /// Newtype wrapper for a C++ namespace.
 #[derive(Debug, PartialEq, PartialOrd, Eq, Hash, Clone)]
 #[allow(clippy::rc_buffer)]
@@ -50,7 +62,6 @@ impl Namespace {
         self.0.iter()
     }
 
-    #[cfg(test)]
     pub(crate) fn from_user_input(input: &str) -> Self {
         Self(Arc::new(input.split("::").map(|x| x.to_string()).collect()))
     }
@@ -172,7 +183,7 @@ impl QualifiedName {
         ["bindgen", "root"]
             .iter()
             .map(make_ident)
-            .chain(self.ns_segment_iter().map(make_ident))
+            .chain(self.ns_segment_iter().map(make_ns_ident))
             .chain(std::iter::once(self.get_final_ident()))
             .collect()
     }
@@ -190,11 +201,9 @@ impl QualifiedName {
         if let Some(known_type_path) = known_types().known_type_type_path(self) {
             known_type_path
         } else {
-            let root = "root".to_string();
-            let segs = std::iter::once(&root)
-                .chain(self.ns_segment_iter())
-                .chain(std::iter::once(&self.1))
-                .map(make_ident);
+            let segs = std::iter::once(make_ident("root"))
+                .chain(self.ns_segment_iter().map(make_ns_ident))
+                .chain(std::iter::once(make_ident(&self.1)));
             parse_quote! {
                 #(#segs)::*
             }
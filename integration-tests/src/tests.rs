@@ -1099,11 +1099,57 @@ fn test_define_str() {
         #define BOB \"foo\"
     "};
     let rs = quote! {
-        assert_eq!(std::str::from_utf8(ffi::BOB).unwrap().trim_end_matches(char::from(0)), "foo");
+        assert_eq!(ffi::BOB, "foo");
     };
     run_test(cxx, hdr, rs, &["BOB"], &[]);
 }
 
+#[test]
+fn test_define_expressions() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #define BASE 4
+        #define SHIFTED ((uint32_t)1 << BASE)
+        #define NAME \"bob\"
+        #define GREETING \"hello \" NAME
+    "};
+    let rs = quote! {
+        let shifted: u32 = ffi::SHIFTED;
+        assert_eq!(shifted, 16);
+        assert_eq!(ffi::GREETING, "hello bob");
+    };
+    run_test("", hdr, rs, &["SHIFTED", "GREETING"], &[]);
+}
+
+#[test]
+fn test_define_function_like() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #define BASE 4
+        #define SHIFTED ((uint32_t)1 << BASE)
+        #define MAX(a, b) ((a) > (b) ? (a) : (b))
+    "};
+    let rs = quote! {
+        let shifted: u32 = ffi::SHIFTED;
+        assert_eq!(shifted, 16);
+    };
+    run_test("", hdr, rs, &["SHIFTED", "MAX"], &[]);
+}
+
+#[test]
+fn test_define_non_constant() {
+    let hdr = indoc! {"
+        #define BOB 3
+        inline int next_value() { static int value = 0; return value++; }
+        #define NEXT_VALUE next_value()
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::BOB, 3);
+        assert_eq!(ffi::next_value(), autocxx::c_int(0));
+    };
+    run_test("", hdr, rs, &["BOB", "NEXT_VALUE", "next_value"], &[]);
+}
+
 #[test]
 fn test_i32_const() {
     let cxx = indoc! {"
@@ -5020,6 +5066,47 @@ fn test_doc_passthru() {
     );
 }
 
+#[test]
+fn test_doxygen_to_rustdoc() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        /// A widget.
+        struct Widget {
+            uint32_t a;
+        };
+        namespace other {
+        struct Gadget {
+            uint32_t b;
+        };
+        }
+        /// @brief Frobs some widgets.
+        /// @param[in] count How many to frob.
+        /// @return The number frobbed.
+        /// @see Widget, other::Gadget
+        inline uint32_t frob(uint32_t count) { return count; }
+    "};
+    let rs = quote! {};
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(&["frob"], &["Widget", "other::Gadget"], None),
+        None,
+        Some(make_string_finder(
+            [
+                "# Arguments",
+                "* `count` - How many to frob.",
+                "# Returns",
+                "* [`Widget`](Widget)",
+                "* `other::Gadget`",
+                "uint32_t frob(uint32_t count)",
+            ]
+            .to_vec(),
+        )),
+        None,
+    );
+}
+
 #[test]
 fn test_closure() {
     // Ensuring presence of this closure doesn't break other things
@@ -6185,134 +6272,175 @@ fn test_non_pv_subclass() {
 }
 
 #[test]
-fn test_pv_subclass_allocation_not_self_owned() {
+fn test_subclass_multiple_superclasses() {
     let hdr = indoc! {"
     #include <cstdint>
-    extern \"C\" void mark_freed() noexcept;
-    extern \"C\" void mark_allocated() noexcept;
 
-    class TestObserver {
+    class Observer {
     public:
-        TestObserver() {
-            mark_allocated();
-        }
-        virtual void a() const = 0;
-        virtual ~TestObserver() {
-            mark_freed();
-        }
+        Observer() {}
+        virtual uint32_t observe() const = 0;
+        virtual ~Observer() {}
     };
-    inline void TriggerTestObserverA(const TestObserver& obs) {
-        obs.a();
+    class Delegate {
+    public:
+        Delegate() {}
+        virtual uint32_t delegate() const { return 1; }
+        virtual ~Delegate() {}
+    };
+    inline uint32_t CallObserver(const Observer& obs) {
+        return obs.observe();
+    }
+    inline uint32_t CallDelegate(const Delegate& del) {
+        return del.delegate();
     }
     "};
     run_test_ex(
         "",
         hdr,
         quote! {
-            assert!(!Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().rust_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
-
-            // Test when owned by C++
-            let obs = MyTestObserver::new_cpp_owned(
-                MyTestObserver::new()
-            );
-            assert!(Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
-            assert!(Lazy::force(&STATUS).lock().unwrap().rust_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
-            let obs_superclass = obs.as_ref().unwrap(); // &subclass
-            let obs_superclass = unsafe { std::mem::transmute::<&ffi::MyTestObserverCpp, &ffi::TestObserver>(obs_superclass) };
-            ffi::TriggerTestObserverA(obs_superclass);
-            assert!(Lazy::force(&STATUS).lock().unwrap().a_called);
-            std::mem::drop(obs);
-            Lazy::force(&STATUS).lock().unwrap().a_called = false;
-            assert!(!Lazy::force(&STATUS).lock().unwrap().rust_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
-
-            // Test when owned by Rust
-            let obs = MyTestObserver::new_rust_owned(
-                MyTestObserver::new()
-            );
-            //let cpp_peer_ptr = unsafe { obs.borrow_mut().peer_mut().get_unchecked_mut() as *mut ffi::MyTestObserverCpp };
-            assert!(Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
-            assert!(Lazy::force(&STATUS).lock().unwrap().rust_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
-            ffi::TriggerTestObserverA(obs.as_ref().borrow().as_ref());
-            assert!(Lazy::force(&STATUS).lock().unwrap().a_called);
-            Lazy::force(&STATUS).lock().unwrap().a_called = false;
-            std::mem::drop(obs);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().rust_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
+            let obs = MyObserver::new_rust_owned(MyObserver { a: 3, cpp_peer: Default::default() });
+            assert_eq!(ffi::CallObserver(obs.borrow().As_Observer()), 3);
+            assert_eq!(ffi::CallDelegate(obs.borrow().As_Delegate()), 4);
         },
         quote! {
-            generate!("TriggerTestObserverA")
-            subclass!("TestObserver",MyTestObserver)
+            generate!("CallObserver")
+            generate!("CallDelegate")
+            subclass!("Observer", MyObserver)
+            subclass!("Delegate", MyObserver)
         },
         None,
         None,
         Some(quote! {
-            use once_cell::sync::Lazy;
-            use std::sync::Mutex;
-
             use autocxx::subclass::CppSubclass;
-            use ffi::TestObserver_methods;
+            use ffi::{Delegate_methods, Delegate_supers, Observer_methods};
             #[autocxx::subclass::subclass]
-            pub struct MyTestObserver {
-                data: ExternalEngine,
+            pub struct MyObserver {
+                a: u32
             }
-            impl TestObserver_methods for MyTestObserver {
-                fn a(&self) {
-                    self.data.do_something();
+            impl Observer_methods for MyObserver {
+                fn observe(&self) -> u32 {
+                    self.a
                 }
             }
-            impl MyTestObserver {
-                fn new() -> Self {
-                    Self {
-                        cpp_peer: Default::default(),
-                        data: ExternalEngine::default(),
-                    }
+            impl Delegate_methods for MyObserver {
+                fn delegate(&self) -> u32 {
+                    self.a + self.delegate_super()
                 }
             }
+        }),
+    );
+}
 
-            #[no_mangle]
-            pub fn mark_allocated() {
-                Lazy::force(&STATUS).lock().unwrap().cpp_allocated = true;
-            }
-
-            #[no_mangle]
-            pub fn mark_freed() {
-                Lazy::force(&STATUS).lock().unwrap().cpp_allocated = false;
-            }
+#[test]
+fn test_subclass_panic_throws() {
+    let hdr = indoc! {"
+    #include <cstdint>
+    #include <exception>
 
-            #[derive(Default)]
-            struct Status {
-                cpp_allocated: bool,
-                rust_allocated: bool,
-                a_called: bool,
+    class Observer {
+    public:
+        Observer() {}
+        virtual uint32_t observe() const = 0;
+        virtual ~Observer() {}
+    };
+    inline bool ObserveThrows(const Observer& obs) {
+        try {
+            obs.observe();
+            return false;
+        } catch (const std::exception&) {
+            return true;
+        }
+    }
+    "};
+    run_test_ex(
+        "",
+        hdr,
+        quote! {
+            static HOOK_CALLED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+            autocxx::subclass::set_subclass_panic_hook(|info| {
+                assert_eq!(info.subclass, "MyObserver");
+                assert_eq!(info.method, "observe");
+                assert_eq!(info.message(), Some("oops"));
+                HOOK_CALLED.store(true, std::sync::atomic::Ordering::SeqCst);
+            });
+            let obs = MyObserver::new_rust_owned(MyObserver { cpp_peer: Default::default() });
+            assert!(ffi::ObserveThrows(obs.borrow().As_Observer()));
+            assert!(HOOK_CALLED.load(std::sync::atomic::Ordering::SeqCst));
+        },
+        quote! {
+            generate!("ObserveThrows")
+            subclass!("Observer", MyObserver)
+        },
+        None,
+        None,
+        Some(quote! {
+            use autocxx::subclass::CppSubclass;
+            use ffi::Observer_methods;
+            #[autocxx::subclass::subclass(on_panic(throw))]
+            pub struct MyObserver;
+            impl Observer_methods for MyObserver {
+                fn observe(&self) -> u32 {
+                    panic!("oops")
+                }
             }
+        }),
+    );
+}
 
-            static STATUS: Lazy<Mutex<Status>> = Lazy::new(|| Mutex::new(Status::default()));
-
-            pub struct ExternalEngine;
+#[test]
+fn test_subclass_new_shared() {
+    let hdr = indoc! {"
+    #include <cstdint>
+    #include <memory>
 
-            impl ExternalEngine {
-                fn do_something(&self) {
-                    Lazy::force(&STATUS).lock().unwrap().a_called = true;
-                }
+    class Observer {
+    public:
+        Observer() {}
+        virtual uint32_t observe() const = 0;
+        virtual ~Observer() {}
+    };
+    inline uint32_t CallShared(std::shared_ptr<Observer> obs) {
+        return obs->observe();
+    }
+    "};
+    run_test_ex(
+        "",
+        hdr,
+        quote! {
+            let obs = MyObserver::new_shared(MyObserver { a: 3, cpp_peer: Default::default() });
+            let as_observer = ffi::MyObserverCpp::As_Observer_shared(obs.clone());
+            assert_eq!(ffi::CallShared(as_observer.clone()), 3);
+            let weak = as_observer.downgrade();
+            std::mem::drop(obs);
+            assert!(!weak.upgrade().is_null());
+            assert!(!DROPPED.load(std::sync::atomic::Ordering::SeqCst));
+            std::mem::drop(as_observer);
+            assert!(weak.upgrade().is_null());
+            assert!(DROPPED.load(std::sync::atomic::Ordering::SeqCst));
+        },
+        quote! {
+            generate!("CallShared")
+            subclass!("Observer", MyObserver)
+        },
+        None,
+        None,
+        Some(quote! {
+            use autocxx::subclass::CppSubclass;
+            use ffi::Observer_methods;
+            static DROPPED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+            #[autocxx::subclass::subclass]
+            pub struct MyObserver {
+                a: u32
             }
-
-            impl Default for ExternalEngine {
-                fn default() -> Self {
-                    Lazy::force(&STATUS).lock().unwrap().rust_allocated = true;
-                    ExternalEngine
+            impl Observer_methods for MyObserver {
+                fn observe(&self) -> u32 {
+                    self.a
                 }
             }
-
-            impl Drop for ExternalEngine {
+            impl Drop for MyObserver {
                 fn drop(&mut self) {
-                    Lazy::force(&STATUS).lock().unwrap().rust_allocated = false;
+                    DROPPED.store(true, std::sync::atomic::Ordering::SeqCst);
                 }
             }
         }),
@@ -6320,158 +6448,188 @@ fn test_pv_subclass_allocation_not_self_owned() {
 }
 
 #[test]
-fn test_pv_subclass_allocation_self_owned() {
+fn test_subclass_superclass_constructor_helpers() {
     let hdr = indoc! {"
     #include <cstdint>
-    extern \"C\" void mark_freed() noexcept;
-    extern \"C\" void mark_allocated() noexcept;
 
-    class TestObserver {
+    class Observer {
     public:
-        TestObserver() {
-            mark_allocated();
-        }
-        virtual void a() const = 0;
-        virtual ~TestObserver() {
-            mark_freed();
-        }
+        Observer() : base(0) {}
+        Observer(uint32_t a) : base(a) {}
+        Observer(uint32_t a, uint32_t b) : base(a * b) {}
+        virtual uint32_t observe() const = 0;
+        uint32_t get_base() const { return base; }
+        virtual ~Observer() {}
+    private:
+        uint32_t base;
     };
-    inline void TriggerTestObserverA(const TestObserver& obs) {
-        const_cast<TestObserver&>(obs).a();
+    inline uint32_t Observe(const Observer& obs) {
+        return obs.observe();
     }
     "};
     run_test_ex(
         "",
         hdr,
         quote! {
-            assert!(!Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().rust_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
-
-            // Test when owned by C++
-            let obs = MyTestObserver::new_cpp_owned(
-                MyTestObserver::new(false)
+            let obs = MyObserver::new_rust_owned(MyObserver { a: 1, cpp_peer: Default::default() });
+            assert_eq!(ffi::Observe(obs.borrow().As_Observer()), 11);
+            let obs = MyObserver::new_rust_owned_with(
+                MyObserver { a: 1, cpp_peer: Default::default() },
+                MyObserver::with_base_default(),
             );
-            assert!(Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
-            assert!(Lazy::force(&STATUS).lock().unwrap().rust_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
-            let obs_superclass = obs.as_ref().unwrap(); // &subclass
-            let obs_superclass = unsafe { std::mem::transmute::<&ffi::MyTestObserverCpp, &ffi::TestObserver>(obs_superclass) };
-
-            ffi::TriggerTestObserverA(obs_superclass);
-            assert!(Lazy::force(&STATUS).lock().unwrap().a_called);
-            std::mem::drop(obs);
-            Lazy::force(&STATUS).lock().unwrap().a_called = false;
-            assert!(!Lazy::force(&STATUS).lock().unwrap().rust_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
-
-            // Test when owned by Rust
-            let obs = MyTestObserver::new_rust_owned(
-                MyTestObserver::new(false)
+            assert_eq!(ffi::Observe(obs.borrow().As_Observer()), 1);
+            let obs = MyObserver::new_rust_owned_with(
+                MyObserver { a: 1, cpp_peer: Default::default() },
+                MyObserver::with_base_u32(4),
             );
-            assert!(Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
-            assert!(Lazy::force(&STATUS).lock().unwrap().rust_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
-            ffi::TriggerTestObserverA(obs.as_ref().borrow().as_ref());
-
-            assert!(Lazy::force(&STATUS).lock().unwrap().a_called);
-            Lazy::force(&STATUS).lock().unwrap().a_called = false;
-            std::mem::drop(obs);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().rust_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
-
-            // Test when self-owned
-            let obs = MyTestObserver::new_self_owned(
-                MyTestObserver::new(true)
+            assert_eq!(ffi::Observe(obs.borrow().As_Observer()), 5);
+            let obs = MyObserver::new_cpp_owned_with(
+                MyObserver { a: 1, cpp_peer: Default::default() },
+                MyObserver::with_base_u32_u32(2, 3),
             );
-            let obs_superclass_ptr: *const ffi::TestObserver = obs.as_ref().borrow().as_ref();
-            // Retain just a pointer on the Rust side, so there is no Rust-side
-            // ownership.
-            std::mem::drop(obs);
-            assert!(Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
-            assert!(Lazy::force(&STATUS).lock().unwrap().rust_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
-            ffi::TriggerTestObserverA(unsafe { obs_superclass_ptr.as_ref().unwrap() });
-
-            assert!(Lazy::force(&STATUS).lock().unwrap().a_called);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().rust_allocated);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
+            assert_eq!(ffi::Observe(obs.as_ref().unwrap().As_Observer()), 7);
         },
         quote! {
-            generate!("TriggerTestObserverA")
-            subclass!("TestObserver",MyTestObserver)
+            generate!("Observe")
+            subclass!("Observer", MyObserver)
         },
         None,
         None,
         Some(quote! {
-            use once_cell::sync::Lazy;
-            use std::sync::Mutex;
-
-            use autocxx::subclass::CppSubclass;
-            use autocxx::subclass::CppSubclassSelfOwned;
-            use ffi::TestObserver_methods;
-            #[autocxx::subclass::subclass(self_owned)]
-            pub struct MyTestObserver {
-                data: ExternalEngine,
-                self_owning: bool,
+            use autocxx::subclass::{CppPeerConstructor, CppSubclass, CppSubclassRustPeerHolder};
+            use ffi::Observer_methods;
+            #[autocxx::subclass::subclass]
+            pub struct MyObserver {
+                a: u32
             }
-            impl TestObserver_methods for MyTestObserver {
-                fn a(&self) {
-                    self.data.do_something();
-                    if self.self_owning {
-                        self.delete_self();
-                    }
+            impl Observer_methods for MyObserver {
+                fn observe(&self) -> u32 {
+                    self.a + self.As_Observer().get_base()
                 }
             }
-            impl MyTestObserver {
-                fn new(self_owning: bool) -> Self {
-                    Self {
-                        cpp_peer: Default::default(),
-                        data: ExternalEngine::default(),
-                        self_owning,
-                    }
+            impl CppPeerConstructor<ffi::MyObserverCpp> for MyObserver {
+                fn make_peer(&mut self, peer_holder: CppSubclassRustPeerHolder<Self>) -> cxx::UniquePtr<ffi::MyObserverCpp> {
+                    Self::with_base_u32(10)(self, peer_holder)
                 }
             }
+        }),
+    );
+}
 
-            #[no_mangle]
-            pub fn mark_allocated() {
-                Lazy::force(&STATUS).lock().unwrap().cpp_allocated = true;
-            }
+#[test]
+fn test_subclass_on_cpp_destroyed() {
+    let hdr = indoc! {"
+    #include <cstdint>
 
-            #[no_mangle]
-            pub fn mark_freed() {
-                Lazy::force(&STATUS).lock().unwrap().cpp_allocated = false;
+    class Observer {
+    public:
+        Observer() {}
+        virtual uint32_t observe() const = 0;
+        virtual ~Observer() {}
+    };
+    inline uint32_t Observe(const Observer& obs) {
+        return obs.observe();
+    }
+    "};
+    run_test_ex(
+        "",
+        hdr,
+        quote! {
+            let obs = MyObserver::new_cpp_owned(MyObserver { a: 3, cpp_peer: Default::default() });
+            assert_eq!(ffi::Observe(obs.as_ref().unwrap().As_Observer()), 3);
+            assert_eq!(DESTROYED.load(std::sync::atomic::Ordering::SeqCst), 0);
+            std::mem::drop(obs);
+            assert_eq!(DESTROYED.load(std::sync::atomic::Ordering::SeqCst), 3);
+            // Dropping a Rust-owned subclass doesn't call the hook.
+            let obs = MyObserver::new_rust_owned(MyObserver { a: 4, cpp_peer: Default::default() });
+            std::mem::drop(obs);
+            assert_eq!(DESTROYED.load(std::sync::atomic::Ordering::SeqCst), 3);
+        },
+        quote! {
+            generate!("Observe")
+            subclass!("Observer", MyObserver)
+        },
+        None,
+        None,
+        Some(quote! {
+            use autocxx::subclass::{CppPeerDestroyed, CppSubclass};
+            use ffi::Observer_methods;
+            static DESTROYED: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+            #[autocxx::subclass::subclass(on_cpp_destroyed)]
+            pub struct MyObserver {
+                a: u32
             }
-
-            #[derive(Default)]
-            struct Status {
-                cpp_allocated: bool,
-                rust_allocated: bool,
-                a_called: bool,
+            impl Observer_methods for MyObserver {
+                fn observe(&self) -> u32 {
+                    self.a
+                }
             }
-
-            static STATUS: Lazy<Mutex<Status>> = Lazy::new(|| Mutex::new(Status::default()));
-
-            pub struct ExternalEngine;
-
-            impl ExternalEngine {
-                fn do_something(&self) {
-                    Lazy::force(&STATUS).lock().unwrap().a_called = true;
+            impl CppPeerDestroyed for MyObserver {
+                fn on_cpp_destroyed(&mut self) {
+                    DESTROYED.store(self.a, std::sync::atomic::Ordering::SeqCst);
                 }
             }
+        }),
+    );
+}
 
-            impl Default for ExternalEngine {
-                fn default() -> Self {
-                    Lazy::force(&STATUS).lock().unwrap().rust_allocated = true;
-                    ExternalEngine
+#[test]
+fn test_thread_safe_subclass_destroyed_by_own_method() {
+    let hdr = indoc! {"
+    class Observer {
+    public:
+        Observer() {}
+        virtual void run() = 0;
+        virtual ~Observer() {}
+    };
+    inline Observer*& CurrentObserver() {
+        static Observer* obs = nullptr;
+        return obs;
+    }
+    inline void SetObserver(Observer& obs) {
+        CurrentObserver() = &obs;
+    }
+    inline void RunObserver() {
+        CurrentObserver()->run();
+    }
+    inline void DestroyObserver() {
+        delete CurrentObserver();
+        CurrentObserver() = nullptr;
+    }
+    "};
+    run_test_ex(
+        "",
+        hdr,
+        quote! {
+            let mut obs = MyObserver::new_cpp_owned(MyObserver { cpp_peer: Default::default() });
+            ffi::SetObserver(obs.pin_mut().As_Observer_mut());
+            let _ = obs.into_raw();
+            // Destroying the peer from within its own method mustn't deadlock.
+            ffi::RunObserver();
+            assert!(!DESTROYED.load(std::sync::atomic::Ordering::SeqCst));
+        },
+        quote! {
+            generate!("SetObserver")
+            generate!("RunObserver")
+            generate!("DestroyObserver")
+            subclass!("Observer", MyObserver, thread_safe)
+        },
+        None,
+        None,
+        Some(quote! {
+            use autocxx::subclass::{CppPeerDestroyed, CppSubclass};
+            use ffi::Observer_methods;
+            static DESTROYED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+            #[autocxx::subclass::subclass(thread_safe, on_cpp_destroyed)]
+            pub struct MyObserver {}
+            impl Observer_methods for MyObserver {
+                fn run(&mut self) {
+                    ffi::DestroyObserver();
                 }
             }
-
-            impl Drop for ExternalEngine {
-                fn drop(&mut self) {
-                    Lazy::force(&STATUS).lock().unwrap().rust_allocated = false;
+            impl CppPeerDestroyed for MyObserver {
+                fn on_cpp_destroyed(&mut self) {
+                    DESTROYED.store(true, std::sync::atomic::Ordering::SeqCst);
                 }
             }
         }),
@@ -6479,293 +6637,147 @@ fn test_pv_subclass_allocation_self_owned() {
 }
 
 #[test]
-fn test_pv_subclass_calls() {
+fn test_subclass_cpp_namespace() {
     let hdr = indoc! {"
     #include <cstdint>
-    extern \"C\" void mark_c_called() noexcept;
-    extern \"C\" void mark_d_called() noexcept;
-    extern \"C\" void mark_e_called() noexcept;
-    extern \"C\" void mark_f_called() noexcept;
-    extern \"C\" void mark_g_called() noexcept;
-    extern \"C\" void mark_h_called() noexcept;
 
-    class TestObserver {
+    class Observer {
     public:
-        TestObserver() {}
-        virtual uint32_t a(uint32_t) const = 0;
-        virtual uint32_t b(uint32_t) = 0;
-        virtual uint32_t c(uint32_t) const { mark_c_called(); return 0; };
-        virtual uint32_t d(uint32_t) { mark_d_called(); return 0; };
-        virtual uint32_t e(uint32_t) const { mark_e_called(); return 0; };
-        virtual uint32_t f(uint32_t) { mark_f_called(); return 0; };
-        virtual uint32_t g(uint32_t) const { mark_g_called(); return 0; };
-        virtual uint32_t h(uint32_t) { mark_h_called(); return 0; };
-        virtual ~TestObserver() {}
+        Observer() {}
+        virtual uint32_t observe(uint32_t a) const { return a; }
+        virtual uint32_t observe_twice(uint32_t a) const = 0;
+        virtual ~Observer() {}
     };
-
-    extern TestObserver* obs;
-
-    inline void register_observer(TestObserver& a) {
-        obs = &a;
-    }
-    inline uint32_t call_a(uint32_t param) {
-        return obs->a(param);
-    }
-    inline uint32_t call_b(uint32_t param) {
-        return obs->b(param);
-    }
-    inline uint32_t call_c(uint32_t param) {
-        return obs->c(param);
-    }
-    inline uint32_t call_d(uint32_t param) {
-        return obs->d(param);
-    }
-    inline uint32_t call_e(uint32_t param) {
-        return obs->e(param);
-    }
-    inline uint32_t call_f(uint32_t param) {
-        return obs->f(param);
-    }
-    inline uint32_t call_g(uint32_t param) {
-        return obs->g(param);
-    }
-    inline uint32_t call_h(uint32_t param) {
-        return obs->h(param);
+    inline uint32_t Observe(const Observer& obs, uint32_t a) {
+        return obs.observe(a) + obs.observe_twice(a);
     }
     "};
     run_test_ex(
-        "TestObserver* obs;",
+        "",
         hdr,
         quote! {
-            let obs = MyTestObserver::new_rust_owned(
-                MyTestObserver::default()
-            );
-            ffi::register_observer(obs.as_ref().borrow_mut().pin_mut());
-            assert_eq!(ffi::call_a(1), 2);
-            assert!(Lazy::force(&STATUS).lock().unwrap().sub_a_called);
-            *Lazy::force(&STATUS).lock().unwrap() = Default::default();
-
-            assert_eq!(ffi::call_b(1), 3);
-            assert!(Lazy::force(&STATUS).lock().unwrap().sub_b_called);
-            *Lazy::force(&STATUS).lock().unwrap() = Default::default();
-
-            assert_eq!(ffi::call_c(1), 4);
-            assert!(Lazy::force(&STATUS).lock().unwrap().sub_c_called);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().super_c_called);
-            *Lazy::force(&STATUS).lock().unwrap() = Default::default();
-
-            assert_eq!(ffi::call_d(1), 5);
-            assert!(Lazy::force(&STATUS).lock().unwrap().sub_d_called);
-            assert!(!Lazy::force(&STATUS).lock().unwrap().super_d_called);
-            *Lazy::force(&STATUS).lock().unwrap() = Default::default();
-
-            assert_eq!(ffi::call_e(1), 0);
-            assert!(Lazy::force(&STATUS).lock().unwrap().sub_e_called);
-            assert!(Lazy::force(&STATUS).lock().unwrap().super_e_called);
-            *Lazy::force(&STATUS).lock().unwrap() = Default::default();
-
-            assert_eq!(ffi::call_f(1), 0);
-            assert!(Lazy::force(&STATUS).lock().unwrap().sub_f_called);
-            assert!(Lazy::force(&STATUS).lock().unwrap().super_f_called);
-            *Lazy::force(&STATUS).lock().unwrap() = Default::default();
-
-            assert_eq!(ffi::call_g(1), 0);
-            assert!(Lazy::force(&STATUS).lock().unwrap().super_g_called);
-            *Lazy::force(&STATUS).lock().unwrap() = Default::default();
-
-            assert_eq!(ffi::call_h(1), 0);
-            assert!(Lazy::force(&STATUS).lock().unwrap().super_h_called);
-            *Lazy::force(&STATUS).lock().unwrap() = Default::default();
+            let obs = MyObserver::new_rust_owned(MyObserver { b: 1, cpp_peer: Default::default() });
+            assert_eq!(ffi::Observe(obs.borrow().As_Observer(), 3), 10);
+            let _: &ffi::app::detail::MyObserverCpp = obs.borrow().peer();
         },
         quote! {
-            generate!("register_observer")
-            generate!("call_a")
-            generate!("call_b")
-            generate!("call_c")
-            generate!("call_d")
-            generate!("call_e")
-            generate!("call_f")
-            generate!("call_g")
-            generate!("call_h")
-            subclass!("TestObserver",MyTestObserver)
+            generate!("Observe")
+            subclass!("Observer", MyObserver, cpp_namespace("app::detail"))
         },
         None,
         None,
         Some(quote! {
-            use once_cell::sync::Lazy;
-            use std::sync::Mutex;
-
             use autocxx::subclass::CppSubclass;
-            use ffi::TestObserver_methods;
-            #[autocxx::subclass::subclass]
-            #[derive(Default)]
-            pub struct MyTestObserver {
+            use ffi::Observer_methods;
+            use ffi::Observer_supers;
+            #[autocxx::subclass::subclass(cpp_namespace("app::detail"))]
+            pub struct MyObserver {
+                b: u32
             }
-            impl TestObserver_methods for MyTestObserver {
-
-                // a and b are pure virtual
-                fn a(&self, param: u32) -> u32 {
-                    Lazy::force(&STATUS).lock().unwrap().sub_a_called = true;
-                    param + 1
+            impl Observer_methods for MyObserver {
+                fn observe(&self, a: u32) -> u32 {
+                    self.observe_super(a) + self.b
                 }
-                fn b(&mut self, param: u32) -> u32 {
-                    Lazy::force(&STATUS).lock().unwrap().sub_b_called = true;
-                    param + 2
-                }
-
-                // c and d we override the superclass
-                fn c(&self, param: u32) -> u32 {
-                    Lazy::force(&STATUS).lock().unwrap().sub_c_called = true;
-                    param + 3
-                }
-                fn d(&mut self, param: u32) -> u32 {
-                    Lazy::force(&STATUS).lock().unwrap().sub_d_called = true;
-                    param + 4
-                }
-
-                // e and f we call through to the superclass
-                fn e(&self, param: u32) -> u32 {
-                    Lazy::force(&STATUS).lock().unwrap().sub_e_called = true;
-                    self.peer().e_super(param)
-                }
-                fn f(&mut self, param: u32) -> u32 {
-                    Lazy::force(&STATUS).lock().unwrap().sub_f_called = true;
-                    self.peer_mut().f_super(param)
+                fn observe_twice(&self, a: u32) -> u32 {
+                    a * 2
                 }
-
-                // g and h we do not do anything, so calls should only call
-                // the superclass
             }
+        }),
+    );
+}
 
-            #[no_mangle]
-            pub fn mark_c_called() {
-                Lazy::force(&STATUS).lock().unwrap().super_c_called = true;
-            }
-            #[no_mangle]
-            pub fn mark_d_called() {
-                Lazy::force(&STATUS).lock().unwrap().super_d_called = true;
-            }
-            #[no_mangle]
-            pub fn mark_e_called() {
-                Lazy::force(&STATUS).lock().unwrap().super_e_called = true;
-            }
-            #[no_mangle]
-            pub fn mark_f_called() {
-                Lazy::force(&STATUS).lock().unwrap().super_f_called = true;
-            }
-            #[no_mangle]
-            pub fn mark_g_called() {
-                Lazy::force(&STATUS).lock().unwrap().super_g_called = true;
-            }
-            #[no_mangle]
-            pub fn mark_h_called() {
-                Lazy::force(&STATUS).lock().unwrap().super_h_called = true;
-            }
+#[test]
+fn test_subclass_cpp_namespace_keyword() {
+    let hdr = indoc! {"
+    #include <cstdint>
 
-            #[derive(Default)]
-            struct Status {
-                super_c_called: bool,
-                super_d_called: bool,
-                super_e_called: bool,
-                super_f_called: bool,
-                super_g_called: bool,
-                super_h_called: bool,
-                sub_a_called: bool,
-                sub_b_called: bool,
-                sub_c_called: bool,
-                sub_d_called: bool,
-                sub_e_called: bool,
-                sub_f_called: bool,
+    class Observer {
+    public:
+        Observer() {}
+        virtual uint32_t observe(uint32_t a) const = 0;
+        virtual ~Observer() {}
+    };
+    inline uint32_t Observe(const Observer& obs, uint32_t a) {
+        return obs.observe(a);
+    }
+    "};
+    run_test_ex(
+        "",
+        hdr,
+        quote! {
+            let obs = MyObserver::new_rust_owned(MyObserver { b: 1, cpp_peer: Default::default() });
+            assert_eq!(ffi::Observe(obs.borrow().As_Observer(), 3), 4);
+            let _: &ffi::app::r#impl::MyObserverCpp = obs.borrow().peer();
+        },
+        quote! {
+            generate!("Observe")
+            subclass!("Observer", MyObserver, cpp_namespace("app::impl"))
+        },
+        None,
+        None,
+        Some(quote! {
+            use autocxx::subclass::CppSubclass;
+            use ffi::Observer_methods;
+            #[autocxx::subclass::subclass(cpp_namespace("app::impl"))]
+            pub struct MyObserver {
+                b: u32
+            }
+            impl Observer_methods for MyObserver {
+                fn observe(&self, a: u32) -> u32 {
+                    a + self.b
+                }
             }
-
-            static STATUS: Lazy<Mutex<Status>> = Lazy::new(|| Mutex::new(Status::default()));
         }),
     );
 }
 
 #[test]
-fn test_pv_subclass_types() {
+fn test_implement_interface() {
     let hdr = indoc! {"
     #include <cstdint>
-    #include <string>
+    #include <memory>
 
-    struct Pod {
-        uint32_t a;
-    };
-    struct NonPod {
-        std::string a;
-    };
-    class TestObserver {
+    namespace ns {
+    class IFoo {
     public:
-        TestObserver() {}
-        virtual std::string s(std::string p) const { return p; }
-        virtual Pod p(Pod p) const { return p; }
-        virtual NonPod n(NonPod p) const { return p; }
-        virtual ~TestObserver() {}
+        virtual uint32_t foo(uint32_t a) const = 0;
+        virtual void bump() = 0;
+        virtual uint32_t extra() const { return 10; }
+        virtual ~IFoo() {}
     };
-
-    extern TestObserver* obs;
-
-    inline void register_observer(TestObserver& a) {
-        obs = &a;
-    }
-    inline std::string call_s(std::string param) {
-        return obs->s(param);
-    }
-    inline Pod call_p(Pod param) {
-        return obs->p(param);
-    }
-    inline NonPod call_n(NonPod param) {
-        return obs->n(param);
     }
-    inline NonPod make_non_pod(std::string a) {
-        NonPod p;
-        p.a = a;
-        return p;
+    inline uint32_t UseFoo(std::unique_ptr<ns::IFoo> foo, uint32_t a) {
+        foo->bump();
+        return foo->foo(a) + foo->extra();
     }
     "};
     run_test_ex(
-        "TestObserver* obs;",
+        "",
         hdr,
         quote! {
-            let obs = MyTestObserver::new_rust_owned(
-                MyTestObserver::default()
-            );
-            ffi::register_observer(obs.as_ref().borrow_mut().pin_mut());
-            ffi::call_p(ffi::Pod { a: 3 });
-            ffi::call_s("hello");
-            ffi::call_n(ffi::make_non_pod("goodbye"));
+            let foo = ffi::Counter_into_IFoo(Box::new(MyCounter { n: 1 }));
+            assert_eq!(ffi::UseFoo(foo, 3), 15);
         },
         quote! {
-            generate!("register_observer")
-            generate!("call_s")
-            generate!("call_n")
-            generate!("call_p")
-            generate!("NonPod")
-            generate!("make_non_pod")
-            generate_pod!("Pod")
-            subclass!("TestObserver",MyTestObserver)
+            generate!("UseFoo")
+            implement_interface!("ns::IFoo", Counter)
         },
         None,
         None,
         Some(quote! {
-            use autocxx::subclass::CppSubclass;
-            use ffi::TestObserver_methods;
-            #[autocxx::subclass::subclass]
-            #[derive(Default)]
-            pub struct MyTestObserver {
+            pub trait Counter {
+                fn foo(&self, a: u32) -> u32;
+                fn bump(&mut self);
             }
-            impl TestObserver_methods for MyTestObserver {
-                fn s(&self, p: cxx::UniquePtr<cxx::CxxString>) -> cxx::UniquePtr<cxx::CxxString> {
-                    self.peer().s_super(p)
-                }
-
-                fn p(&self, p: ffi::Pod) -> ffi::Pod {
-                    self.peer().p_super(p)
+            struct MyCounter {
+                n: u32
+            }
+            impl Counter for MyCounter {
+                fn foo(&self, a: u32) -> u32 {
+                    a + self.n
                 }
-
-                fn n(&self, p: cxx::UniquePtr<ffi::NonPod>) -> cxx::UniquePtr<ffi::NonPod> {
-                    self.peer().n_super(p)
+                fn bump(&mut self) {
+                    self.n += 1;
                 }
             }
         }),
@@ -6773,60 +6785,90 @@ fn test_pv_subclass_types() {
 }
 
 #[test]
-fn test_pv_subclass_constructors() {
-    // Also tests a Rust-side subclass type which is an empty struct
+fn test_implement_interface_without_virtual_destructor() {
     let hdr = indoc! {"
     #include <cstdint>
-    #include <string>
+    #include <memory>
 
-    class TestObserver {
+    class IFoo {
     public:
-        TestObserver() {}
-        TestObserver(uint8_t) {}
-        TestObserver(std::string) {}
-        virtual void call() const { }
-        virtual ~TestObserver() {}
+        virtual uint32_t foo(uint32_t a) const = 0;
     };
+    inline uint32_t UseFoo(const IFoo& foo, uint32_t a) {
+        return foo.foo(a);
+    }
+    "};
+    run_test_expect_fail_ex(
+        "",
+        hdr,
+        quote! {},
+        quote! {
+            generate!("UseFoo")
+            implement_interface!("IFoo", Foo)
+        },
+        None,
+        None,
+        Some(quote! {
+            pub trait Foo {
+                fn foo(&self, a: u32) -> u32;
+            }
+        }),
+    );
+}
 
-    extern TestObserver* obs;
+#[test]
+fn test_thread_safe_subclass() {
+    let hdr = indoc! {"
+    #include <cstdint>
 
-    inline void register_observer(TestObserver& a) {
-        obs = &a;
-    }
-    inline void do_a_thing() {
-        return obs->call();
+    class Observer {
+    public:
+        Observer() {}
+        virtual uint32_t get_a() const = 0;
+        virtual ~Observer() {}
+    };
+    inline uint32_t GetA(const Observer& obs) {
+        return obs.get_a();
     }
     "};
     run_test_ex(
-        "TestObserver* obs;",
+        "",
         hdr,
         quote! {
-            let obs = MyTestObserver::new_rust_owned(
-                MyTestObserver::default()
-            );
-            ffi::register_observer(obs.as_ref().borrow_mut().pin_mut());
-            ffi::do_a_thing();
+            let obs = MyObserver::new_cpp_owned(MyObserver { a: 3, cpp_peer: Default::default() });
+            let reader = MyReader::new_cpp_owned(MyReader { a: 4, cpp_peer: Default::default() });
+            let handle = std::thread::spawn(move || {
+                ffi::GetA(obs.as_ref().unwrap().As_Observer())
+                    + ffi::GetA(reader.as_ref().unwrap().As_Observer())
+            });
+            assert_eq!(handle.join().unwrap(), 7);
         },
         quote! {
-            generate!("register_observer")
-            generate!("do_a_thing")
-            subclass!("TestObserver",MyTestObserver)
+            generate!("GetA")
+            subclass!("Observer", MyObserver, thread_safe)
+            subclass!("Observer", MyReader, thread_safe(rwlock))
         },
         None,
         None,
         Some(quote! {
-            use autocxx::subclass::prelude::*;
-            #[subclass]
-            #[derive(Default)]
-            pub struct MyTestObserver;
-            impl ffi::TestObserver_methods for MyTestObserver {
-                fn call(&self) {
-                    self.peer().call_super()
+            use autocxx::subclass::CppSubclass;
+            use ffi::Observer_methods;
+            #[autocxx::subclass::subclass(thread_safe)]
+            pub struct MyObserver {
+                a: u32
+            }
+            impl Observer_methods for MyObserver {
+                fn get_a(&self) -> u32 {
+                    self.a
                 }
             }
-            impl CppPeerConstructor<ffi::MyTestObserverCpp> for MyTestObserver {
-                fn make_peer(&mut self, peer_holder: CppSubclassRustPeerHolder<Self>) -> cxx::UniquePtr<ffi::MyTestObserverCpp> {
-                    ffi::MyTestObserverCpp::make_unique1(peer_holder, 3u8)
+            #[autocxx::subclass::subclass(thread_safe(rwlock))]
+            pub struct MyReader {
+                a: u32
+            }
+            impl Observer_methods for MyReader {
+                fn get_a(&self) -> u32 {
+                    self.a
                 }
             }
         }),
@@ -6834,238 +6876,1674 @@ fn test_pv_subclass_constructors() {
 }
 
 #[test]
-fn test_pv_subclass_fancy_constructor() {
+fn test_thread_safe_subclass_requires_unsafe() {
     let hdr = indoc! {"
     #include <cstdint>
 
     class Observer {
     public:
-        Observer(uint8_t) {}
-        virtual uint32_t foo() const = 0;
+        Observer() {}
+        virtual uint32_t get_a() const = 0;
         virtual ~Observer() {}
     };
-    inline void take_observer(const Observer&) {}
     "};
-    run_test_expect_fail_ex(
-        "",
-        hdr,
-        quote! {
-            let o = MyObserver::new_rust_owned(MyObserver { a: 3, cpp_peer: Default::default() }, ffi::MyObserverCpp::make_unique);
-            ffi::take_observer(o.borrow().as_ref());
-        },
+    let rs = |hdr| {
+        let hexathorpe = Token![#](Span::call_site());
         quote! {
-            generate!("take_observer")
-            subclass!("Observer",MyObserver)
-        },
+            use autocxx::include_cpp;
+            include_cpp! {
+                #hexathorpe include #hdr
+                subclass!("Observer", MyObserver)
+            }
+            use ffi::Observer_methods;
+            #[autocxx::subclass::subclass(thread_safe)]
+            pub struct MyObserver {
+                a: u32
+            }
+            impl Observer_methods for MyObserver {
+                fn get_a(&self) -> u32 {
+                    self.a
+                }
+            }
+            fn main() {}
+        }
+    };
+    assert!(do_run_test_manual("", hdr, rs, None, None).is_err());
+}
+
+#[test]
+fn test_pv_subclass_allocation_not_self_owned() {
+    let hdr = indoc! {"
+    #include <cstdint>
+    extern \"C\" void mark_freed() noexcept;
+    extern \"C\" void mark_allocated() noexcept;
+
+    class TestObserver {
+    public:
+        TestObserver() {
+            mark_allocated();
+        }
+        virtual void a() const = 0;
+        virtual ~TestObserver() {
+            mark_freed();
+        }
+    };
+    inline void TriggerTestObserverA(const TestObserver& obs) {
+        obs.a();
+    }
+    "};
+    run_test_ex(
+        "",
+        hdr,
+        quote! {
+            assert!(!Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().rust_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
+
+            // Test when owned by C++
+            let obs = MyTestObserver::new_cpp_owned(
+                MyTestObserver::new()
+            );
+            assert!(Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
+            assert!(Lazy::force(&STATUS).lock().unwrap().rust_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
+            let obs_superclass = obs.as_ref().unwrap(); // &subclass
+            let obs_superclass = unsafe { std::mem::transmute::<&ffi::MyTestObserverCpp, &ffi::TestObserver>(obs_superclass) };
+            ffi::TriggerTestObserverA(obs_superclass);
+            assert!(Lazy::force(&STATUS).lock().unwrap().a_called);
+            std::mem::drop(obs);
+            Lazy::force(&STATUS).lock().unwrap().a_called = false;
+            assert!(!Lazy::force(&STATUS).lock().unwrap().rust_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
+
+            // Test when owned by Rust
+            let obs = MyTestObserver::new_rust_owned(
+                MyTestObserver::new()
+            );
+            //let cpp_peer_ptr = unsafe { obs.borrow_mut().peer_mut().get_unchecked_mut() as *mut ffi::MyTestObserverCpp };
+            assert!(Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
+            assert!(Lazy::force(&STATUS).lock().unwrap().rust_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
+            ffi::TriggerTestObserverA(obs.as_ref().borrow().as_ref());
+            assert!(Lazy::force(&STATUS).lock().unwrap().a_called);
+            Lazy::force(&STATUS).lock().unwrap().a_called = false;
+            std::mem::drop(obs);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().rust_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
+        },
+        quote! {
+            generate!("TriggerTestObserverA")
+            subclass!("TestObserver",MyTestObserver)
+        },
+        None,
+        None,
+        Some(quote! {
+            use once_cell::sync::Lazy;
+            use std::sync::Mutex;
+
+            use autocxx::subclass::CppSubclass;
+            use ffi::TestObserver_methods;
+            #[autocxx::subclass::subclass]
+            pub struct MyTestObserver {
+                data: ExternalEngine,
+            }
+            impl TestObserver_methods for MyTestObserver {
+                fn a(&self) {
+                    self.data.do_something();
+                }
+            }
+            impl MyTestObserver {
+                fn new() -> Self {
+                    Self {
+                        cpp_peer: Default::default(),
+                        data: ExternalEngine::default(),
+                    }
+                }
+            }
+
+            #[no_mangle]
+            pub fn mark_allocated() {
+                Lazy::force(&STATUS).lock().unwrap().cpp_allocated = true;
+            }
+
+            #[no_mangle]
+            pub fn mark_freed() {
+                Lazy::force(&STATUS).lock().unwrap().cpp_allocated = false;
+            }
+
+            #[derive(Default)]
+            struct Status {
+                cpp_allocated: bool,
+                rust_allocated: bool,
+                a_called: bool,
+            }
+
+            static STATUS: Lazy<Mutex<Status>> = Lazy::new(|| Mutex::new(Status::default()));
+
+            pub struct ExternalEngine;
+
+            impl ExternalEngine {
+                fn do_something(&self) {
+                    Lazy::force(&STATUS).lock().unwrap().a_called = true;
+                }
+            }
+
+            impl Default for ExternalEngine {
+                fn default() -> Self {
+                    Lazy::force(&STATUS).lock().unwrap().rust_allocated = true;
+                    ExternalEngine
+                }
+            }
+
+            impl Drop for ExternalEngine {
+                fn drop(&mut self) {
+                    Lazy::force(&STATUS).lock().unwrap().rust_allocated = false;
+                }
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_pv_subclass_allocation_self_owned() {
+    let hdr = indoc! {"
+    #include <cstdint>
+    extern \"C\" void mark_freed() noexcept;
+    extern \"C\" void mark_allocated() noexcept;
+
+    class TestObserver {
+    public:
+        TestObserver() {
+            mark_allocated();
+        }
+        virtual void a() const = 0;
+        virtual ~TestObserver() {
+            mark_freed();
+        }
+    };
+    inline void TriggerTestObserverA(const TestObserver& obs) {
+        const_cast<TestObserver&>(obs).a();
+    }
+    "};
+    run_test_ex(
+        "",
+        hdr,
+        quote! {
+            assert!(!Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().rust_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
+
+            // Test when owned by C++
+            let obs = MyTestObserver::new_cpp_owned(
+                MyTestObserver::new(false)
+            );
+            assert!(Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
+            assert!(Lazy::force(&STATUS).lock().unwrap().rust_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
+            let obs_superclass = obs.as_ref().unwrap(); // &subclass
+            let obs_superclass = unsafe { std::mem::transmute::<&ffi::MyTestObserverCpp, &ffi::TestObserver>(obs_superclass) };
+
+            ffi::TriggerTestObserverA(obs_superclass);
+            assert!(Lazy::force(&STATUS).lock().unwrap().a_called);
+            std::mem::drop(obs);
+            Lazy::force(&STATUS).lock().unwrap().a_called = false;
+            assert!(!Lazy::force(&STATUS).lock().unwrap().rust_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
+
+            // Test when owned by Rust
+            let obs = MyTestObserver::new_rust_owned(
+                MyTestObserver::new(false)
+            );
+            assert!(Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
+            assert!(Lazy::force(&STATUS).lock().unwrap().rust_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
+            ffi::TriggerTestObserverA(obs.as_ref().borrow().as_ref());
+
+            assert!(Lazy::force(&STATUS).lock().unwrap().a_called);
+            Lazy::force(&STATUS).lock().unwrap().a_called = false;
+            std::mem::drop(obs);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().rust_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
+
+            // Test when self-owned
+            let obs = MyTestObserver::new_self_owned(
+                MyTestObserver::new(true)
+            );
+            let obs_superclass_ptr: *const ffi::TestObserver = obs.as_ref().borrow().as_ref();
+            // Retain just a pointer on the Rust side, so there is no Rust-side
+            // ownership.
+            std::mem::drop(obs);
+            assert!(Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
+            assert!(Lazy::force(&STATUS).lock().unwrap().rust_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().a_called);
+            ffi::TriggerTestObserverA(unsafe { obs_superclass_ptr.as_ref().unwrap() });
+
+            assert!(Lazy::force(&STATUS).lock().unwrap().a_called);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().rust_allocated);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().cpp_allocated);
+        },
+        quote! {
+            generate!("TriggerTestObserverA")
+            subclass!("TestObserver",MyTestObserver)
+        },
+        None,
+        None,
+        Some(quote! {
+            use once_cell::sync::Lazy;
+            use std::sync::Mutex;
+
+            use autocxx::subclass::CppSubclass;
+            use autocxx::subclass::CppSubclassSelfOwned;
+            use ffi::TestObserver_methods;
+            #[autocxx::subclass::subclass(self_owned)]
+            pub struct MyTestObserver {
+                data: ExternalEngine,
+                self_owning: bool,
+            }
+            impl TestObserver_methods for MyTestObserver {
+                fn a(&self) {
+                    self.data.do_something();
+                    if self.self_owning {
+                        self.delete_self();
+                    }
+                }
+            }
+            impl MyTestObserver {
+                fn new(self_owning: bool) -> Self {
+                    Self {
+                        cpp_peer: Default::default(),
+                        data: ExternalEngine::default(),
+                        self_owning,
+                    }
+                }
+            }
+
+            #[no_mangle]
+            pub fn mark_allocated() {
+                Lazy::force(&STATUS).lock().unwrap().cpp_allocated = true;
+            }
+
+            #[no_mangle]
+            pub fn mark_freed() {
+                Lazy::force(&STATUS).lock().unwrap().cpp_allocated = false;
+            }
+
+            #[derive(Default)]
+            struct Status {
+                cpp_allocated: bool,
+                rust_allocated: bool,
+                a_called: bool,
+            }
+
+            static STATUS: Lazy<Mutex<Status>> = Lazy::new(|| Mutex::new(Status::default()));
+
+            pub struct ExternalEngine;
+
+            impl ExternalEngine {
+                fn do_something(&self) {
+                    Lazy::force(&STATUS).lock().unwrap().a_called = true;
+                }
+            }
+
+            impl Default for ExternalEngine {
+                fn default() -> Self {
+                    Lazy::force(&STATUS).lock().unwrap().rust_allocated = true;
+                    ExternalEngine
+                }
+            }
+
+            impl Drop for ExternalEngine {
+                fn drop(&mut self) {
+                    Lazy::force(&STATUS).lock().unwrap().rust_allocated = false;
+                }
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_pv_subclass_calls() {
+    let hdr = indoc! {"
+    #include <cstdint>
+    extern \"C\" void mark_c_called() noexcept;
+    extern \"C\" void mark_d_called() noexcept;
+    extern \"C\" void mark_e_called() noexcept;
+    extern \"C\" void mark_f_called() noexcept;
+    extern \"C\" void mark_g_called() noexcept;
+    extern \"C\" void mark_h_called() noexcept;
+
+    class TestObserver {
+    public:
+        TestObserver() {}
+        virtual uint32_t a(uint32_t) const = 0;
+        virtual uint32_t b(uint32_t) = 0;
+        virtual uint32_t c(uint32_t) const { mark_c_called(); return 0; };
+        virtual uint32_t d(uint32_t) { mark_d_called(); return 0; };
+        virtual uint32_t e(uint32_t) const { mark_e_called(); return 0; };
+        virtual uint32_t f(uint32_t) { mark_f_called(); return 0; };
+        virtual uint32_t g(uint32_t) const { mark_g_called(); return 0; };
+        virtual uint32_t h(uint32_t) { mark_h_called(); return 0; };
+        virtual ~TestObserver() {}
+    };
+
+    extern TestObserver* obs;
+
+    inline void register_observer(TestObserver& a) {
+        obs = &a;
+    }
+    inline uint32_t call_a(uint32_t param) {
+        return obs->a(param);
+    }
+    inline uint32_t call_b(uint32_t param) {
+        return obs->b(param);
+    }
+    inline uint32_t call_c(uint32_t param) {
+        return obs->c(param);
+    }
+    inline uint32_t call_d(uint32_t param) {
+        return obs->d(param);
+    }
+    inline uint32_t call_e(uint32_t param) {
+        return obs->e(param);
+    }
+    inline uint32_t call_f(uint32_t param) {
+        return obs->f(param);
+    }
+    inline uint32_t call_g(uint32_t param) {
+        return obs->g(param);
+    }
+    inline uint32_t call_h(uint32_t param) {
+        return obs->h(param);
+    }
+    "};
+    run_test_ex(
+        "TestObserver* obs;",
+        hdr,
+        quote! {
+            let obs = MyTestObserver::new_rust_owned(
+                MyTestObserver::default()
+            );
+            ffi::register_observer(obs.as_ref().borrow_mut().pin_mut());
+            assert_eq!(ffi::call_a(1), 2);
+            assert!(Lazy::force(&STATUS).lock().unwrap().sub_a_called);
+            *Lazy::force(&STATUS).lock().unwrap() = Default::default();
+
+            assert_eq!(ffi::call_b(1), 3);
+            assert!(Lazy::force(&STATUS).lock().unwrap().sub_b_called);
+            *Lazy::force(&STATUS).lock().unwrap() = Default::default();
+
+            assert_eq!(ffi::call_c(1), 4);
+            assert!(Lazy::force(&STATUS).lock().unwrap().sub_c_called);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().super_c_called);
+            *Lazy::force(&STATUS).lock().unwrap() = Default::default();
+
+            assert_eq!(ffi::call_d(1), 5);
+            assert!(Lazy::force(&STATUS).lock().unwrap().sub_d_called);
+            assert!(!Lazy::force(&STATUS).lock().unwrap().super_d_called);
+            *Lazy::force(&STATUS).lock().unwrap() = Default::default();
+
+            assert_eq!(ffi::call_e(1), 0);
+            assert!(Lazy::force(&STATUS).lock().unwrap().sub_e_called);
+            assert!(Lazy::force(&STATUS).lock().unwrap().super_e_called);
+            *Lazy::force(&STATUS).lock().unwrap() = Default::default();
+
+            assert_eq!(ffi::call_f(1), 0);
+            assert!(Lazy::force(&STATUS).lock().unwrap().sub_f_called);
+            assert!(Lazy::force(&STATUS).lock().unwrap().super_f_called);
+            *Lazy::force(&STATUS).lock().unwrap() = Default::default();
+
+            assert_eq!(ffi::call_g(1), 0);
+            assert!(Lazy::force(&STATUS).lock().unwrap().super_g_called);
+            *Lazy::force(&STATUS).lock().unwrap() = Default::default();
+
+            assert_eq!(ffi::call_h(1), 0);
+            assert!(Lazy::force(&STATUS).lock().unwrap().super_h_called);
+            *Lazy::force(&STATUS).lock().unwrap() = Default::default();
+        },
+        quote! {
+            generate!("register_observer")
+            generate!("call_a")
+            generate!("call_b")
+            generate!("call_c")
+            generate!("call_d")
+            generate!("call_e")
+            generate!("call_f")
+            generate!("call_g")
+            generate!("call_h")
+            subclass!("TestObserver",MyTestObserver)
+        },
+        None,
+        None,
+        Some(quote! {
+            use once_cell::sync::Lazy;
+            use std::sync::Mutex;
+
+            use autocxx::subclass::CppSubclass;
+            use ffi::TestObserver_methods;
+            #[autocxx::subclass::subclass]
+            #[derive(Default)]
+            pub struct MyTestObserver {
+            }
+            impl TestObserver_methods for MyTestObserver {
+
+                // a and b are pure virtual
+                fn a(&self, param: u32) -> u32 {
+                    Lazy::force(&STATUS).lock().unwrap().sub_a_called = true;
+                    param + 1
+                }
+                fn b(&mut self, param: u32) -> u32 {
+                    Lazy::force(&STATUS).lock().unwrap().sub_b_called = true;
+                    param + 2
+                }
+
+                // c and d we override the superclass
+                fn c(&self, param: u32) -> u32 {
+                    Lazy::force(&STATUS).lock().unwrap().sub_c_called = true;
+                    param + 3
+                }
+                fn d(&mut self, param: u32) -> u32 {
+                    Lazy::force(&STATUS).lock().unwrap().sub_d_called = true;
+                    param + 4
+                }
+
+                // e and f we call through to the superclass
+                fn e(&self, param: u32) -> u32 {
+                    Lazy::force(&STATUS).lock().unwrap().sub_e_called = true;
+                    self.peer().e_super(param)
+                }
+                fn f(&mut self, param: u32) -> u32 {
+                    Lazy::force(&STATUS).lock().unwrap().sub_f_called = true;
+                    self.peer_mut().f_super(param)
+                }
+
+                // g and h we do not do anything, so calls should only call
+                // the superclass
+            }
+
+            #[no_mangle]
+            pub fn mark_c_called() {
+                Lazy::force(&STATUS).lock().unwrap().super_c_called = true;
+            }
+            #[no_mangle]
+            pub fn mark_d_called() {
+                Lazy::force(&STATUS).lock().unwrap().super_d_called = true;
+            }
+            #[no_mangle]
+            pub fn mark_e_called() {
+                Lazy::force(&STATUS).lock().unwrap().super_e_called = true;
+            }
+            #[no_mangle]
+            pub fn mark_f_called() {
+                Lazy::force(&STATUS).lock().unwrap().super_f_called = true;
+            }
+            #[no_mangle]
+            pub fn mark_g_called() {
+                Lazy::force(&STATUS).lock().unwrap().super_g_called = true;
+            }
+            #[no_mangle]
+            pub fn mark_h_called() {
+                Lazy::force(&STATUS).lock().unwrap().super_h_called = true;
+            }
+
+            #[derive(Default)]
+            struct Status {
+                super_c_called: bool,
+                super_d_called: bool,
+                super_e_called: bool,
+                super_f_called: bool,
+                super_g_called: bool,
+                super_h_called: bool,
+                sub_a_called: bool,
+                sub_b_called: bool,
+                sub_c_called: bool,
+                sub_d_called: bool,
+                sub_e_called: bool,
+                sub_f_called: bool,
+            }
+
+            static STATUS: Lazy<Mutex<Status>> = Lazy::new(|| Mutex::new(Status::default()));
+        }),
+    );
+}
+
+#[test]
+fn test_pv_subclass_types() {
+    let hdr = indoc! {"
+    #include <cstdint>
+    #include <string>
+
+    struct Pod {
+        uint32_t a;
+    };
+    struct NonPod {
+        std::string a;
+    };
+    class TestObserver {
+    public:
+        TestObserver() {}
+        virtual std::string s(std::string p) const { return p; }
+        virtual Pod p(Pod p) const { return p; }
+        virtual NonPod n(NonPod p) const { return p; }
+        virtual ~TestObserver() {}
+    };
+
+    extern TestObserver* obs;
+
+    inline void register_observer(TestObserver& a) {
+        obs = &a;
+    }
+    inline std::string call_s(std::string param) {
+        return obs->s(param);
+    }
+    inline Pod call_p(Pod param) {
+        return obs->p(param);
+    }
+    inline NonPod call_n(NonPod param) {
+        return obs->n(param);
+    }
+    inline NonPod make_non_pod(std::string a) {
+        NonPod p;
+        p.a = a;
+        return p;
+    }
+    "};
+    run_test_ex(
+        "TestObserver* obs;",
+        hdr,
+        quote! {
+            let obs = MyTestObserver::new_rust_owned(
+                MyTestObserver::default()
+            );
+            ffi::register_observer(obs.as_ref().borrow_mut().pin_mut());
+            ffi::call_p(ffi::Pod { a: 3 });
+            ffi::call_s("hello");
+            ffi::call_n(ffi::make_non_pod("goodbye"));
+        },
+        quote! {
+            generate!("register_observer")
+            generate!("call_s")
+            generate!("call_n")
+            generate!("call_p")
+            generate!("NonPod")
+            generate!("make_non_pod")
+            generate_pod!("Pod")
+            subclass!("TestObserver",MyTestObserver)
+        },
+        None,
+        None,
+        Some(quote! {
+            use autocxx::subclass::CppSubclass;
+            use ffi::TestObserver_methods;
+            #[autocxx::subclass::subclass]
+            #[derive(Default)]
+            pub struct MyTestObserver {
+            }
+            impl TestObserver_methods for MyTestObserver {
+                fn s(&self, p: cxx::UniquePtr<cxx::CxxString>) -> cxx::UniquePtr<cxx::CxxString> {
+                    self.peer().s_super(p)
+                }
+
+                fn p(&self, p: ffi::Pod) -> ffi::Pod {
+                    self.peer().p_super(p)
+                }
+
+                fn n(&self, p: cxx::UniquePtr<ffi::NonPod>) -> cxx::UniquePtr<ffi::NonPod> {
+                    self.peer().n_super(p)
+                }
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_pv_subclass_constructors() {
+    // Also tests a Rust-side subclass type which is an empty struct
+    let hdr = indoc! {"
+    #include <cstdint>
+    #include <string>
+
+    class TestObserver {
+    public:
+        TestObserver() {}
+        TestObserver(uint8_t) {}
+        TestObserver(std::string) {}
+        virtual void call() const { }
+        virtual ~TestObserver() {}
+    };
+
+    extern TestObserver* obs;
+
+    inline void register_observer(TestObserver& a) {
+        obs = &a;
+    }
+    inline void do_a_thing() {
+        return obs->call();
+    }
+    "};
+    run_test_ex(
+        "TestObserver* obs;",
+        hdr,
+        quote! {
+            let obs = MyTestObserver::new_rust_owned(
+                MyTestObserver::default()
+            );
+            ffi::register_observer(obs.as_ref().borrow_mut().pin_mut());
+            ffi::do_a_thing();
+        },
+        quote! {
+            generate!("register_observer")
+            generate!("do_a_thing")
+            subclass!("TestObserver",MyTestObserver)
+        },
+        None,
+        None,
+        Some(quote! {
+            use autocxx::subclass::prelude::*;
+            #[subclass]
+            #[derive(Default)]
+            pub struct MyTestObserver;
+            impl ffi::TestObserver_methods for MyTestObserver {
+                fn call(&self) {
+                    self.peer().call_super()
+                }
+            }
+            impl CppPeerConstructor<ffi::MyTestObserverCpp> for MyTestObserver {
+                fn make_peer(&mut self, peer_holder: CppSubclassRustPeerHolder<Self>) -> cxx::UniquePtr<ffi::MyTestObserverCpp> {
+                    ffi::MyTestObserverCpp::make_unique1(peer_holder, 3u8)
+                }
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_pv_subclass_fancy_constructor() {
+    let hdr = indoc! {"
+    #include <cstdint>
+
+    class Observer {
+    public:
+        Observer(uint8_t) {}
+        virtual uint32_t foo() const = 0;
+        virtual ~Observer() {}
+    };
+    inline void take_observer(const Observer&) {}
+    "};
+    run_test_expect_fail_ex(
+        "",
+        hdr,
+        quote! {
+            let o = MyObserver::new_rust_owned(MyObserver { a: 3, cpp_peer: Default::default() }, ffi::MyObserverCpp::make_unique);
+            ffi::take_observer(o.borrow().as_ref());
+        },
+        quote! {
+            generate!("take_observer")
+            subclass!("Observer",MyObserver)
+        },
+        None,
+        None,
+        Some(quote! {
+            use autocxx::subclass::CppSubclass;
+            use ffi::Observer_methods;
+            #[autocxx::subclass::subclass]
+            pub struct MyObserver {
+                a: u32
+            }
+            impl Observer_methods for MyObserver {
+                fn foo(&self) -> u32 {
+                    4
+                }
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_pv_subclass_namespaced_superclass() {
+    let hdr = indoc! {"
+    #include <cstdint>
+
+    namespace a {
+    class Observer {
+    public:
+        Observer() {}
+        virtual uint32_t foo() const = 0;
+        virtual ~Observer() {}
+    };
+    }
+    inline void take_observer(const a::Observer&) {}
+    "};
+    run_test_ex(
+        "",
+        hdr,
+        quote! {
+            let o = MyObserver::new_rust_owned(MyObserver { a: 3, cpp_peer: Default::default() });
+            ffi::take_observer(o.borrow().as_ref());
+        },
+        quote! {
+            generate!("take_observer")
+            subclass!("a::Observer",MyObserver)
+        },
+        None,
+        None,
+        Some(quote! {
+            use autocxx::subclass::CppSubclass;
+            #[autocxx::subclass::subclass]
+            pub struct MyObserver {
+                a: u32
+            }
+            impl ffi::a::Observer_methods for MyObserver {
+                fn foo(&self) -> u32 {
+                    4
+                }
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_no_constructor_make_unique() {
+    let hdr = indoc! {"
+    #include <stdint.h>
+    struct A {
+        uint32_t a;
+    };
+    "};
+    let rs = quote! {
+        ffi::A::make_unique();
+    };
+    run_test("", hdr, rs, &["A"], &[]);
+}
+
+#[test]
+fn test_no_constructor_make_unique_ns() {
+    let hdr = indoc! {"
+    #include <stdint.h>
+    namespace B {
+    struct A {
+        uint32_t a;
+    };
+    }
+    "};
+    let rs = quote! {
+        ffi::B::A::make_unique();
+    };
+    run_test("", hdr, rs, &["B::A"], &[]);
+}
+
+#[test]
+fn test_no_constructor_pod_make_unique() {
+    let hdr = indoc! {"
+    #include <stdint.h>
+    struct A {
+        uint32_t a;
+    };
+    "};
+    let rs = quote! {
+        ffi::A::make_unique();
+    };
+    run_test("", hdr, rs, &[], &["A"]);
+}
+
+#[test]
+fn test_no_constructor_pv() {
+    let hdr = indoc! {"
+    #include <stdint.h>
+    class A {
+    public:
+        virtual void foo() = 0;
+    };
+    "};
+    let rs = quote! {};
+    run_test("", hdr, rs, &["A"], &[]);
+}
+
+#[test]
+fn test_suppress_system_includes() {
+    let hdr = indoc! {"
+    #include <stdint.h>
+    #include <string>
+    inline void a() {};
+    "};
+    let rs = quote! {};
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! { generate("a")},
+        Some(Box::new(SetSuppressSystemHeaders)),
+        Some(Box::new(NoSystemHeadersChecker)),
+        None,
+    );
+}
+
+#[test]
+fn test_no_rvo_move() {
+    let hdr = indoc! {"
+    #include <memory>
+    class A {
+    public:
+        static std::unique_ptr<A> create() { return std::make_unique<A>(); }
+    };
+    "};
+    let rs = quote! {
+        ffi::A::create();
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! { generate!("A") },
+        None,
+        Some(Box::new(CppMatcher::new(
+            &["return A::create();"],
+            &["return std::move(A::create());"],
+        ))),
+        None,
+    );
+}
+
+#[test]
+fn test_abstract_up() {
+    let hdr = indoc! {"
+    #include <memory>
+    class A {
+    public:
+        virtual void foo() = 0;
+        virtual ~A() {}
+    };
+    class B : public A {
+    public:
+        void foo() {}
+    };
+    inline std::unique_ptr<A> get_a() { return std::make_unique<B>(); }
+    "};
+    let rs = quote! {};
+    run_test("", hdr, rs, &["A", "get_a"], &[]);
+}
+
+#[test]
+fn test_class_having_protected_method() {
+    let hdr = indoc! {"
+        class A {
+        protected:
+            uint32_t protected_method();
+        };
+    "};
+    let rs = quote! {};
+    run_test("", hdr, rs, &[], &["A"]);
+}
+
+#[test]
+fn test_class_having_private_method() {
+    let hdr = indoc! {"
+        class A {
+        private:
+            uint32_t private_method();
+        };
+    "};
+    let rs = quote! {};
+    run_test("", hdr, rs, &[], &["A"]);
+}
+
+#[test]
+fn test_unannotated_pointers_nullable() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        struct Bob {
+            uint32_t a;
+        };
+        inline uint32_t take_bob(const Bob* a) {
+            return a ? a->a : 0;
+        }
+    "};
+    let rs = quote! {
+        let a = ffi::Bob { a: 12 };
+        assert_eq!(ffi::take_bob(Some(&a)), 12);
+        assert_eq!(ffi::take_bob(None), 0);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(
+            &["take_bob"],
+            &["Bob"],
+            Some(quote! {
+                unannotated_pointers!(nullable)
+            }),
+        ),
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_unannotated_pointers_nullable_return() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        struct Bob {
+            uint32_t a;
+        };
+        class A {
+        public:
+            A() : bob{3}, has_bob(false) {}
+            const Bob* get_bob() const { return has_bob ? &bob : nullptr; }
+            void set_has_bob() { has_bob = true; }
+        private:
+            Bob bob;
+            bool has_bob;
+        };
+    "};
+    let rs = quote! {
+        let mut a = ffi::A::make_unique();
+        assert!(a.get_bob().is_none());
+        a.pin_mut().set_has_bob();
+        assert_eq!(a.get_bob().unwrap().a, 3);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(
+            &["A"],
+            &["Bob"],
+            Some(quote! {
+                unannotated_pointers!(nullable)
+            }),
+        ),
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_unannotated_pointers_nonnull() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        struct Bob {
+            uint32_t a;
+        };
+        inline void poke_bob(Bob* a) {
+            a->a = 42;
+        }
+    "};
+    let rs = quote! {
+        let mut a = ffi::Bob { a: 12 };
+        ffi::poke_bob(std::pin::Pin::new(&mut a));
+        assert_eq!(a.a, 42);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(
+            &["poke_bob"],
+            &["Bob"],
+            Some(quote! {
+                unannotated_pointers!(nonnull)
+            }),
+        ),
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_unannotated_pointers_nonnull_raw_fallbacks() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <cstring>
+        struct Bob {
+            uint32_t a;
+        };
+        inline Bob* get_instance() {
+            static Bob bob { 12 };
+            return &bob;
+        }
+        inline uint32_t c_string_length(const char* s) {
+            return strlen(s);
+        }
+        inline void* get_context() {
+            return get_instance();
+        }
+        inline uint32_t first_bob(Bob** bobs) {
+            return bobs[0]->a;
+        }
+    "};
+    let rs = quote! {
+        let bob: *mut ffi::Bob = ffi::get_instance();
+        assert_eq!(unsafe { (*bob).a }, 12);
+        let s = std::ffi::CString::new("hello").unwrap();
+        assert_eq!(unsafe { ffi::c_string_length(s.as_ptr()) }, 5);
+        assert_eq!(ffi::get_context() as *mut ffi::Bob, bob);
+        let mut bobs = [bob];
+        assert_eq!(unsafe { ffi::first_bob(bobs.as_mut_ptr()) }, 12);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(
+            &[
+                "get_instance",
+                "c_string_length",
+                "get_context",
+                "first_bob",
+            ],
+            &["Bob"],
+            Some(quote! {
+                unannotated_pointers!(nonnull)
+            }),
+        ),
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_out_param_pod_bool_return() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        struct Bob {
+            uint32_t a;
+        };
+        inline bool get_bob(bool succeed, Bob* out) {
+            if (succeed) {
+                out->a = 12;
+            }
+            return succeed;
+        }
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::get_bob(true).unwrap().a, 12);
+        assert!(ffi::get_bob(false).is_none());
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(
+            &["get_bob"],
+            &["Bob"],
+            Some(quote! {
+                out_param!("get_bob", out)
+            }),
+        ),
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_out_param_nonpod_reference_void_return() {
+    let hdr = indoc! {"
+        #include <string>
+        namespace a {
+        inline void get_name(std::string& out) {
+            out = \"hello\";
+        }
+        }
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::a::get_name().to_str().unwrap(), "hello");
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(
+            &["a::get_name"],
+            &[],
+            Some(quote! {
+                out_param!("a::get_name", out)
+            }),
+        ),
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_out_param_method() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <string>
+        struct ParseResult {
+            std::string msg;
+            uint32_t get_len() const { return msg.size(); }
+        };
+        class Parser {
+        public:
+            Parser() {}
+            uint32_t parse(uint32_t input, ParseResult* out) const {
+                out->msg = std::string(input, 'x');
+                return input + 1;
+            }
+        };
+    "};
+    let rs = quote! {
+        let p = ffi::Parser::make_unique();
+        let (ret, result) = p.parse(3);
+        assert_eq!(ret, 4);
+        assert_eq!(result.get_len(), 3);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(
+            &["Parser", "ParseResult"],
+            &[],
+            Some(quote! {
+                out_param!("Parser::parse", out)
+            }),
+        ),
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_detect_out_params() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        struct Bob {
+            uint32_t a;
+        };
+        inline uint32_t double_it(uint32_t input, Bob* out_bob) {
+            out_bob->a = input * 2;
+            return input;
+        }
+    "};
+    let rs = quote! {
+        let (ret, bob) = ffi::double_it(4);
+        assert_eq!(ret, 4);
+        assert_eq!(bob.a, 8);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(
+            &["double_it"],
+            &["Bob"],
+            Some(quote! {
+                detect_out_params!()
+            }),
+        ),
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_detect_out_params_without_default_constructor() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        struct Bob {
+            Bob(uint32_t a_) : a(a_) {}
+            uint32_t a;
+        };
+        inline void get_bob(Bob* out) {
+            out->a = 3;
+        }
+        inline uint32_t double_it(uint32_t input, uint32_t* out) {
+            *out = input * 2;
+            return input;
+        }
+    "};
+    let rs = quote! {
+        let (ret, doubled) = ffi::double_it(4);
+        assert_eq!(ret, 4);
+        assert_eq!(doubled, 8);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(
+            &["get_bob", "double_it", "Bob"],
+            &[],
+            Some(quote! {
+                detect_out_params!()
+            }),
+        ),
         None,
+        Some(make_error_finder("get_bob")),
         None,
-        Some(quote! {
-            use autocxx::subclass::CppSubclass;
-            use ffi::Observer_methods;
-            #[autocxx::subclass::subclass]
-            pub struct MyObserver {
-                a: u32
-            }
-            impl Observer_methods for MyObserver {
-                fn foo(&self) -> u32 {
-                    4
-                }
-            }
-        }),
     );
 }
 
 #[test]
-fn test_pv_subclass_namespaced_superclass() {
+fn test_out_param_abstract() {
     let hdr = indoc! {"
-    #include <cstdint>
-
-    namespace a {
-    class Observer {
-    public:
-        Observer() {}
-        virtual uint32_t foo() const = 0;
-        virtual ~Observer() {}
-    };
-    }
-    inline void take_observer(const a::Observer&) {}
+        #include <cstdint>
+        class Shape {
+        public:
+            virtual uint32_t sides() const = 0;
+            virtual ~Shape() {}
+        };
+        inline void get_shape(Shape* out) {}
     "};
+    let rs = quote! {};
     run_test_ex(
         "",
         hdr,
-        quote! {
-            let o = MyObserver::new_rust_owned(MyObserver { a: 3, cpp_peer: Default::default() });
-            ffi::take_observer(o.borrow().as_ref());
-        },
-        quote! {
-            generate!("take_observer")
-            subclass!("a::Observer",MyObserver)
-        },
+        rs,
+        directives_from_lists(
+            &["get_shape", "Shape"],
+            &[],
+            Some(quote! {
+                out_param!("get_shape", out)
+            }),
+        ),
         None,
+        Some(make_error_finder("get_shape")),
         None,
-        Some(quote! {
-            use autocxx::subclass::CppSubclass;
-            #[autocxx::subclass::subclass]
-            pub struct MyObserver {
-                a: u32
-            }
-            impl ffi::a::Observer_methods for MyObserver {
-                fn foo(&self) -> u32 {
-                    4
-                }
-            }
-        }),
     );
 }
 
 #[test]
-fn test_no_constructor_make_unique() {
+fn test_pod_with_bitfields() {
     let hdr = indoc! {"
-    #include <stdint.h>
-    struct A {
-        uint32_t a;
-    };
+        #include <cstdint>
+        struct Flags {
+            uint32_t a : 3;
+            uint32_t b : 5;
+            bool c : 1;
+            uint32_t d;
+        };
+        inline Flags make_flags() {
+            Flags f;
+            f.a = 5;
+            f.b = 17;
+            f.c = true;
+            f.d = 100;
+            return f;
+        }
+        inline uint32_t sum_flags(const Flags& f) {
+            return f.a + f.b + f.d;
+        }
     "};
     let rs = quote! {
-        ffi::A::make_unique();
+        let mut f = ffi::make_flags();
+        assert_eq!(f.a(), 5);
+        assert_eq!(f.b(), 17);
+        assert!(f.c());
+        assert_eq!(f.d, 100);
+        std::pin::Pin::new(&mut f).set_a(2);
+        std::pin::Pin::new(&mut f).set_c(false);
+        assert_eq!(f.a(), 2);
+        assert!(!f.c());
+        assert_eq!(ffi::sum_flags(&f), 119);
     };
-    run_test("", hdr, rs, &["A"], &[]);
+    run_test("", hdr, rs, &["make_flags", "sum_flags"], &["Flags"]);
 }
 
 #[test]
-fn test_no_constructor_make_unique_ns() {
+fn test_pod_with_bitfields_and_similarly_named_method() {
     let hdr = indoc! {"
-    #include <stdint.h>
-    namespace B {
-    struct A {
-        uint32_t a;
-    };
-    }
+        #include <cstdint>
+        struct Flags {
+            uint32_t a : 3;
+            uint32_t d;
+            uint32_t get_bitfield_total() const { return a + d; }
+            void set_bitfield_total(uint32_t total) { d = total - a; }
+        };
+        inline Flags make_flags() {
+            Flags f;
+            f.a = 5;
+            f.d = 100;
+            return f;
+        }
     "};
     let rs = quote! {
-        ffi::B::A::make_unique();
+        let mut f = ffi::make_flags();
+        assert_eq!(f.a(), 5);
+        assert_eq!(f.get_bitfield_total(), 105);
+        std::pin::Pin::new(&mut f).set_bitfield_total(10);
+        assert_eq!(f.d, 5);
     };
-    run_test("", hdr, rs, &["B::A"], &[]);
+    run_test("", hdr, rs, &["make_flags"], &["Flags"]);
 }
 
 #[test]
-fn test_no_constructor_pod_make_unique() {
+fn test_pod_union() {
     let hdr = indoc! {"
-    #include <stdint.h>
-    struct A {
-        uint32_t a;
-    };
+        #include <cstdint>
+        union Number {
+            int32_t i;
+            float f;
+        };
+        struct Tagged {
+            uint32_t tag;
+            Number value;
+        };
+        inline Tagged make_int(int32_t i) {
+            Tagged t;
+            t.tag = 0;
+            t.value.i = i;
+            return t;
+        }
+        inline int32_t get_int(const Tagged& t) {
+            return t.value.i;
+        }
     "};
     let rs = quote! {
-        ffi::A::make_unique();
+        let mut t = ffi::make_int(42);
+        assert_eq!(t.tag, 0);
+        assert_eq!(unsafe { t.value.i }, 42);
+        t.value.i = 7;
+        assert_eq!(ffi::get_int(&t), 7);
     };
-    run_test("", hdr, rs, &[], &["A"]);
+    run_test("", hdr, rs, &["make_int", "get_int"], &["Tagged"]);
 }
 
 #[test]
-fn test_no_constructor_pv() {
+fn test_union_with_non_pod_member() {
     let hdr = indoc! {"
-    #include <stdint.h>
-    class A {
-    public:
-        virtual void foo() = 0;
+        #include <cstdint>
+        #include <string>
+        union Holder {
+            Holder() : i(3) {}
+            ~Holder() {}
+            std::string s;
+            uint32_t i;
+        };
+    "};
+    let rs = quote! {
+        let mut h = ffi::Holder::make_unique();
+        assert_eq!(unsafe { h.i() }, 3);
+        h.pin_mut().set_i(5);
+        assert_eq!(unsafe { h.i() }, 5);
     };
+    run_test("", hdr, rs, &["Holder"], &[]);
+}
+
+#[test]
+fn test_pod_layout_assertions() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        struct Point {
+            uint8_t tag;
+            uint32_t x;
+            uint16_t y;
+        };
+        inline Point make_point() {
+            Point p;
+            p.tag = 1;
+            p.x = 2;
+            p.y = 3;
+            return p;
+        }
     "};
-    let rs = quote! {};
-    run_test("", hdr, rs, &["A"], &[]);
+    let rs = quote! {
+        let p = ffi::make_point();
+        assert_eq!(p.x, 2);
+        assert_eq!(p.y, 3);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("make_point")
+            generate_pod!("Point")
+        },
+        None,
+        Some(Box::new(CppMatcher::new(
+            &[
+                "static_assert(sizeof(Point) == 12",
+                "static_assert(alignof(Point) == 4",
+                "static_assert(offsetof(Point, y) == 8",
+            ],
+            &[],
+        ))),
+        None,
+    );
 }
 
 #[test]
-fn test_suppress_system_includes() {
+fn test_pod_layout_assertions_rust() {
     let hdr = indoc! {"
-    #include <stdint.h>
-    #include <string>
-    inline void a() {};
+        #include <cstdint>
+        struct Point {
+            uint8_t tag;
+            uint32_t x;
+        };
     "};
     let rs = quote! {};
     run_test_ex(
         "",
         hdr,
         rs,
-        quote! { generate("a")},
-        Some(Box::new(SetSuppressSystemHeaders)),
-        Some(Box::new(NoSystemHeadersChecker)),
+        quote! {
+            generate_pod!("Point")
+        },
+        None,
+        Some(make_string_finder(vec![
+            "size_of",
+            "align_of",
+            "autocxx_layout_test_Point",
+        ])),
         None,
     );
 }
 
 #[test]
-fn test_no_rvo_move() {
+fn test_pod_layout_assertions_private_fields() {
     let hdr = indoc! {"
-    #include <memory>
-    class A {
-    public:
-        static std::unique_ptr<A> create() { return std::make_unique<A>(); }
-    };
+        #include <cstdint>
+        class Counter {
+        public:
+            Counter() : tag(1), count(2) {}
+            uint32_t get() const { return count; }
+        private:
+            uint8_t tag;
+            uint32_t count;
+        };
     "};
     let rs = quote! {
-        ffi::A::create();
+        let c = ffi::Counter::make_unique();
+        assert_eq!(c.get(), 2);
     };
     run_test_ex(
         "",
         hdr,
         rs,
-        quote! { generate!("A") },
+        quote! {
+            generate_pod!("Counter")
+        },
         None,
         Some(Box::new(CppMatcher::new(
-            &["return A::create();"],
-            &["return std::move(A::create());"],
+            &["static_assert(sizeof(Counter) == 8"],
+            &["offsetof(Counter"],
         ))),
         None,
     );
 }
 
 #[test]
-fn test_abstract_up() {
+fn test_namespace_alias() {
     let hdr = indoc! {"
-    #include <memory>
-    class A {
-    public:
-        virtual void foo() = 0;
-        virtual ~A() {}
+        #include <cstdint>
+        namespace org {
+        namespace chromium {
+        namespace base {
+        struct Point {
+            uint32_t x;
+        };
+        namespace internal {
+        inline uint32_t get_x(const Point& p) { return p.x; }
+        }
+        }
+        }
+        }
+    "};
+    let rs = quote! {
+        let p = ffi::base::Point { x: 3 };
+        assert_eq!(ffi::base::internal::get_x(&p), 3);
     };
-    class B : public A {
-    public:
-        void foo() {}
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("org::chromium::base::internal::get_x")
+            generate_pod!("org::chromium::base::Point")
+            namespace_alias!("org::chromium::base", base)
+        },
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_naming_policy() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        struct frame_buffer {
+            uint32_t frame_count;
+        };
+        class Decoder {
+        public:
+            Decoder() : frames(0) {}
+            void DecodeFrame() { frames++; }
+            uint32_t GetFrameCount() const { return frames; }
+        private:
+            uint32_t frames;
+        };
+        inline uint32_t GetFrameCount(const frame_buffer& fb) { return fb.frame_count; }
+    "};
+    let rs = quote! {
+        let fb = ffi::FrameBuffer { frame_count: 4 };
+        assert_eq!(ffi::get_frame_count(&fb), 4);
+        let mut d = ffi::Decoder::make_unique();
+        d.pin_mut().decode_frame();
+        assert_eq!(d.get_frame_count(), 1);
     };
-    inline std::unique_ptr<A> get_a() { return std::make_unique<B>(); }
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("Decoder")
+            generate!("GetFrameCount")
+            generate_pod!("frame_buffer")
+            naming_policy!(snake_case, upper_camel_case_types)
+        },
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_naming_policy_collision() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        inline uint32_t get_value() { return 1; }
+        inline uint32_t GetValue() { return 2; }
     "};
-    let rs = quote! {};
-    run_test("", hdr, rs, &["A", "get_a"], &[]);
+    let rs = quote! {
+        assert_eq!(ffi::get_value(), 1);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("get_value")
+            generate!("GetValue")
+            naming_policy!(snake_case)
+        },
+        None,
+        None,
+        None,
+    );
 }
 
 #[test]
-fn test_class_having_protected_method() {
+fn test_naming_policy_type_collision() {
     let hdr = indoc! {"
-        class A {
-        protected:
-            uint32_t protected_method();
+        #include <cstdint>
+        struct frame_buffer {
+            uint32_t frame_count;
+        };
+        struct FrameBuffer {
+            uint32_t frame_count;
         };
     "};
-    let rs = quote! {};
-    run_test("", hdr, rs, &[], &["A"]);
+    let rs = quote! {
+        let _ = ffi::FrameBuffer { frame_count: 4 };
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate_pod!("frame_buffer")
+            generate_pod!("FrameBuffer")
+            naming_policy!(upper_camel_case_types)
+        },
+        None,
+        Some(make_error_finder("frame_buffer")),
+        None,
+    );
 }
 
 #[test]
-fn test_class_having_private_method() {
+fn test_thread_safe() {
     let hdr = indoc! {"
-        class A {
+        #include <cstdint>
+        namespace ns {
+        class Counter {
+        public:
+            Counter() : count(0) {}
+            uint32_t get() const { return count; }
         private:
-            uint32_t private_method();
+            uint32_t count;
         };
+        }
     "};
-    let rs = quote! {};
-    run_test("", hdr, rs, &[], &["A"]);
+    let rs = quote! {
+        fn assert_send_and_sync<T: Send + Sync>(_: &T) {}
+        let c = ffi::ns::Counter::make_unique();
+        assert_send_and_sync(c.as_ref().unwrap());
+        let c = std::thread::spawn(move || c).join().unwrap();
+        assert_eq!(c.get(), 0);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("ns::Counter")
+            safety!(unsafe)
+            thread_safe!("ns::Counter", send, sync)
+        },
+        None,
+        None,
+        None,
+    );
 }
 
 // Yet to test:
 // - Ifdef
-// - Out param pointers
 // - ExcludeUtilities
 // - Struct fields which are typedefs
 // Negative tests:
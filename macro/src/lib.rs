@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use autocxx_parser::{IncludeCpp, SubclassAttrs};
+use autocxx_parser::{IncludeCpp, SubclassAttrs, SubclassLock};
 use proc_macro::TokenStream;
 use proc_macro2::{Ident, Span};
 use proc_macro_error::{abort, proc_macro_error};
@@ -61,9 +61,23 @@ pub fn subclass(attr: TokenStream, item: TokenStream) -> TokenStream {
     };
     let subclass_attrs: SubclassAttrs = syn::parse(attr)
         .unwrap_or_else(|_| abort!(Span::call_site(), "Unable to parse attributes"));
+    let storage = match subclass_attrs.thread_safe {
+        None => quote! { autocxx::subclass::SingleThreaded },
+        Some(SubclassLock::Mutex) => quote! { autocxx::subclass::ThreadSafeMutex },
+        Some(SubclassLock::RwLock) => quote! { autocxx::subclass::ThreadSafeRwLock },
+    };
     let self_owned_bit = if subclass_attrs.self_owned {
         Some(quote! {
-            impl autocxx::subclass::CppSubclassSelfOwned<ffi::#cpp_ident> for #id {}
+            impl autocxx::subclass::CppSubclassSelfOwned<ffi::#cpp_ident, #storage> for #id {}
+        })
+    } else {
+        None
+    };
+    let on_cpp_destroyed_bit = if subclass_attrs.on_cpp_destroyed {
+        Some(quote! {
+            fn on_cpp_destroyed(&mut self) {
+                <Self as autocxx::subclass::CppPeerDestroyed>::on_cpp_destroyed(self)
+            }
         })
     } else {
         None
@@ -71,13 +85,14 @@ pub fn subclass(attr: TokenStream, item: TokenStream) -> TokenStream {
     let toks = quote! {
         #s
 
-        impl autocxx::subclass::CppSubclass<ffi::#cpp_ident> for #id {
+        impl autocxx::subclass::CppSubclass<ffi::#cpp_ident, #storage> for #id {
             fn peer_holder_mut(&mut self) -> &mut autocxx::subclass::CppSubclassCppPeerHolder<ffi::#cpp_ident> {
                 &mut self.cpp_peer
             }
             fn peer_holder(&self) -> &autocxx::subclass::CppSubclassCppPeerHolder<ffi::#cpp_ident> {
                 &self.cpp_peer
             }
+            #on_cpp_destroyed_bit
         }
 
         #self_owned_bit
@@ -24,7 +24,8 @@ use syn::{Ident, Result as ParseResult};
 
 use crate::{
     directives::{EXTERN_RUST_TYPE, SUBCLASS},
-    RustPath,
+    subclass_attrs::parse_cpp_namespace,
+    RustPath, SubclassLock, SubclassPanicPolicy,
 };
 
 #[derive(PartialEq, Clone, Debug, Hash)]
@@ -58,6 +59,81 @@ impl Parse for UnsafePolicy {
     }
 }
 
+impl UnsafePolicy {
+    /// `thread_safe!` and thread-safe subclasses assert something which
+    /// autocxx can't check, so are only allowed alongside `safety!(unsafe)`.
+    pub fn check_thread_safety_allowed(&self, span: Span) -> ParseResult<()> {
+        if *self == UnsafePolicy::AllFunctionsSafe {
+            Ok(())
+        } else {
+            Err(syn::Error::new(
+                span,
+                "thread_safe asserts something which autocxx can't check, so requires safety!(unsafe)",
+            ))
+        }
+    }
+}
+
+/// How to treat C++ pointers whose nullability is not otherwise known.
+#[derive(PartialEq, Clone, Copy, Debug, Hash)]
+pub enum Nullability {
+    /// The pointer may be null, so it is represented in Rust as an
+    /// `Option` of a reference.
+    Nullable,
+    /// The pointer is never null, so it is represented in Rust as
+    /// a reference.
+    NonNull,
+}
+
+impl Parse for Nullability {
+    fn parse(input: ParseStream) -> ParseResult<Self> {
+        let id: syn::Ident = input.parse()?;
+        let r = if id == "nullable" {
+            Ok(Nullability::Nullable)
+        } else if id == "nonnull" {
+            Ok(Nullability::NonNull)
+        } else {
+            Err(syn::Error::new(id.span(), "expected nullable or nonnull"))
+        };
+        if !input.is_empty() {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "unexpected tokens within unannotated_pointers directive",
+            ));
+        }
+        r
+    }
+}
+
+/// How to adjust C++ names so that they follow Rust naming conventions.
+#[derive(PartialEq, Clone, Copy, Debug, Hash, Default)]
+pub struct NamingPolicy {
+    /// Convert the names of methods and free functions to snake_case.
+    pub snake_case_functions: bool,
+    /// Convert the names of types to UpperCamelCase.
+    pub upper_camel_case_types: bool,
+}
+
+impl Parse for NamingPolicy {
+    fn parse(input: ParseStream) -> ParseResult<Self> {
+        let mut policy = NamingPolicy::default();
+        let options = input.parse_terminated::<syn::Ident, Token![,]>(syn::Ident::parse)?;
+        for option in options {
+            if option == "snake_case" {
+                policy.snake_case_functions = true;
+            } else if option == "upper_camel_case_types" {
+                policy.upper_camel_case_types = true;
+            } else {
+                return Err(syn::Error::new(
+                    option.span(),
+                    "expected snake_case or upper_camel_case_types",
+                ));
+            }
+        }
+        Ok(policy)
+    }
+}
+
 /// Allowlist configuration.
 #[derive(Hash, Debug)]
 pub enum Allowlist {
@@ -68,29 +144,37 @@ pub enum Allowlist {
 
 impl Allowlist {
     pub fn push(&mut self, item: LitStr) -> ParseResult<()> {
+        self.push_str(item.value(), item.span())
+    }
+
+    fn push_str(&mut self, item: String, span: Span) -> ParseResult<()> {
         match self {
             Allowlist::Unspecified(ref mut uncommitted_list) => {
                 let new_list = uncommitted_list
                     .drain(..)
-                    .chain(std::iter::once(item.value()))
+                    .chain(std::iter::once(item))
                     .collect();
                 *self = Allowlist::Specific(new_list);
             }
             Allowlist::All => {
                 return Err(syn::Error::new(
-                    item.span(),
+                    span,
                     "use either generate!/generate_pod! or generate_all!, not both.",
                 ))
             }
-            Allowlist::Specific(list) => list.push(item.value()),
+            Allowlist::Specific(list) => list.push(item),
         };
         Ok(())
     }
 
     pub(crate) fn set_all(&mut self, ident: &Ident) -> ParseResult<()> {
+        self.set_all_with_span(ident.span())
+    }
+
+    fn set_all_with_span(&mut self, span: Span) -> ParseResult<()> {
         if matches!(self, Allowlist::Specific(..)) {
             return Err(syn::Error::new(
-                ident.span(),
+                span,
                 "use either generate!/generate_pod! or generate_all!, not both.",
             ));
         }
@@ -109,6 +193,35 @@ impl Default for Allowlist {
 pub struct Subclass {
     pub superclass: String,
     pub subclass: Ident,
+    /// Whether the subclass may be used from multiple threads, and if so
+    /// how it's locked.
+    pub thread_safe: Option<SubclassLock>,
+    /// What to do if a method of the subclass panics when called from C++.
+    pub on_panic: Option<SubclassPanicPolicy>,
+    /// The C++ namespace in which to generate the subclass's C++ peer,
+    /// e.g. `app::detail`. If this isn't given, the build-time code
+    /// generator derives one from the package name and Rust module path,
+    /// or failing that uses the root namespace.
+    pub cpp_namespace: Option<String>,
+    /// Whether the Rust subclass wants to know when its C++ peer is
+    /// destroyed, via `CppSubclass::on_cpp_destroyed`.
+    pub on_cpp_destroyed: bool,
+    /// Whether this came from `implement_interface!`, in which case
+    /// `subclass` names a Rust trait whose trait objects implement the
+    /// superclass, rather than a Rust struct.
+    pub interface_impl: bool,
+}
+
+impl Subclass {
+    /// The C++ name of an item generated for this subclass, given the
+    /// suffix which distinguishes it, qualified by the subclass's C++
+    /// namespace if any.
+    fn cpp_name_with_suffix(&self, suffix: &str) -> String {
+        match &self.cpp_namespace {
+            Some(ns) => format!("{}::{}{}", ns, self.subclass, suffix),
+            None => format!("{}{}", self.subclass, suffix),
+        }
+    }
 }
 
 pub struct RustFun {
@@ -136,6 +249,14 @@ pub struct IncludeCppConfig {
     blocklist: Vec<String>,
     exclude_utilities: bool,
     mod_name: Option<Ident>,
+    unannotated_pointers: Option<Nullability>,
+    out_params: Vec<(String, String)>,
+    detect_out_params: bool,
+    namespace_aliases: Vec<(String, Ident)>,
+    flatten_inline_namespaces: bool,
+    naming_policy: NamingPolicy,
+    send_types: Vec<String>,
+    sync_types: Vec<String>,
     pub rust_types: Vec<RustPath>,
     pub subclasses: Vec<Subclass>,
     pub extern_rust_funs: Vec<RustFun>,
@@ -158,6 +279,15 @@ impl Parse for IncludeCppConfig {
         let mut rust_types = Vec::new();
         let mut exclude_utilities = false;
         let mut mod_name = None;
+        let mut unannotated_pointers = None;
+        let mut out_params = Vec::new();
+        let mut detect_out_params = false;
+        let mut namespace_aliases = Vec::new();
+        let mut flatten_inline_namespaces = false;
+        let mut naming_policy = NamingPolicy::default();
+        let mut send_types = Vec::new();
+        let mut sync_types = Vec::new();
+        let mut thread_safe_directive = None;
         let mut subclasses = Vec::new();
 
         while !input.is_empty() {
@@ -203,9 +333,51 @@ impl Parse for IncludeCppConfig {
                     let superclass: syn::LitStr = args.parse()?;
                     args.parse::<syn::token::Comma>()?;
                     let subclass: syn::Ident = args.parse()?;
+                    let mut thread_safe = None;
+                    let mut on_panic = None;
+                    let mut cpp_namespace = None;
+                    let mut on_cpp_destroyed = false;
+                    while args.parse::<Option<syn::token::Comma>>()?.is_some() {
+                        let option: Ident = args.parse()?;
+                        if option == "thread_safe" {
+                            thread_safe = Some(SubclassLock::parse_args(&args)?);
+                            thread_safe_directive = Some(option);
+                        } else if option == "on_panic" {
+                            on_panic = Some(SubclassPanicPolicy::parse_args(&args)?);
+                        } else if option == "cpp_namespace" {
+                            cpp_namespace = Some(parse_cpp_namespace(&args)?);
+                        } else if option == "on_cpp_destroyed" {
+                            on_cpp_destroyed = true;
+                        } else {
+                            return Err(syn::Error::new(
+                                option.span(),
+                                "expected thread_safe, on_panic, cpp_namespace or on_cpp_destroyed",
+                            ));
+                        }
+                    }
                     subclasses.push(Subclass {
                         superclass: superclass.value(),
                         subclass,
+                        thread_safe,
+                        on_panic,
+                        cpp_namespace,
+                        on_cpp_destroyed,
+                        interface_impl: false,
+                    });
+                } else if ident == "implement_interface" {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    let superclass: syn::LitStr = args.parse()?;
+                    args.parse::<syn::token::Comma>()?;
+                    let rust_trait: syn::Ident = args.parse()?;
+                    subclasses.push(Subclass {
+                        superclass: superclass.value(),
+                        subclass: rust_trait,
+                        thread_safe: None,
+                        on_panic: None,
+                        cpp_namespace: None,
+                        on_cpp_destroyed: false,
+                        interface_impl: true,
                     });
                 } else if ident == "parse_only" {
                     parse_only = true;
@@ -228,6 +400,54 @@ impl Parse for IncludeCppConfig {
                     let args;
                     syn::parenthesized!(args in input);
                     unsafe_policy = args.parse()?;
+                } else if ident == "unannotated_pointers" {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    unannotated_pointers = Some(args.parse()?);
+                } else if ident == "out_param" {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    let function: syn::LitStr = args.parse()?;
+                    args.parse::<syn::token::Comma>()?;
+                    let param: syn::Ident = args.parse()?;
+                    out_params.push((function.value(), param.to_string()));
+                } else if ident == "detect_out_params" {
+                    detect_out_params = true;
+                    swallow_parentheses(&input, &ident)?;
+                } else if ident == "namespace_alias" {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    let namespace: syn::LitStr = args.parse()?;
+                    args.parse::<syn::token::Comma>()?;
+                    let alias: syn::Ident = args.parse()?;
+                    namespace_aliases.push((namespace.value(), alias));
+                } else if ident == "flatten_inline_namespaces" {
+                    flatten_inline_namespaces = true;
+                    swallow_parentheses(&input, &ident)?;
+                } else if ident == "naming_policy" {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    naming_policy = args.parse()?;
+                } else if ident == "thread_safe" {
+                    let args;
+                    syn::parenthesized!(args in input);
+                    let ty: syn::LitStr = args.parse()?;
+                    args.parse::<syn::token::Comma>()?;
+                    let traits =
+                        args.parse_terminated::<syn::Ident, Token![,]>(syn::Ident::parse)?;
+                    if traits.is_empty() {
+                        return Err(syn::Error::new(ident.span(), "expected send and/or sync"));
+                    }
+                    for t in traits {
+                        if t == "send" {
+                            send_types.push(ty.value());
+                        } else if t == "sync" {
+                            sync_types.push(ty.value());
+                        } else {
+                            return Err(syn::Error::new(t.span(), "expected send or sync"));
+                        }
+                    }
+                    thread_safe_directive = Some(ident);
                 } else {
                     return Err(syn::Error::new(
                         ident.span(),
@@ -240,6 +460,10 @@ impl Parse for IncludeCppConfig {
             }
         }
 
+        if let Some(ident) = thread_safe_directive {
+            unsafe_policy.check_thread_safety_allowed(ident.span())?;
+        }
+
         Ok(IncludeCppConfig {
             inclusions,
             unsafe_policy,
@@ -251,12 +475,178 @@ impl Parse for IncludeCppConfig {
             blocklist,
             exclude_utilities,
             mod_name,
+            unannotated_pointers,
+            out_params,
+            detect_out_params,
+            namespace_aliases,
+            flatten_inline_namespaces,
+            naming_policy,
+            send_types,
+            sync_types,
             subclasses,
             extern_rust_funs: Vec::new(),
         })
     }
 }
 
+impl Default for IncludeCppConfig {
+    fn default() -> Self {
+        IncludeCppConfig {
+            inclusions: Vec::new(),
+            unsafe_policy: UnsafePolicy::AllFunctionsUnsafe,
+            parse_only: false,
+            exclude_impls: false,
+            pod_requests: Vec::new(),
+            allowlist: Allowlist::default(),
+            blocklist: Vec::new(),
+            exclude_utilities: false,
+            mod_name: None,
+            unannotated_pointers: None,
+            out_params: Vec::new(),
+            detect_out_params: false,
+            namespace_aliases: Vec::new(),
+            flatten_inline_namespaces: false,
+            naming_policy: NamingPolicy::default(),
+            send_types: Vec::new(),
+            sync_types: Vec::new(),
+            rust_types: Vec::new(),
+            subclasses: Vec::new(),
+            extern_rust_funs: Vec::new(),
+        }
+    }
+}
+
+/// Builds an [`IncludeCppConfig`] programmatically, for tools which want
+/// to drive autocxx without constructing an `include_cpp!` macro. Each
+/// method corresponds to one of the directives which may be used within
+/// that macro.
+///
+/// ```
+/// # use autocxx_parser::{IncludeCppConfig, UnsafePolicy};
+/// let config = IncludeCppConfig::builder()
+///     .include("input.h")
+///     .generate("do_math")
+///     .generate_pod("Point")
+///     .safety(UnsafePolicy::AllFunctionsSafe)
+///     .build()
+///     .unwrap();
+/// assert_eq!(config.get_pod_requests(), &["Point".to_string()]);
+/// ```
+#[derive(Default)]
+pub struct IncludeCppConfigBuilder {
+    config: IncludeCppConfig,
+    allowlist: Vec<String>,
+    generate_all: bool,
+    subclasses: Vec<(String, String)>,
+    mod_name: Option<String>,
+}
+
+impl IncludeCppConfigBuilder {
+    /// Equivalent to `#include "header.h"`.
+    pub fn include(mut self, header: impl Into<String>) -> Self {
+        self.config.inclusions.push(header.into());
+        self
+    }
+
+    /// Equivalent to `generate!`.
+    pub fn generate(mut self, item: impl Into<String>) -> Self {
+        self.allowlist.push(item.into());
+        self
+    }
+
+    /// Equivalent to `generate_pod!`.
+    pub fn generate_pod(mut self, item: impl Into<String>) -> Self {
+        let item = item.into();
+        self.config.pod_requests.push(item.clone());
+        self.allowlist.push(item);
+        self
+    }
+
+    /// Equivalent to `generate_all!`.
+    pub fn generate_all(mut self) -> Self {
+        self.generate_all = true;
+        self
+    }
+
+    /// Equivalent to `pod!`.
+    pub fn pod(mut self, item: impl Into<String>) -> Self {
+        self.config.pod_requests.push(item.into());
+        self
+    }
+
+    /// Equivalent to `block!`.
+    pub fn block(mut self, item: impl Into<String>) -> Self {
+        self.config.blocklist.push(item.into());
+        self
+    }
+
+    /// Equivalent to `subclass!`.
+    pub fn subclass(mut self, superclass: impl Into<String>, subclass: impl Into<String>) -> Self {
+        self.subclasses.push((superclass.into(), subclass.into()));
+        self
+    }
+
+    /// Equivalent to `safety!`.
+    pub fn safety(mut self, unsafe_policy: UnsafePolicy) -> Self {
+        self.config.unsafe_policy = unsafe_policy;
+        self
+    }
+
+    /// Equivalent to `name!`.
+    pub fn name(mut self, mod_name: impl Into<String>) -> Self {
+        self.mod_name = Some(mod_name.into());
+        self
+    }
+
+    /// Equivalent to `exclude_utilities!`.
+    pub fn exclude_utilities(mut self) -> Self {
+        self.config.exclude_utilities = true;
+        self
+    }
+
+    /// Equivalent to `parse_only!`.
+    pub fn parse_only(mut self) -> Self {
+        self.config.parse_only = true;
+        self
+    }
+
+    /// Check the configuration for consistency, and return it. As with
+    /// `include_cpp!`, it's an error to specify neither `generate` nor
+    /// `generate_all`, or both.
+    pub fn build(self) -> ParseResult<IncludeCppConfig> {
+        let mut config = self.config;
+        let span = Span::call_site();
+        if self.generate_all {
+            config.allowlist.set_all_with_span(span)?;
+        }
+        for item in self.allowlist {
+            config.allowlist.push_str(item, span)?;
+        }
+        for (superclass, subclass) in self.subclasses {
+            config.subclasses.push(Subclass {
+                superclass,
+                subclass: syn::parse_str(&subclass)?,
+                thread_safe: None,
+                on_panic: None,
+                cpp_namespace: None,
+                on_cpp_destroyed: false,
+                interface_impl: false,
+            });
+        }
+        config.mod_name = self.mod_name.as_deref().map(syn::parse_str).transpose()?;
+        config.confirm_complete(false)?;
+        Ok(config)
+    }
+}
+
+impl IncludeCppConfig {
+    /// Returns a builder, for constructing a configuration without
+    /// parsing an `include_cpp!` macro.
+    pub fn builder() -> IncludeCppConfigBuilder {
+        IncludeCppConfigBuilder::default()
+    }
+}
+
 fn swallow_parentheses(input: &ParseStream, latest_ident: &Ident) -> ParseResult<()> {
     let args;
     syn::parenthesized!(args in input);
@@ -288,6 +678,59 @@ impl IncludeCppConfig {
         self.exclude_utilities
     }
 
+    /// How to represent pointer parameters and return values which
+    /// carry no nullability annotation in the C++ headers. `None` means
+    /// that such pointers remain raw pointers.
+    pub fn unannotated_pointer_nullability(&self) -> Option<Nullability> {
+        self.unannotated_pointers
+    }
+
+    /// Whether the user has asked for the given parameter of the given
+    /// function (e.g. `ns::Get` or `ns::Type::Get`) to be treated as an
+    /// out parameter, and thus turned into a return value.
+    pub fn is_out_param(&self, fn_name: &str, param: &str) -> bool {
+        self.out_params
+            .iter()
+            .any(|(f, p)| f == fn_name && p == param)
+    }
+
+    /// Whether to guess which parameters are out parameters based on
+    /// their names and types.
+    pub fn detect_out_params(&self) -> bool {
+        self.detect_out_params
+    }
+
+    /// C++ namespaces (e.g. `org::chromium::base`) which should appear
+    /// under a different name in the generated Rust mod hierarchy.
+    pub fn namespace_aliases(&self) -> impl Iterator<Item = (&str, &Ident)> {
+        self.namespace_aliases
+            .iter()
+            .map(|(namespace, alias)| (namespace.as_str(), alias))
+    }
+
+    /// Whether to omit ABI-versioning namespaces such as `std::__1`
+    /// from the generated Rust mod hierarchy.
+    pub fn flatten_inline_namespaces(&self) -> bool {
+        self.flatten_inline_namespaces
+    }
+
+    /// How C++ names should be adjusted to follow Rust naming conventions.
+    pub fn naming_policy(&self) -> NamingPolicy {
+        self.naming_policy
+    }
+
+    /// Whether the user has promised that the given type (e.g. `ns::Foo`)
+    /// may be sent between threads.
+    pub fn is_send(&self, cpp_name: &str) -> bool {
+        self.send_types.iter().any(|t| t == cpp_name)
+    }
+
+    /// Whether the user has promised that the given type (e.g. `ns::Foo`)
+    /// may be shared between threads.
+    pub fn is_sync(&self, cpp_name: &str) -> bool {
+        self.sync_types.iter().any(|t| t == cpp_name)
+    }
+
     /// Items which the user has explicitly asked us to generate;
     /// we should raise an error if we weren't able to do so.
     pub fn must_generate_list(&self) -> Box<dyn Iterator<Item = String> + '_> {
@@ -298,6 +741,19 @@ impl IncludeCppConfig {
         }
     }
 
+    /// Items on the allowlist which might be preprocessor macros, for which
+    /// we can generate C++ shims to have the C++ compiler evaluate them.
+    pub fn possible_macros(&self) -> impl Iterator<Item = &str> + '_ {
+        let items = match &self.allowlist {
+            Allowlist::Specific(items) => items.as_slice(),
+            _ => &[],
+        };
+        items
+            .iter()
+            .filter(|item| !item.contains("::"))
+            .map(|item| item.as_str())
+    }
+
     /// The allowlist of items to be passed into bindgen, if any.
     pub fn bindgen_allowlist(&self) -> Option<Box<dyn Iterator<Item = String> + '_>> {
         match &self.allowlist {
@@ -313,8 +769,8 @@ impl IncludeCppConfig {
                             .iter()
                             .map(|sc| {
                                 [
-                                    format!("{}Cpp", sc.subclass),
-                                    sc.subclass.to_string(), // TODO may not be necessary
+                                    sc.cpp_name_with_suffix("Cpp"),
+                                    sc.cpp_name_with_suffix(""), // TODO may not be necessary
                                     sc.superclass.clone(),
                                 ]
                             })
@@ -398,10 +854,52 @@ impl IncludeCppConfig {
             .any(|sc| format!("{}Holder", sc.subclass.to_string()) == id)
     }
 
+    /// How the given Rust subclass is locked, if it's thread-safe.
+    pub fn get_subclass_lock(&self, subclass: &Ident) -> Option<SubclassLock> {
+        self.subclasses
+            .iter()
+            .filter(|sc| sc.subclass == *subclass)
+            .find_map(|sc| sc.thread_safe)
+    }
+
+    /// What to do if a method of the given Rust subclass panics.
+    pub fn get_subclass_panic_policy(&self, subclass: &Ident) -> SubclassPanicPolicy {
+        self.subclasses
+            .iter()
+            .filter(|sc| sc.subclass == *subclass)
+            .find_map(|sc| sc.on_panic)
+            .unwrap_or_default()
+    }
+
+    /// The C++ namespace in which to generate the C++ peer of the given
+    /// Rust subclass, if not the root namespace.
+    pub fn get_subclass_cpp_namespace(&self, subclass: &Ident) -> Option<&str> {
+        self.subclasses
+            .iter()
+            .filter(|sc| sc.subclass == *subclass)
+            .find_map(|sc| sc.cpp_namespace.as_deref())
+    }
+
+    /// Whether the given Rust subclass should be told when its C++ peer
+    /// is destroyed.
+    pub fn subclass_wants_cpp_destroyed(&self, subclass: &Ident) -> bool {
+        self.subclasses
+            .iter()
+            .any(|sc| sc.subclass == *subclass && sc.on_cpp_destroyed)
+    }
+
+    /// Whether the given "subclass" is actually a Rust trait implementing
+    /// a C++ interface, from `implement_interface!`.
+    pub fn is_interface_impl(&self, subclass: &Ident) -> bool {
+        self.subclasses
+            .iter()
+            .any(|sc| sc.subclass == *subclass && sc.interface_impl)
+    }
+
     fn is_subclass_cpp(&self, id: &str) -> bool {
         self.subclasses
             .iter()
-            .any(|sc| format!("{}Cpp", sc.subclass.to_string()) == id)
+            .any(|sc| sc.cpp_name_with_suffix("Cpp") == id)
     }
 
     /// Return the filename to which generated .rs should be written.
@@ -434,8 +932,15 @@ impl IncludeCppConfig {
 
 #[cfg(test)]
 mod parse_tests {
-    use crate::config::UnsafePolicy;
-    use syn::parse_quote;
+    use crate::config::{IncludeCppConfig, NamingPolicy, Nullability, UnsafePolicy};
+    use crate::{SubclassLock, SubclassPanicPolicy};
+    use proc_macro2::Span;
+    use syn::{parse_quote, Ident};
+
+    fn make_ident(id: &str) -> Ident {
+        Ident::new(id, Span::call_site())
+    }
+
     #[test]
     fn test_safety_unsafe() {
         let us: UnsafePolicy = parse_quote! {
@@ -457,4 +962,246 @@ mod parse_tests {
         let us: UnsafePolicy = parse_quote! {};
         assert_eq!(us, UnsafePolicy::AllFunctionsUnsafe)
     }
+
+    #[test]
+    fn test_nullability_nullable() {
+        let n: Nullability = parse_quote! {
+            nullable
+        };
+        assert_eq!(n, Nullability::Nullable)
+    }
+
+    #[test]
+    fn test_nullability_nonnull() {
+        let n: Nullability = parse_quote! {
+            nonnull
+        };
+        assert_eq!(n, Nullability::NonNull)
+    }
+
+    #[test]
+    fn test_out_params() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("ns::Get")
+            out_param!("ns::Get", out)
+            detect_out_params!()
+        };
+        assert!(config.is_out_param("ns::Get", "out"));
+        assert!(!config.is_out_param("ns::Get", "in"));
+        assert!(!config.is_out_param("Get", "out"));
+        assert!(config.detect_out_params());
+    }
+
+    #[test]
+    fn test_namespace_aliases() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("org::chromium::base::Foo")
+            namespace_alias!("org::chromium::base", base)
+            flatten_inline_namespaces!()
+        };
+        let aliases: Vec<_> = config
+            .namespace_aliases()
+            .map(|(ns, alias)| (ns.to_string(), alias.to_string()))
+            .collect();
+        assert_eq!(
+            aliases,
+            vec![("org::chromium::base".to_string(), "base".to_string())]
+        );
+        assert!(config.flatten_inline_namespaces());
+    }
+
+    #[test]
+    fn test_possible_macros() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("MY_MACRO")
+            generate!("ns::Foo")
+        };
+        assert_eq!(
+            config.possible_macros().collect::<Vec<_>>(),
+            vec!["MY_MACRO"]
+        );
+    }
+
+    #[test]
+    fn test_naming_policy() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("ns::Foo")
+            naming_policy!(snake_case, upper_camel_case_types)
+        };
+        assert_eq!(
+            config.naming_policy(),
+            NamingPolicy {
+                snake_case_functions: true,
+                upper_camel_case_types: true,
+            }
+        );
+        let np: NamingPolicy = parse_quote! {
+            snake_case
+        };
+        assert!(np.snake_case_functions);
+        assert!(!np.upper_camel_case_types);
+    }
+
+    #[test]
+    fn test_builder() {
+        let config = IncludeCppConfig::builder()
+            .include("input.h")
+            .generate("ns::Foo")
+            .generate_pod("ns::Bar")
+            .block("ns::Baz")
+            .subclass("ns::Observer", "MyObserver")
+            .safety(UnsafePolicy::AllFunctionsSafe)
+            .name("ffi2")
+            .build()
+            .unwrap();
+        assert_eq!(config.inclusions, vec!["input.h".to_string()]);
+        assert!(config.is_on_allowlist("ns::Foo"));
+        assert!(config.is_on_allowlist("ns::Bar"));
+        assert!(!config.is_on_allowlist("ns::Qux"));
+        assert_eq!(config.get_pod_requests(), &["ns::Bar".to_string()]);
+        assert!(config.is_on_blocklist("ns::Baz"));
+        assert_eq!(config.subclasses[0].subclass, "MyObserver");
+        assert_eq!(config.unsafe_policy, UnsafePolicy::AllFunctionsSafe);
+        assert_eq!(config.get_mod_name(), "ffi2");
+    }
+
+    #[test]
+    fn test_builder_errors() {
+        assert!(IncludeCppConfig::builder()
+            .include("input.h")
+            .build()
+            .is_err());
+        assert!(IncludeCppConfig::builder()
+            .generate("Foo")
+            .generate_all()
+            .build()
+            .is_err());
+        assert!(IncludeCppConfig::builder()
+            .generate("Foo")
+            .subclass("Observer", "not an ident")
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn test_thread_safe() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("ns::Foo")
+            generate!("ns::Bar")
+            safety!(unsafe)
+            thread_safe!("ns::Foo", send, sync)
+            thread_safe!("ns::Bar", send)
+        };
+        assert!(config.is_send("ns::Foo"));
+        assert!(config.is_sync("ns::Foo"));
+        assert!(config.is_send("ns::Bar"));
+        assert!(!config.is_sync("ns::Bar"));
+        assert!(!config.is_send("ns::Baz"));
+    }
+
+    #[test]
+    fn test_thread_safe_subclass() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("ns::Foo")
+            safety!(unsafe)
+            subclass!("ns::Observer", A)
+            subclass!("ns::Observer", B, thread_safe)
+            subclass!("ns::Observer", C, thread_safe(rwlock))
+        };
+        let locks: Vec<_> = config.subclasses.iter().map(|sc| sc.thread_safe).collect();
+        assert_eq!(
+            locks,
+            vec![None, Some(SubclassLock::Mutex), Some(SubclassLock::RwLock)]
+        );
+        let r: syn::Result<IncludeCppConfig> = syn::parse2(quote::quote! {
+            generate!("ns::Foo")
+            subclass!("ns::Observer", B, thread_safe)
+        });
+        assert!(r.is_err());
+    }
+
+    #[test]
+    fn test_subclass_panic_policy() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("ns::Foo")
+            safety!(unsafe)
+            subclass!("ns::Observer", A)
+            subclass!("ns::Observer", B, on_panic(throw))
+            subclass!("ns::Observer", C, thread_safe, on_panic(abort))
+        };
+        let policy = |id| config.get_subclass_panic_policy(&make_ident(id));
+        assert_eq!(policy("A"), SubclassPanicPolicy::Abort);
+        assert_eq!(policy("B"), SubclassPanicPolicy::Throw);
+        assert_eq!(policy("C"), SubclassPanicPolicy::Abort);
+        assert_eq!(
+            config.get_subclass_lock(&make_ident("C")),
+            Some(SubclassLock::Mutex)
+        );
+        let r: syn::Result<IncludeCppConfig> = syn::parse2(quote::quote! {
+            subclass!("ns::Observer", D, on_panic(ignore))
+        });
+        assert!(r.is_err());
+    }
+
+    #[test]
+    fn test_subclass_on_cpp_destroyed() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("ns::Foo")
+            subclass!("ns::Observer", A)
+            subclass!("ns::Observer", B, on_cpp_destroyed)
+        };
+        assert!(!config.subclass_wants_cpp_destroyed(&make_ident("A")));
+        assert!(config.subclass_wants_cpp_destroyed(&make_ident("B")));
+    }
+
+    #[test]
+    fn test_subclass_cpp_namespace() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("ns::Foo")
+            subclass!("ns::Observer", A)
+            subclass!("ns::Observer", B, cpp_namespace("app::detail"))
+        };
+        assert_eq!(config.get_subclass_cpp_namespace(&make_ident("A")), None);
+        assert_eq!(
+            config.get_subclass_cpp_namespace(&make_ident("B")),
+            Some("app::detail")
+        );
+        assert!(config.is_on_allowlist("app::detail::BCpp"));
+        assert!(!config.is_on_allowlist("BCpp"));
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("ns::Foo")
+            subclass!("ns::Observer", C, cpp_namespace("app::impl"))
+        };
+        assert_eq!(
+            config.get_subclass_cpp_namespace(&make_ident("C")),
+            Some("app::impl")
+        );
+        let r: syn::Result<IncludeCppConfig> = syn::parse2(quote::quote! {
+            subclass!("ns::Observer", D, cpp_namespace("app::super"))
+        });
+        assert!(r.is_err());
+    }
+
+    #[test]
+    fn test_implement_interface() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("ns::Foo")
+            subclass!("ns::Observer", A)
+            implement_interface!("ns::IFoo", MyTrait)
+        };
+        assert!(!config.is_interface_impl(&make_ident("A")));
+        assert!(config.is_interface_impl(&make_ident("MyTrait")));
+        assert!(config.is_on_allowlist("ns::IFoo"));
+        assert!(config.is_on_allowlist("MyTraitCpp"));
+        assert!(config.is_rust_type(&make_ident("MyTraitHolder")));
+    }
+
+    #[test]
+    fn test_thread_safe_requires_unsafe() {
+        let r: syn::Result<IncludeCppConfig> = syn::parse2(quote::quote! {
+            generate!("ns::Foo")
+            thread_safe!("ns::Foo", send)
+        });
+        assert!(r.is_err());
+    }
 }
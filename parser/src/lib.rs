@@ -17,11 +17,16 @@ pub mod file_locations;
 mod path;
 mod subclass_attrs;
 
-pub use config::{IncludeCppConfig, RustFun, Subclass, UnsafePolicy};
+pub use config::{
+    IncludeCppConfig, IncludeCppConfigBuilder, NamingPolicy, Nullability, RustFun, Subclass,
+    UnsafePolicy,
+};
 use file_locations::FileLocationStrategy;
 pub use path::RustPath;
 use proc_macro2::TokenStream as TokenStream2;
-pub use subclass_attrs::SubclassAttrs;
+pub use subclass_attrs::{
+    is_valid_subclass_cpp_namespace, SubclassAttrs, SubclassLock, SubclassPanicPolicy,
+};
 use syn::Result as ParseResult;
 use syn::{
     parse::{Parse, ParseStream},
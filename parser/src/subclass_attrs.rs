@@ -15,15 +15,104 @@
 use proc_macro2::Ident;
 use quote::ToTokens;
 use syn::Result as ParseResult;
+use syn::{ext::IdentExt, parse::Parser};
 use syn::{
     parse::{Parse, ParseStream},
     token::Comma,
 };
 
+/// The lock used to share a thread-safe Rust subclass between threads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SubclassLock {
+    /// `Arc<Mutex<T>>`
+    Mutex,
+    /// `Arc<RwLock<T>>`
+    RwLock,
+}
+
+impl SubclassLock {
+    /// Parses the optional arguments following `thread_safe`, i.e.
+    /// nothing, `(mutex)` or `(rwlock)`.
+    pub(crate) fn parse_args(input: ParseStream) -> ParseResult<Self> {
+        if !input.peek(syn::token::Paren) {
+            return Ok(SubclassLock::Mutex);
+        }
+        let args;
+        syn::parenthesized!(args in input);
+        let lock: Ident = args.parse()?;
+        match lock.to_string().as_str() {
+            "mutex" => Ok(SubclassLock::Mutex),
+            "rwlock" => Ok(SubclassLock::RwLock),
+            _ => Err(syn::Error::new_spanned(
+                lock.into_token_stream(),
+                "Expected mutex or rwlock",
+            )),
+        }
+    }
+}
+
+/// What to do if a Rust subclass method, called from C++, panics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SubclassPanicPolicy {
+    /// Print a message and abort the process.
+    #[default]
+    Abort,
+    /// Throw a C++ exception, unless the method is `noexcept`, in which
+    /// case we abort.
+    Throw,
+}
+
+impl SubclassPanicPolicy {
+    /// Parses the arguments following `on_panic`, i.e. `(abort)` or `(throw)`.
+    pub(crate) fn parse_args(input: ParseStream) -> ParseResult<Self> {
+        let args;
+        syn::parenthesized!(args in input);
+        let policy: Ident = args.parse()?;
+        match policy.to_string().as_str() {
+            "abort" => Ok(SubclassPanicPolicy::Abort),
+            "throw" => Ok(SubclassPanicPolicy::Throw),
+            _ => Err(syn::Error::new_spanned(
+                policy.into_token_stream(),
+                "Expected abort or throw",
+            )),
+        }
+    }
+}
+
+/// Whether the given string can be used as the C++ namespace of a
+/// subclass's C++ peer. Each part must be usable as the name of the Rust
+/// mod autocxx generates for it, which may be a raw identifier such as
+/// `r#impl`.
+pub fn is_valid_subclass_cpp_namespace(ns: &str) -> bool {
+    ns.split("::").all(|seg| {
+        Ident::parse_any.parse_str(seg).is_ok()
+            && !matches!(seg, "self" | "Self" | "super" | "crate" | "_")
+    })
+}
+
+/// Parses the arguments following `cpp_namespace`, e.g. `("app::detail")`.
+pub(crate) fn parse_cpp_namespace(input: ParseStream) -> ParseResult<String> {
+    let args;
+    syn::parenthesized!(args in input);
+    let ns: syn::LitStr = args.parse()?;
+    let value = ns.value();
+    if !is_valid_subclass_cpp_namespace(&value) {
+        return Err(syn::Error::new_spanned(
+            ns.into_token_stream(),
+            "Expected a C++ namespace such as \"app::detail\", each part of which can be the name of a Rust mod",
+        ));
+    }
+    Ok(value)
+}
+
 #[derive(Default)]
 pub struct SubclassAttrs {
     pub self_owned: bool,
-    pub superclass: Option<String>,
+    pub superclasses: Vec<String>,
+    pub thread_safe: Option<SubclassLock>,
+    pub on_panic: Option<SubclassPanicPolicy>,
+    pub on_cpp_destroyed: bool,
+    pub cpp_namespace: Option<String>,
 }
 
 impl Parse for SubclassAttrs {
@@ -33,24 +122,34 @@ impl Parse for SubclassAttrs {
         while id.is_some() {
             match id {
                 Some(id) if id == "self_owned" => me.self_owned = true,
+                Some(id) if id == "on_cpp_destroyed" => me.on_cpp_destroyed = true,
+                Some(id) if id == "thread_safe" => {
+                    me.thread_safe = Some(SubclassLock::parse_args(input)?)
+                }
+                Some(id) if id == "on_panic" => {
+                    me.on_panic = Some(SubclassPanicPolicy::parse_args(input)?)
+                }
+                Some(id) if id == "cpp_namespace" => {
+                    me.cpp_namespace = Some(parse_cpp_namespace(input)?)
+                }
                 Some(id) if id == "superclass" => {
                     let args;
                     syn::parenthesized!(args in input);
-                    let superclass: syn::LitStr = args.parse()?;
-                    if me.superclass.is_some() {
+                    let superclasses =
+                        args.parse_terminated::<syn::LitStr, Comma>(syn::parse::Parse::parse)?;
+                    if superclasses.is_empty() {
                         return Err(syn::Error::new_spanned(
                             id.into_token_stream(),
-                            "Expected single superclass specification",
+                            "Expected at least one superclass",
                         ));
                     }
-                    me.superclass = Some(superclass.value());
-                }
-                Some(id) => {
-                    return Err(syn::Error::new_spanned(
-                        id.into_token_stream(),
-                        "Expected self_owned or superclass",
-                    ))
+                    me.superclasses
+                        .extend(superclasses.into_iter().map(|sc| sc.value()));
                 }
+                Some(id) => return Err(syn::Error::new_spanned(
+                    id.into_token_stream(),
+                    "Expected self_owned, superclass, thread_safe, on_panic, on_cpp_destroyed or cpp_namespace",
+                )),
                 None => {}
             };
             let comma = input.parse::<Option<Comma>>()?;
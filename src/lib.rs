@@ -151,6 +151,11 @@ use autocxx_engine::IncludeCppEngine;
 ///     rsb --> l
 /// ```
 ///
+/// Tools which generate bindings without any Rust source containing
+/// `include_cpp!` can instead construct the configuration directly using
+/// `autocxx_engine::IncludeCppConfig::builder()`, and pass it to
+/// `autocxx_engine::IncludeCppEngine::new_from_config`.
+///
 /// # The [`include_cpp`] macro
 ///
 /// Within the braces of the `include_cpp!{...}` macro, you should provide
@@ -261,6 +266,28 @@ use autocxx_engine::IncludeCppEngine;
 ///
 /// Otherwise, your build will fail.
 ///
+/// POD types may contain bitfields. The storage for the bitfields is left
+/// as opaque bytes, and each bitfield instead gets a getter and setter method
+/// (`foo()` and `set_foo()`) which calls into C++, so the C++ compiler
+/// remains responsible for the layout.
+///
+/// A C++ union whose members are all POD becomes a Rust `union` when it's
+/// POD (either because you asked for it with [`generate_pod`] or because
+/// it's a field of such a type). As usual in Rust, writing a member is safe
+/// but reading one is `unsafe`. If any member isn't POD, the union is instead
+/// an opaque type with an `unsafe` getter for each member (which returns a
+/// reference for non-POD members) and a setter for each POD member.
+///
+/// Since POD types are copied around by value, it's vital that Rust and C++
+/// agree on their layout. autocxx therefore generates compile-time assertions
+/// of the size, alignment and field offsets of each POD type in the generated
+/// C++, and of the size and alignment in the generated Rust. (C++ can only
+/// check field offsets if the type has no base classes and all its fields are
+/// public.) If, for instance, your compiler flags for bindgen and for your C++
+/// build don't match, you'll get a build failure rather than memory corruption.
+/// The Rust field offsets are checked by a generated `#[test]`, like bindgen's
+/// own layout tests, so that autocxx doesn't need a recent Rust compiler.
+///
 /// This doesn't just make a difference to the generated code for the type;
 /// it also makes a difference to any functions which take or return that type.
 /// If there's a C++ function which takes a struct by value, but that struct
@@ -294,11 +321,43 @@ use autocxx_engine::IncludeCppEngine;
 /// (See also the discussion of [`safety`] - if you haven't specified
 /// an unsafety policy, _all_ C++ APIs require `unsafe` so the discussion is moot.)
 ///
+/// There's one exception. If your C++ headers follow a consistent convention
+/// about whether pointers may be null, you can use the [`unannotated_pointers`]
+/// directive to tell autocxx so, and we can do better:
+/// * A non-null pointer becomes a Rust reference (or `Pin<&mut T>`).
+/// * A nullable pointer becomes an `Option` of such a reference,
+///   with `None` representing a null pointer.
+///
+/// Neither of these requires `unsafe`. Clang's nullability qualifiers
+/// (`_Nullable` and `_Nonnull`) are not yet passed to autocxx by bindgen,
+/// so at present the directive applies to every pointer, except for
+/// `void*`, C strings such as `const char*`, and pointers to pointers,
+/// which remain raw pointers. A returned pointer also remains a raw pointer
+/// unless the function takes exactly one reference (or `Option` of a
+/// reference) from which Rust can infer the lifetime of the result.
+///
 /// If you're given a C++ object by pointer, and you want to interact with it,
 /// you'll need to figure out the guarantees attached to the C++ object - most
 /// notably its lifetime. To see some of the decision making process involved
 /// see the [Steam example](https://github.com/google/autocxx/tree/main/examples/steam-mini/src/main.rs).
 ///
+/// ### Out parameters
+///
+/// C++ APIs often return values through pointer or reference parameters,
+/// for example `bool Get(Foo* out)`. Use the [`out_param`] directive to
+/// have the generated C++ wrapper allocate that value, such that Rust
+/// receives it as a return value instead. A POD type is returned by value;
+/// anything else is returned as a [`cxx::UniquePtr`]. If the C++ function
+/// returns `void`, the Rust function returns just that value; if it
+/// returns `bool`, you get an `Option`; otherwise you get a tuple of the
+/// original return value and the out parameter. [`detect_out_params`]
+/// applies the same treatment based on parameter names.
+///
+/// The C++ wrapper default-constructs the value before calling the
+/// function, so this can't be done if the parameter's type is abstract or
+/// has no public default constructor. In that case, the function is not
+/// generated and its documentation explains why.
+///
 /// ### [`cxx::UniquePtr`]s
 ///
 /// We use [`cxx::UniquePtr`] in completely the normal way, but there are a few
@@ -367,6 +426,15 @@ use autocxx_engine::IncludeCppEngine;
 /// At present there is no way to do compile-time disablement of code
 /// (equivalent of `#ifdef`).
 ///
+/// If you name a macro in a `generate!` directive, autocxx asks the C++
+/// compiler to evaluate it, so macros involving casts, arithmetic or
+/// other macros work too. The resulting constant has the type the C++
+/// compiler gives the expression, and string macros become `&'static str`.
+/// Function-like macros are ignored. If any of the macros you name doesn't
+/// expand to a constant expression, none of them are evaluated this way,
+/// and you get only those macros which autocxx can evaluate itself
+/// (simple integer and string literals).
+///
 /// ## Integer types
 ///
 /// For C++ types with a defined size, just go ahead and use `u64`, `i32` etc.
@@ -377,7 +445,8 @@ use autocxx_engine::IncludeCppEngine;
 ///
 /// ## String constants
 ///
-/// Whether from a preprocessor symbol or from a C++ `char*` constant,
+/// String macros named in `generate!` directives become `&'static str`.
+/// Otherwise, whether from a preprocessor symbol or from a C++ `char*` constant,
 /// strings appear as `[u8]` with a null terminator. To get a Rust string,
 /// do this:
 ///
@@ -397,6 +466,20 @@ use autocxx_engine::IncludeCppEngine;
 /// autocxx can't handle multiple symbols with the same identifier, even
 /// if they're in different namespaces. This will be fixed in future.
 ///
+/// Deeply nested namespaces can be given a shorter name in Rust using
+/// [`namespace_alias`], and [`flatten_inline_namespaces`] omits namespaces
+/// such as `std::__1` which exist only for ABI versioning. These affect only
+/// the Rust mod hierarchy; the C++ side still uses the real namespaces.
+///
+/// ## Naming
+///
+/// By default, C++ names appear verbatim in Rust. The [`naming_policy`]
+/// directive instead converts method and function names to snake_case
+/// (`GetFrameCount` becomes `get_frame_count`) and, optionally, type names
+/// to UpperCamelCase. If an item's new name is already taken, whether by a
+/// function, a method or a type, that item is not generated, and a naming
+/// policy collision is reported in its documentation.
+///
 /// ## Overloads - and identifiers ending in digits
 ///
 /// C++ allows function overloads; Rust doesn't. `autocxx` follows the lead
@@ -410,6 +493,15 @@ use autocxx_engine::IncludeCppEngine;
 /// we can pass tuples of different parameter types into a single function
 /// implementation.
 ///
+/// ## Documentation
+///
+/// C++ comments are carried over as Rust documentation. Common Doxygen
+/// commands are translated into rustdoc conventions: `@param` becomes an
+/// "Arguments" section, `@return` a "Returns" section, `@code` a code block,
+/// and `\ref` or `@see` a link to the relevant generated item, if there is
+/// one in the same namespace. The documentation for each function also
+/// shows its original C++ declaration.
+///
 /// ## Forward declarations
 ///
 /// A type which is incomplete in the C++ headers (i.e. represented only by a forward
@@ -443,6 +535,8 @@ use autocxx_engine::IncludeCppEngine;
 /// See [`subclass::CppSubclass`] for information about how you do this.
 /// This is useful primarily if you want to listen out for messages broadcast
 /// using the C++ observer/listener pattern.
+/// If you just need to hand C++ an implementation of an abstract interface,
+/// [`implement_interface`] is lighter-weight.
 ///
 /// # Mixing manual and automated bindings
 ///
@@ -622,11 +716,121 @@ macro_rules! name {
 ///
 /// Generated C++ APIs which use raw pointers remain `unsafe`
 /// no matter what policy you choose.
+///
+/// Generated types are neither `Send` nor `Sync`. Once you've
+/// specified `safety!(unsafe)`, you can use [`thread_safe`] to
+/// declare that particular types are.
 #[macro_export]
 macro_rules! safety {
     ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
 }
 
+/// Specifies how to treat pointer parameters and return values.
+/// Clang nullability annotations (`_Nullable` or `_Nonnull`) are not
+/// yet visible to autocxx, so this applies to all such pointers, other
+/// than `void*`, C strings and pointers to pointers.
+/// By default, they remain raw pointers. Alternatively, you
+/// can specify:
+/// `unannotated_pointers!(nullable)`, such that they're represented
+/// as `Option<&T>` or `Option<Pin<&mut T>>`; or
+/// `unannotated_pointers!(nonnull)`, such that they're represented
+/// as `&T` or `Pin<&mut T>`.
+///
+/// This is a promise by *you, the human*, about the C++ APIs you're
+/// calling: if an API you declare `nonnull` returns a null pointer,
+/// badness will ensue.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! unannotated_pointers {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Treat a parameter of a C++ function as an out parameter, such that
+/// its value is returned from the Rust function rather than passed in.
+/// Specify the fully-qualified function or method name, then the name of
+/// the parameter: `out_param!("ns::Get", out)` or
+/// `out_param!("ns::Foo::Get", out)`. The parameter must be a non-const
+/// pointer or reference. See [include_cpp] for details of the Rust
+/// function signature which results.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! out_param {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Guess which parameters are out parameters, and treat them as if they
+/// had been listed in an [`out_param`] directive. A parameter is taken to
+/// be an out parameter if it's the last parameter, it's a non-const
+/// pointer or reference, and it's called `out` or its name starts with
+/// `out_` or ends with `_out`. Constructors and virtual functions are
+/// never affected.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! detect_out_params {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Make a C++ namespace, and any namespaces within it, appear in the
+/// generated Rust mod hierarchy under a different name.
+/// For example, `namespace_alias!("org::chromium::base", base)` makes
+/// `org::chromium::base::internal::Foo` available as `ffi::base::internal::Foo`.
+/// If several aliases apply to a namespace, the most specific is used.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! namespace_alias {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Omit namespaces which exist only for ABI versioning, such as `std::__1`
+/// or `std::__cxx11`, from the generated Rust mod hierarchy. Their contents
+/// appear in the enclosing namespace's mod instead. Only namespaces named
+/// `__N`, `__cxxN`, `__ndkN` or `_VN`, where `N` is a number, are omitted;
+/// other reserved names such as `std::__detail` are kept.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! flatten_inline_namespaces {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Adjust C++ names so that they follow Rust naming conventions.
+/// `naming_policy!(snake_case)` converts the names of methods and free
+/// functions to snake_case. Adding `upper_camel_case_types`, as in
+/// `naming_policy!(snake_case, upper_camel_case_types)`, also converts
+/// type names to UpperCamelCase.
+///
+/// If converting a name would make it clash with another item, that item
+/// isn't generated; instead, its documentation explains the clash.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! naming_policy {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Declare that a C++ type may be sent and/or shared between threads,
+/// for example `thread_safe!("ns::Foo", send, sync)`. The generated type
+/// then implements `Send` and/or `Sync`. autocxx can't check this, so it's
+/// only allowed alongside `safety!(unsafe)`: *you the human* are promising
+/// that the C++ type really is thread-safe.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! thread_safe {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
 /// Whether to avoid generating [`cxx::UniquePtr`] and [`cxx::Vector`]
 /// implementations. This is primarily useful for reducing test cases and
 /// shouldn't be used in normal operation.
@@ -657,6 +861,32 @@ macro_rules! subclass {
     ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
 }
 
+/// Implement a C++ interface using a Rust trait object, for example
+/// `implement_interface!("ns::IFoo", MyTrait)`. This generates a C++ class
+/// implementing each pure virtual method of `ns::IFoo` by calling the
+/// method of the same name on a `Box<dyn MyTrait>`, and a function
+/// `ffi::MyTrait_into_IFoo` which hands such a box to C++, returning a
+/// `cxx::UniquePtr<ffi::ns::IFoo>`. C++ then owns the trait object.
+///
+/// `MyTrait` must be declared in the same module as the [include_cpp]
+/// macro, and must have a method matching each pure virtual method of the
+/// interface. Those methods take the same parameters and return the same
+/// types as in the generated `IFoo_methods` trait. Methods which are
+/// non-const in C++ take `&mut self`. Unlike a true subclass (see
+/// [`subclass::CppSubclass`]), the Rust object doesn't need a peer holder,
+/// but also can't call the C++ object's other methods. Other virtual
+/// methods keep their C++ implementations. The interface must have a
+/// default constructor, and a virtual destructor, since C++ deletes the
+/// generated class through a `std::unique_ptr<IFoo>`; the generated C++
+/// fails to compile otherwise.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! implement_interface {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
 #[doc(hidden)]
 #[macro_export]
 macro_rules! usage {
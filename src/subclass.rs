@@ -16,12 +16,19 @@
 // limitations under the License.
 
 use std::{
+    any::Any,
     cell::RefCell,
+    panic::AssertUnwindSafe,
     pin::Pin,
-    rc::{Rc, Weak},
+    rc::Rc,
+    sync::{Arc, Mutex, RwLock, TryLockError},
 };
 
-use cxx::{memory::UniquePtrTarget, UniquePtr};
+use cxx::{
+    memory::{SharedPtrTarget, UniquePtrTarget},
+    SharedPtr, UniquePtr,
+};
+use once_cell::sync::Lazy;
 
 /// Deprecated - use [`subclass`] instead.
 #[deprecated]
@@ -56,6 +63,11 @@ pub use autocxx_macro::subclass as is_subclass;
 ///   [`CppSubclass`] for the struct, so it's
 ///   generally easier to use the former option.
 ///
+/// A subclass may have several superclasses: use
+/// `#[subclass(superclass("Observer", "Delegate"))]`, or several
+/// `subclass!` directives naming the same subclass. See [`CppSubclass`]
+/// for details.
+///
 /// See [`CppSubclass`] for information about the
 /// multiple steps you need to take to be able to make Rust
 /// subclasses of a C++ class.
@@ -69,33 +81,191 @@ pub use autocxx_macro::subclass;
 /// ```
 pub mod prelude {
     pub use super::{
-        is_subclass, subclass, CppPeerConstructor, CppSubclass, CppSubclassDefault,
-        CppSubclassRustPeerHolder, CppSubclassSelfOwned, CppSubclassSelfOwnedDefault,
+        is_subclass, subclass, CppPeerConstructor, CppPeerDestroyed, CppSubclass,
+        CppSubclassDefault, CppSubclassRustPeerHolder, CppSubclassSelfOwned,
+        CppSubclassSelfOwnedDefault, SingleThreaded, ThreadSafeMutex, ThreadSafeRwLock,
     };
 }
 
 #[doc(hidden)]
-pub trait CppSubclassCppPeer: UniquePtrTarget {
+pub trait CppSubclassCppPeer: UniquePtrTarget + SharedPtrTarget + Sized {
     fn relinquish_ownership(&self);
+    fn into_shared(peer: UniquePtr<Self>) -> SharedPtr<Self>;
+}
+
+/// The way in which a Rust subclass object is shared between Rust code
+/// and its C++ peer. Normally this is [`SingleThreaded`], but subclasses
+/// declared with `#[subclass(thread_safe)]` use [`ThreadSafeMutex`] or
+/// [`ThreadSafeRwLock`] so that C++ can call them from any thread.
+pub trait CppSubclassRustPeerStorage<T> {
+    /// A strong reference to the subclass, e.g. `Rc<RefCell<T>>`.
+    type Strong: Clone;
+    /// A weak reference to the subclass.
+    type Weak;
+    fn new_strong(obj: T) -> Self::Strong;
+    fn downgrade(strong: &Self::Strong) -> Self::Weak;
+    fn upgrade(weak: &Self::Weak) -> Option<Self::Strong>;
+    /// Obtains mutable access to the subclass, panicking if that's not
+    /// possible.
+    fn with_mut<R>(strong: &Self::Strong, f: impl FnOnce(&mut T) -> R) -> R;
+    /// Obtains mutable access to the subclass, returning `None` if that's not
+    /// possible: for [`SingleThreaded`] storage, if it's already borrowed;
+    /// for thread-safe storage, if its lock was poisoned or is held by a
+    /// subclass method further up this thread's stack. Thread-safe storage
+    /// waits for the lock rather than giving up if another thread holds it.
+    fn try_with_mut<R>(strong: &Self::Strong, f: impl FnOnce(&mut T) -> R) -> Option<R>;
+}
+
+thread_local! {
+    /// The addresses of the locks of thread-safe subclasses which this
+    /// thread currently holds, so that we can avoid deadlocking if a C++
+    /// peer is destroyed from within one of its own subclass's methods.
+    static LOCKED_BY_THIS_THREAD: RefCell<Vec<usize>> = RefCell::new(Vec::new());
+}
+
+/// Records that this thread holds the lock of a thread-safe subclass,
+/// until dropped. Used by generated code when calling subclass methods.
+#[doc(hidden)]
+pub struct SubclassLockedByThisThread(usize);
+
+impl SubclassLockedByThisThread {
+    pub fn new<L>(lock: &L) -> Self {
+        let addr = lock as *const L as usize;
+        LOCKED_BY_THIS_THREAD.with(|locked| locked.borrow_mut().push(addr));
+        Self(addr)
+    }
+
+    fn is_locked<L>(lock: &L) -> bool {
+        let addr = lock as *const L as usize;
+        LOCKED_BY_THIS_THREAD.with(|locked| locked.borrow().contains(&addr))
+    }
+}
+
+impl Drop for SubclassLockedByThisThread {
+    fn drop(&mut self) {
+        LOCKED_BY_THIS_THREAD.with(|locked| {
+            let mut locked = locked.borrow_mut();
+            if let Some(pos) = locked.iter().rposition(|addr| *addr == self.0) {
+                locked.remove(pos);
+            }
+        });
+    }
+}
+
+/// Storage for a subclass which can only be used on a single thread:
+/// `Rc<RefCell<T>>`.
+pub struct SingleThreaded;
+
+impl<T> CppSubclassRustPeerStorage<T> for SingleThreaded {
+    type Strong = Rc<RefCell<T>>;
+    type Weak = std::rc::Weak<RefCell<T>>;
+    fn new_strong(obj: T) -> Self::Strong {
+        Rc::new(RefCell::new(obj))
+    }
+    fn downgrade(strong: &Self::Strong) -> Self::Weak {
+        Rc::downgrade(strong)
+    }
+    fn upgrade(weak: &Self::Weak) -> Option<Self::Strong> {
+        weak.upgrade()
+    }
+    fn with_mut<R>(strong: &Self::Strong, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut strong.borrow_mut())
+    }
+    fn try_with_mut<R>(strong: &Self::Strong, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        strong.try_borrow_mut().ok().map(|mut obj| f(&mut obj))
+    }
+}
+
+/// Storage for a subclass which may be called from any thread:
+/// `Arc<Mutex<T>>`. The subclass must be `Send`.
+pub struct ThreadSafeMutex;
+
+impl<T: Send> CppSubclassRustPeerStorage<T> for ThreadSafeMutex {
+    type Strong = Arc<Mutex<T>>;
+    type Weak = std::sync::Weak<Mutex<T>>;
+    fn new_strong(obj: T) -> Self::Strong {
+        Arc::new(Mutex::new(obj))
+    }
+    fn downgrade(strong: &Self::Strong) -> Self::Weak {
+        Arc::downgrade(strong)
+    }
+    fn upgrade(weak: &Self::Weak) -> Option<Self::Strong> {
+        weak.upgrade()
+    }
+    fn with_mut<R>(strong: &Self::Strong, f: impl FnOnce(&mut T) -> R) -> R {
+        let mut obj = strong.lock().expect("Rust subclass mutex poisoned");
+        let _locked = SubclassLockedByThisThread::new(strong.as_ref());
+        f(&mut obj)
+    }
+    fn try_with_mut<R>(strong: &Self::Strong, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        let mut obj = match strong.try_lock() {
+            Ok(obj) => obj,
+            Err(TryLockError::WouldBlock)
+                if !SubclassLockedByThisThread::is_locked(strong.as_ref()) =>
+            {
+                strong.lock().ok()?
+            }
+            Err(_) => return None,
+        };
+        let _locked = SubclassLockedByThisThread::new(strong.as_ref());
+        Some(f(&mut obj))
+    }
+}
+
+/// Storage for a subclass which may be called from any thread:
+/// `Arc<RwLock<T>>`. Calls to const C++ methods take a read lock,
+/// so may run concurrently. The subclass must be `Send` and `Sync`.
+pub struct ThreadSafeRwLock;
+
+impl<T: Send + Sync> CppSubclassRustPeerStorage<T> for ThreadSafeRwLock {
+    type Strong = Arc<RwLock<T>>;
+    type Weak = std::sync::Weak<RwLock<T>>;
+    fn new_strong(obj: T) -> Self::Strong {
+        Arc::new(RwLock::new(obj))
+    }
+    fn downgrade(strong: &Self::Strong) -> Self::Weak {
+        Arc::downgrade(strong)
+    }
+    fn upgrade(weak: &Self::Weak) -> Option<Self::Strong> {
+        weak.upgrade()
+    }
+    fn with_mut<R>(strong: &Self::Strong, f: impl FnOnce(&mut T) -> R) -> R {
+        let mut obj = strong.write().expect("Rust subclass lock poisoned");
+        let _locked = SubclassLockedByThisThread::new(strong.as_ref());
+        f(&mut obj)
+    }
+    fn try_with_mut<R>(strong: &Self::Strong, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        let mut obj = match strong.try_write() {
+            Ok(obj) => obj,
+            Err(TryLockError::WouldBlock)
+                if !SubclassLockedByThisThread::is_locked(strong.as_ref()) =>
+            {
+                strong.write().ok()?
+            }
+            Err(_) => return None,
+        };
+        let _locked = SubclassLockedByThisThread::new(strong.as_ref());
+        Some(f(&mut obj))
+    }
 }
 
 #[doc(hidden)]
-pub enum CppSubclassRustPeerHolder<T> {
-    Owned(Rc<RefCell<T>>),
-    Unowned(Weak<RefCell<T>>),
+pub enum CppSubclassRustPeerHolder<T, S: CppSubclassRustPeerStorage<T> = SingleThreaded> {
+    Owned(S::Strong),
+    Unowned(S::Weak),
 }
 
-impl<T> CppSubclassRustPeerHolder<T> {
-    pub fn get(&self) -> Option<Rc<RefCell<T>>> {
+impl<T, S: CppSubclassRustPeerStorage<T>> CppSubclassRustPeerHolder<T, S> {
+    pub fn get(&self) -> Option<S::Strong> {
         match self {
             CppSubclassRustPeerHolder::Owned(strong) => Some(strong.clone()),
-            CppSubclassRustPeerHolder::Unowned(weak) => weak.upgrade(),
+            CppSubclassRustPeerHolder::Unowned(weak) => S::upgrade(weak),
         }
     }
     pub fn relinquish_ownership(self) -> Self {
         match self {
             CppSubclassRustPeerHolder::Owned(strong) => {
-                CppSubclassRustPeerHolder::Unowned(Rc::downgrade(&strong))
+                CppSubclassRustPeerHolder::Unowned(S::downgrade(&strong))
             }
             _ => self,
         }
@@ -109,6 +279,12 @@ pub enum CppSubclassCppPeerHolder<CppPeer: CppSubclassCppPeer> {
     Unowned(*mut CppPeer),
 }
 
+// Safety: the holder either owns the peer or acts as a mutable reference
+// to it, so it may be sent or shared exactly when the peer may be. C++
+// peers are only `Send` or `Sync` for `#[subclass(thread_safe)]` subclasses.
+unsafe impl<CppPeer: CppSubclassCppPeer + Send> Send for CppSubclassCppPeerHolder<CppPeer> {}
+unsafe impl<CppPeer: CppSubclassCppPeer + Sync> Sync for CppSubclassCppPeerHolder<CppPeer> {}
+
 impl<CppPeer: CppSubclassCppPeer> Default for CppSubclassCppPeerHolder<CppPeer> {
     fn default() -> Self {
         CppSubclassCppPeerHolder::Empty
@@ -142,28 +318,37 @@ impl<CppPeer: CppSubclassCppPeer> CppSubclassCppPeerHolder<CppPeer> {
     }
 }
 
-fn make_owning_peer<CppPeer, PeerConstructor, Subclass, PeerBoxer>(
+fn make_owning_peer<CppPeer, PeerConstructor, Subclass, PeerBoxer, S>(
     me: Subclass,
     peer_constructor: PeerConstructor,
     peer_boxer: PeerBoxer,
-) -> Rc<RefCell<Subclass>>
+) -> S::Strong
 where
     CppPeer: CppSubclassCppPeer,
-    Subclass: CppSubclass<CppPeer>,
+    Subclass: CppSubclass<CppPeer, S>,
+    S: CppSubclassRustPeerStorage<Subclass>,
     PeerConstructor:
-        FnOnce(&mut Subclass, CppSubclassRustPeerHolder<Subclass>) -> UniquePtr<CppPeer>,
-    PeerBoxer: FnOnce(Rc<RefCell<Subclass>>) -> CppSubclassRustPeerHolder<Subclass>,
+        FnOnce(&mut Subclass, CppSubclassRustPeerHolder<Subclass, S>) -> UniquePtr<CppPeer>,
+    PeerBoxer: FnOnce(&S::Strong) -> CppSubclassRustPeerHolder<Subclass, S>,
 {
-    let me = Rc::new(RefCell::new(me));
-    let holder = peer_boxer(me.clone());
-    let cpp_side = peer_constructor(&mut me.as_ref().borrow_mut(), holder);
-    me.as_ref()
-        .borrow_mut()
-        .peer_holder_mut()
-        .set_owned(cpp_side);
+    let me = S::new_strong(me);
+    let holder = peer_boxer(&me);
+    S::with_mut(&me, |obj| {
+        let cpp_side = peer_constructor(obj, holder);
+        obj.peer_holder_mut().set_owned(cpp_side);
+    });
     me
 }
 
+/// A trait to be implemented by a subclass which wants to know when its C++
+/// peer is destroyed. Declare the subclass using
+/// `#[subclass(on_cpp_destroyed)]` to have [`CppSubclass::on_cpp_destroyed`]
+/// call this.
+pub trait CppPeerDestroyed {
+    /// See [`CppSubclass::on_cpp_destroyed`].
+    fn on_cpp_destroyed(&mut self);
+}
+
 /// A trait to be implemented by a subclass which knows how to construct
 /// its C++ peer object. Specifically, the implementation here will
 /// arrange to call one or other of the `make_unique` methods to be
@@ -172,15 +357,20 @@ where
 /// automatically for you. If there are multiple constructors, or
 /// a single constructor which takes parameters, you'll need to implement
 /// this trait for your subclass in order to call the correct
-/// constructor.
-pub trait CppPeerConstructor<CppPeer: CppSubclassCppPeer>: Sized {
+/// constructor. The `_with` variants of the [`CppSubclass`] constructors,
+/// along with the generated `with_base_...` functions, can be used to pick
+/// a different constructor for a particular instance.
+pub trait CppPeerConstructor<CppPeer: CppSubclassCppPeer, S = SingleThreaded>: Sized
+where
+    S: CppSubclassRustPeerStorage<Self>,
+{
     /// Create the C++ peer. This method will be automatically generated
     /// for you *except* in cases where the superclass has multiple constructors,
     /// or its only constructor takes parameters. In such a case you'll need
     /// to implement this by calling a `make_unique` method on the
     /// `<my subclass name>Cpp` type, passing `peer_holder` as the first
     /// argument.
-    fn make_peer(&mut self, peer_holder: CppSubclassRustPeerHolder<Self>) -> UniquePtr<CppPeer>;
+    fn make_peer(&mut self, peer_holder: CppSubclassRustPeerHolder<Self, S>) -> UniquePtr<CppPeer>;
 }
 
 /// A subclass of a C++ type.
@@ -203,7 +393,13 @@ pub trait CppPeerConstructor<CppPeer: CppSubclassCppPeer>: Sized {
 ///   but only if autocxx determines that there are multiple possible superclass
 ///   constructors so you need to call one explicitly (or if there's a single
 ///   non-trivial superclass constructor.) autocxx will implemente this trait
-///   for you if there's no ambiguity.
+///   for you if there's no ambiguity. You can also pick the superclass
+///   constructor for a particular instance, by passing one of the
+///   generated `with_base_...` functions to [`CppSubclass::new_rust_owned_with`]
+///   or similar. There's one such function per superclass constructor, named
+///   after its parameter types, so for a superclass with constructors
+///   `Observer()` and `Observer(uint32_t)` you'd have
+///   `MyObserver::with_base_default()` and `MyObserver::with_base_u32(n)`.
 ///
 /// # How to access your Rust structure from outside
 ///
@@ -235,6 +431,15 @@ pub trait CppPeerConstructor<CppPeer: CppSubclassCppPeer>: Sized {
 ///    [`UniquePtr`] held elsewhere in C++. That C++ peer then owns
 ///    this Rust-side object via a strong [`Rc`] reference. This is the
 ///    ownership relationship set up by [`CppSubclass::new_cpp_owned`].
+///    [`CppSubclass::new_shared`] is the same, except that the C++ peer is
+///    owned by a [`SharedPtr`] (a C++ `std::shared_ptr`) instead, so the
+///    Rust-side object is destroyed when the last C++ or Rust reference to
+///    the peer goes away. To pass it to a C++ API which takes a
+///    `std::shared_ptr` (or, by way of [`SharedPtr::downgrade`], a
+///    `std::weak_ptr`) to a superclass, use the `As_<superclass>_shared`
+///    function generated on the C++ peer type. Either way, implement
+///    [`CppSubclass::on_cpp_destroyed`] to find out when C++ destroys the
+///    peer.
 /// 2. The object pair is owned by Rust. Specifically, by a strong
 ///    [`Rc`] reference to this Rust-side object. In turn, the Rust-side object
 ///    owns the C++-side peer via a [`UniquePtr`]. This is what's set up by
@@ -262,17 +467,78 @@ pub trait CppPeerConstructor<CppPeer: CppSubclassCppPeer>: Sized {
 ///   with existing C++ interfaces. If you need this, indicate support on
 ///   [this issue](https://github.com/google/autocxx/issues/622).
 ///
-/// * *Thread safety*. The subclass object is not thread-safe and shouldn't
-///   be passed to different threads in C++. A future version of this code
-///   will give the option to use `Arc` and `Mutex` internally rather than
-///   `Rc` and `RefCell`, solving this problem.
+/// * *Thread safety*. By default, the subclass object is not thread-safe and
+///   shouldn't be passed to different threads in C++. See below.
 ///
 /// * *Protected methods.* We don't do anything clever here - they're public.
 ///
 /// * *Non-trivial class hierarchies*. We don't yet consider virtual methods
 ///   on base classes of base classes. This is a temporary limitation,
 ///   [see this issue](https://github.com/google/autocxx/issues/610).
-pub trait CppSubclass<CppPeer: CppSubclassCppPeer>: CppPeerConstructor<CppPeer> {
+///
+/// # Multiple superclasses
+///
+/// If you specify several superclasses, the C++ peer inherits from all
+/// of them. Implement the `_methods` trait for each superclass, and use
+/// the `As_<superclass>` and `As_<superclass>_mut` methods generated on
+/// your struct (or [`AsRef`]) to obtain a reference to each superclass. Only the
+/// first superclass's constructors are used: the others must have a default
+/// constructor. Virtual methods with the same name in different superclasses
+/// aren't yet supported.
+///
+/// # Thread-safe subclasses
+///
+/// If C++ may call your subclass from other threads, declare it using
+/// `#[subclass(thread_safe)]` (and, if you're also using a `subclass!`
+/// directive, add `thread_safe` to that too: `subclass!("Observer", MyObserver,
+/// thread_safe)`). The subclass is then held in an `Arc<Mutex<T>>` instead
+/// of an `Rc<RefCell<T>>`, so [`CppSubclass::new_rust_owned`] returns one of
+/// those. Use `thread_safe(rwlock)` to use an `Arc<RwLock<T>>`, in which case
+/// calls to const C++ methods take only a read lock. The subclass must be
+/// `Send` (and, for `rwlock`, `Sync`), which is checked at compile time.
+/// The C++ peer is marked `Send` and `Sync`, so you're promising that it's
+/// OK to call the superclass's methods from any thread; autocxx can't check
+/// that, so thread-safe subclasses are only allowed alongside
+/// `safety!(unsafe)`.
+///
+/// Re-entrant calls into a thread-safe subclass will deadlock rather than
+/// panic. The exception is destroying a C++ peer from within one of its
+/// subclass's methods: see [`CppSubclass::on_cpp_destroyed`].
+///
+/// # Panics
+///
+/// A panic mustn't unwind from Rust into C++, so if one of your subclass
+/// methods panics when called from C++, by default we print a message and
+/// abort the process. Declare your subclass using `#[subclass(on_panic(throw))]`
+/// (or add `on_panic(throw)` to the `subclass!` directive) to instead throw
+/// a C++ exception, which will be a `rust::Error`. autocxx can't tell
+/// whether a C++ method is `noexcept`, so if your subclass overrides any
+/// `noexcept` methods, a panic in one of them under `on_panic(throw)` will
+/// reach `std::terminate`; stick with the default (`on_panic(abort)`) for
+/// such classes. Either way, any hook installed using
+/// [`set_subclass_panic_hook`] is called first, which is useful to report
+/// the panic to a crash reporter.
+///
+/// # C++ namespaces
+///
+/// The C++ peer class, `MyObserverCpp`, and its supporting functions are
+/// generated in a C++ namespace derived from your package name and the
+/// Rust module path of the file containing the `include_cpp!`, such as
+/// `my_crate::observers` for `src/observers.rs`, so that subclasses of the
+/// same name in different crates or modules don't collide when linked into
+/// one binary. (If autocxx isn't run by cargo, they're generated in the
+/// root namespace.) Declare your subclass using
+/// `#[subclass(cpp_namespace("app::detail"))]` (or add
+/// `cpp_namespace("app::detail")` to the `subclass!` directive) to choose
+/// the namespace yourself. Parts of the namespace which are Rust keywords,
+/// such as `impl`, appear in Rust as raw identifiers (`r#impl`). The peer
+/// remains available as `ffi::MyObserverCpp`, as well as within the `ffi`
+/// mod for the namespace.
+pub trait CppSubclass<CppPeer: CppSubclassCppPeer, S = SingleThreaded>:
+    CppPeerConstructor<CppPeer, S>
+where
+    S: CppSubclassRustPeerStorage<Self>,
+{
     /// Return the field which holds the C++ peer object. This is normally
     /// implemented by the #[`is_subclass`] macro, but you're welcome to
     /// implement it yourself if you prefer.
@@ -295,34 +561,98 @@ pub trait CppSubclass<CppPeer: CppSubclassCppPeer>: CppPeerConstructor<CppPeer>
         self.peer_holder_mut().pin_mut()
     }
 
+    /// Called when the C++ peer is destroyed while this object still
+    /// exists, typically because C++ deleted an object created using
+    /// [`CppSubclass::new_cpp_owned`]. It's called from the C++ destructor,
+    /// before C++ releases its reference to this object, so it's a good place
+    /// to unregister from anything which refers to it. The C++ peer is
+    /// already partly destroyed so mustn't be used here; after this returns,
+    /// [`CppSubclass::peer`] panics. The default does nothing.
+    ///
+    /// This isn't called if a [`SingleThreaded`] subclass is already borrowed
+    /// when the C++ peer is destroyed, nor when dropping a Rust-owned subclass
+    /// destroys its C++ peer. A thread-safe subclass waits until any other
+    /// thread has finished with it, but this isn't called if the peer is
+    /// destroyed from within one of the subclass's own methods on the same
+    /// thread. If your own code locks a thread-safe subclass directly,
+    /// rather than through its methods, it must release the lock before
+    /// anything it does destroys the C++ peer, or it will deadlock.
+    ///
+    /// This is only called if the subclass is declared with
+    /// `subclass!("Superclass", Subclass, on_cpp_destroyed)`; otherwise the
+    /// C++ peer doesn't tell the Rust side when it's destroyed.
+    /// If you're using the [`subclass`] macro, which implements this trait
+    /// for you, use `#[subclass(on_cpp_destroyed)]` and implement
+    /// [`CppPeerDestroyed`] instead.
+    fn on_cpp_destroyed(&mut self) {}
+
     /// Creates a new instance of this subclass. This instance is owned by the
     /// returned [`cxx::UniquePtr`] and thus would typically be returned immediately
     /// to C++ such that it can be owned on the C++ side.
     fn new_cpp_owned(me: Self) -> UniquePtr<CppPeer> {
-        let me = Rc::new(RefCell::new(me));
+        Self::new_cpp_owned_with(me, Self::make_peer)
+    }
+
+    /// Like [`CppSubclass::new_cpp_owned`], but uses the given function to
+    /// construct the C++ peer. This is typically one of the `with_base_...`
+    /// functions generated for each constructor of the superclass, for example
+    /// `MySubclass::new_cpp_owned_with(me, MySubclass::with_base_u32(42))`.
+    fn new_cpp_owned_with<F>(me: Self, peer_constructor: F) -> UniquePtr<CppPeer>
+    where
+        F: FnOnce(&mut Self, CppSubclassRustPeerHolder<Self, S>) -> UniquePtr<CppPeer>,
+    {
+        let me = S::new_strong(me);
         let holder = CppSubclassRustPeerHolder::Owned(me.clone());
-        let mut borrowed = me.as_ref().borrow_mut();
-        let mut cpp_side = borrowed.make_peer(holder);
-        borrowed.peer_holder_mut().set_unowned(&mut cpp_side);
-        cpp_side
+        S::with_mut(&me, |obj| {
+            let mut cpp_side = peer_constructor(obj, holder);
+            obj.peer_holder_mut().set_unowned(&mut cpp_side);
+            cpp_side
+        })
+    }
+
+    /// Creates a new instance of this subclass. This instance is owned by the
+    /// returned [`cxx::SharedPtr`], and by any copies of it, whether in Rust
+    /// or C++. It's deleted when the last of them goes away.
+    fn new_shared(me: Self) -> SharedPtr<CppPeer> {
+        Self::new_shared_with(me, Self::make_peer)
+    }
+
+    /// Like [`CppSubclass::new_shared`], but uses the given function to
+    /// construct the C++ peer. See [`CppSubclass::new_cpp_owned_with`].
+    fn new_shared_with<F>(me: Self, peer_constructor: F) -> SharedPtr<CppPeer>
+    where
+        F: FnOnce(&mut Self, CppSubclassRustPeerHolder<Self, S>) -> UniquePtr<CppPeer>,
+    {
+        CppPeer::into_shared(Self::new_cpp_owned_with(me, peer_constructor))
     }
 
     /// Creates a new instance of this subclass. This instance is not owned
     /// by C++, and therefore will be deleted when it goes out of scope in
     /// Rust.
-    fn new_rust_owned(me: Self) -> Rc<RefCell<Self>> {
-        make_owning_peer(
-            me,
-            |obj, holder| obj.make_peer(holder),
-            |me| CppSubclassRustPeerHolder::Unowned(Rc::downgrade(&me)),
-        )
+    fn new_rust_owned(me: Self) -> S::Strong {
+        Self::new_rust_owned_with(me, Self::make_peer)
+    }
+
+    /// Like [`CppSubclass::new_rust_owned`], but uses the given function to
+    /// construct the C++ peer. See [`CppSubclass::new_cpp_owned_with`].
+    fn new_rust_owned_with<F>(me: Self, peer_constructor: F) -> S::Strong
+    where
+        F: FnOnce(&mut Self, CppSubclassRustPeerHolder<Self, S>) -> UniquePtr<CppPeer>,
+    {
+        make_owning_peer(me, peer_constructor, |me| {
+            CppSubclassRustPeerHolder::Unowned(S::downgrade(me))
+        })
     }
 }
 
 /// Trait to be implemented by subclasses which are self-owned, i.e. not owned
 /// externally by either Rust or C++ code, and thus need the ability to delete
 /// themselves when some virtual function is called.
-pub trait CppSubclassSelfOwned<CppPeer: CppSubclassCppPeer>: CppSubclass<CppPeer> {
+pub trait CppSubclassSelfOwned<CppPeer: CppSubclassCppPeer, S = SingleThreaded>:
+    CppSubclass<CppPeer, S>
+where
+    S: CppSubclassRustPeerStorage<Self>,
+{
     /// Creates a new instance of this subclass which owns itself.
     /// This is useful
     /// for observers (etc.) which self-register to listen to events.
@@ -330,12 +660,19 @@ pub trait CppSubclassSelfOwned<CppPeer: CppSubclassCppPeer>: CppSubclass<CppPeer
     /// use [`CppSubclassSelfOwned::delete_self`].
     /// The return value may be useful to register this, etc. but can ultimately
     /// be discarded without destroying this object.
-    fn new_self_owned(me: Self) -> Rc<RefCell<Self>> {
-        make_owning_peer(
-            me,
-            |obj, holder| obj.make_peer(holder),
-            CppSubclassRustPeerHolder::Owned,
-        )
+    fn new_self_owned(me: Self) -> S::Strong {
+        Self::new_self_owned_with(me, Self::make_peer)
+    }
+
+    /// Like [`CppSubclassSelfOwned::new_self_owned`], but uses the given function to
+    /// construct the C++ peer. See [`CppSubclass::new_cpp_owned_with`].
+    fn new_self_owned_with<F>(me: Self, peer_constructor: F) -> S::Strong
+    where
+        F: FnOnce(&mut Self, CppSubclassRustPeerHolder<Self, S>) -> UniquePtr<CppPeer>,
+    {
+        make_owning_peer(me, peer_constructor, |me| {
+            CppSubclassRustPeerHolder::Owned(me.clone())
+        })
     }
 
     /// Relinquishes ownership from the C++ side. If there are no outstanding
@@ -347,46 +684,158 @@ pub trait CppSubclassSelfOwned<CppPeer: CppSubclassCppPeer>: CppSubclass<CppPeer
 }
 
 /// Provides default constructors for subclasses which implement `Default`.
-pub trait CppSubclassDefault<CppPeer: CppSubclassCppPeer>: CppSubclass<CppPeer> + Default {
+pub trait CppSubclassDefault<CppPeer: CppSubclassCppPeer, S = SingleThreaded>:
+    CppSubclass<CppPeer, S> + Default
+where
+    S: CppSubclassRustPeerStorage<Self>,
+{
     /// Create a Rust-owned instance of this subclass, initializing with default values. See
     /// [`CppSubclass`] for more details of the ownership models available.
-    fn default_rust_owned() -> Rc<RefCell<Self>>;
+    fn default_rust_owned() -> S::Strong;
 
     /// Create a C++-owned instance of this subclass, initializing with default values. See
     /// [`CppSubclass`] for more details of the ownership models available.
     fn default_cpp_owned() -> UniquePtr<CppPeer>;
+
+    /// Create a shared instance of this subclass, initializing with default values. See
+    /// [`CppSubclass`] for more details of the ownership models available.
+    fn default_shared() -> SharedPtr<CppPeer>;
 }
 
-impl<T, CppPeer> CppSubclassDefault<CppPeer> for T
+impl<T, CppPeer, S> CppSubclassDefault<CppPeer, S> for T
 where
-    T: CppSubclass<CppPeer> + Default,
+    T: CppSubclass<CppPeer, S> + Default,
     CppPeer: CppSubclassCppPeer,
+    S: CppSubclassRustPeerStorage<T>,
 {
-    fn default_rust_owned() -> Rc<RefCell<Self>> {
+    fn default_rust_owned() -> S::Strong {
         Self::new_rust_owned(Self::default())
     }
 
     fn default_cpp_owned() -> UniquePtr<CppPeer> {
         Self::new_cpp_owned(Self::default())
     }
+
+    fn default_shared() -> SharedPtr<CppPeer> {
+        Self::new_shared(Self::default())
+    }
 }
 
 /// Provides default constructors for subclasses which implement `Default`
 /// and are self-owning.
-pub trait CppSubclassSelfOwnedDefault<CppPeer: CppSubclassCppPeer>:
-    CppSubclassSelfOwned<CppPeer> + Default
+pub trait CppSubclassSelfOwnedDefault<CppPeer: CppSubclassCppPeer, S = SingleThreaded>:
+    CppSubclassSelfOwned<CppPeer, S> + Default
+where
+    S: CppSubclassRustPeerStorage<Self>,
 {
     /// Create a self-owned instance of this subclass, initializing with default values. See
     /// [`CppSubclass`] for more details of the ownership models available.
-    fn default_self_owned() -> Rc<RefCell<Self>>;
+    fn default_self_owned() -> S::Strong;
 }
 
-impl<T, CppPeer> CppSubclassSelfOwnedDefault<CppPeer> for T
+impl<T, CppPeer, S> CppSubclassSelfOwnedDefault<CppPeer, S> for T
 where
-    T: CppSubclassSelfOwned<CppPeer> + Default,
+    T: CppSubclassSelfOwned<CppPeer, S> + Default,
     CppPeer: CppSubclassCppPeer,
+    S: CppSubclassRustPeerStorage<T>,
 {
-    fn default_self_owned() -> Rc<RefCell<Self>> {
+    fn default_self_owned() -> S::Strong {
         Self::new_self_owned(Self::default())
     }
 }
+
+/// Details of a panic in a Rust subclass method called from C++.
+pub struct SubclassPanicInfo<'a> {
+    /// The name of the Rust subclass.
+    pub subclass: &'a str,
+    /// The name of the method which panicked.
+    pub method: &'a str,
+    /// The payload of the panic, as passed to [`std::panic::panic_any`]
+    /// or similar.
+    pub payload: &'a (dyn Any + Send),
+}
+
+impl<'a> SubclassPanicInfo<'a> {
+    /// The panic message, if the payload is a string.
+    pub fn message(&self) -> Option<&str> {
+        self.payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| self.payload.downcast_ref::<String>().map(String::as_str))
+    }
+}
+
+type SubclassPanicHook = Box<dyn Fn(&SubclassPanicInfo) + Send + Sync>;
+
+static SUBCLASS_PANIC_HOOK: Lazy<RwLock<Option<SubclassPanicHook>>> =
+    Lazy::new(|| RwLock::new(None));
+
+/// Installs a hook to be called whenever a Rust subclass method, called
+/// from C++, panics. This replaces any previous hook. The hook is called
+/// before we abort or throw a C++ exception, so it's a good place to
+/// notify a crash reporter. See [`CppSubclass`].
+pub fn set_subclass_panic_hook<F>(hook: F)
+where
+    F: Fn(&SubclassPanicInfo) + Send + Sync + 'static,
+{
+    *SUBCLASS_PANIC_HOOK
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(Box::new(hook));
+}
+
+/// Calls any subclass panic hook and returns a message describing the panic.
+/// Used by generated code when a subclass method panics.
+#[doc(hidden)]
+pub fn report_subclass_panic(subclass: &str, method: &str, payload: Box<dyn Any + Send>) -> String {
+    let info = SubclassPanicInfo {
+        subclass,
+        method,
+        payload: payload.as_ref(),
+    };
+    if let Some(hook) = SUBCLASS_PANIC_HOOK
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .as_ref()
+    {
+        hook(&info);
+    }
+    format!(
+        "Rust subclass method {} of {} panicked: {}",
+        method,
+        subclass,
+        info.message().unwrap_or("Box<dyn Any>")
+    )
+}
+
+/// Calls [`CppSubclass::on_cpp_destroyed`] if the Rust side of a subclass
+/// still exists. Used by the generated C++ peer destructor.
+#[doc(hidden)]
+pub fn notify_cpp_peer_destroyed<T, CppPeer, S>(
+    holder: &CppSubclassRustPeerHolder<T, S>,
+    subclass: &str,
+) where
+    T: CppSubclass<CppPeer, S>,
+    CppPeer: CppSubclassCppPeer,
+    S: CppSubclassRustPeerStorage<T>,
+{
+    // Destructors are noexcept, so we must abort on panic.
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+        if let Some(strong) = holder.get() {
+            S::try_with_mut(&strong, |obj| {
+                obj.on_cpp_destroyed();
+                *obj.peer_holder_mut() = CppSubclassCppPeerHolder::Empty;
+            });
+        }
+    }));
+    if let Err(payload) = result {
+        abort_on_subclass_panic(subclass, "on_cpp_destroyed", payload)
+    }
+}
+
+/// Reports a panic in a subclass method and aborts. Used by generated code
+/// when a subclass method panics and can't throw a C++ exception.
+#[doc(hidden)]
+pub fn abort_on_subclass_panic(subclass: &str, method: &str, payload: Box<dyn Any + Send>) -> ! {
+    eprintln!("{}", report_subclass_panic(subclass, method, payload));
+    std::process::abort()
+}